@@ -1,6 +1,7 @@
 // icc profile reader
 use icc_profile::cms::transration::*;
 use icc_profile::utils::decoded_print;
+use icc_profile::cms::kpreserve::{KPreservation,KPreservingTransform};
 pub use icc_profile::iccprofile::*;
 
 use std::env;
@@ -33,6 +34,8 @@ pub fn main() -> std::io::Result<()> {
             return Ok(())
         };
 
+        let k_preserving = KPreservingTransform::new(&decoded,&decoded,0,KPreservation::BlackOnly)?;
+
         for c in 0..=20 { 
             for m in 0..=20 { 
                 for y in 0..=20 { 
@@ -43,10 +46,13 @@ pub fn main() -> std::io::Result<()> {
                         let k0 = ((k as usize * 255) / 20).clamp(0,255);
 
                         let (l,la,lb) = cmyk_to_lab_lut16(c0 as u8,m0 as u8,y0 as u8,k0 as u8,lut16);
-                        let (c1,m1,y1,k1) = lab_f64_to_cmyk_lut8_u8(l,la,la,lut8);
+                        let (c1,m1,y1,k1) = lab_f64_to_cmyk_lut8_u8(l,la,lb,lut8);
+                        let mut preserved = [0;4];
+                        k_preserving.transform_u8(&[c0 as u8,m0 as u8,y0 as u8,k0 as u8],&mut preserved)?;
                         println!("CMYK {} {} {} {}",c0,m0,y0,k0);
                         println!("Lab {} {} {}",l,la,lb);
                         println!("CMYK {} {} {} {}",c1,m1,y1,k1);
+                        println!("CMYK (K preserved) {} {} {} {}",preserved[0],preserved[1],preserved[2],preserved[3]);
                         println!("");
                     }
                 }
@@ -0,0 +1,10 @@
+// cargo +nightly fuzz run decode
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use icc_profile::{DecodedICCProfile,DecodeOptions};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(decoded) = DecodedICCProfile::new_with_options(data,&DecodeOptions::untrusted()) {
+        let _ = decoded.to_string_with_verbose(1);
+    }
+});
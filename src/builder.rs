@@ -0,0 +1,247 @@
+//! ICC Profile writer
+//! ICCProfileBuilder serializes a header and tags to profile data.
+//! Tags are 4 byte aligned, identical tag data is stored once and shared,
+//! and the profile ID is computed for version 2.4 or later profiles and for sources that had an ID.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn gray_profile() -> std::io::Result<Vec<u8>> {
+//!     let mut builder = ICCProfileBuilder::new(
+//!         u32::from_be_bytes(*b"mntr"),u32::from_be_bytes(*b"GRAY"),u32::from_be_bytes(*b"XYZ "));
+//!     builder.tag("desc",&Data::ASCII("gray 2.2".to_string()),"mluc")?
+//!         .tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642,1.0,0.8249)),"XYZ ")?
+//!         .tag("kTRC",&Data::Curve(vec![0x0233]),"curv")?;
+//!     builder.build()
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use std::time::{SystemTime,UNIX_EPOCH};
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::encoder::signature_bytes;
+use crate::utils::read_u128;
+
+#[derive(Debug,Clone)]
+pub struct ICCProfileBuilder {
+    header: Vec<u8>,
+    tags: Vec<(String,Vec<u8>)>,
+}
+
+impl ICCProfileBuilder {
+    /// new creates a version 4.4 profile with a D50 illuminant
+    pub fn new(device_class:u32,color_space:u32,pcs:u32) -> Self {
+        let mut builder = Self {
+            header: vec![0;128],
+            tags: vec![],
+        };
+        builder.version(0x04400000)
+            .device_class(device_class)
+            .color_space(color_space)
+            .pcs(pcs)
+            .illuminate(0.9642,1.0,0.8249)
+            .create_date_now();
+        builder.header[36..40].copy_from_slice(b"acsp");
+        builder
+    }
+
+    /// from_bytes copies the header and the raw tags of existing profile data
+    pub fn from_bytes(data:&[u8]) -> Result<Self> {
+        let directory = TagDirectory::new(data)?;
+        let mut tags = Vec::with_capacity(directory.len());
+        for entry in &directory {
+            let raw = entry.raw(data)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,format!("tag {} is out of the profile",entry.signature)))?;
+            tags.push((entry.signature.to_string(),raw.to_vec()));
+        }
+        Ok(Self {
+            header: data[..128].to_vec(),
+            tags,
+        })
+    }
+
+    pub(crate) fn from_parts(header:Vec<u8>,tags:Vec<(String,Vec<u8>)>) -> Self {
+        Self {
+            header,
+            tags,
+        }
+    }
+
+    fn set_u32(&mut self,ptr:usize,val:u32) -> &mut Self {
+        self.header[ptr..ptr + 4].copy_from_slice(&val.to_be_bytes());
+        self
+    }
+
+    pub fn cmmid(&mut self,cmmid:u32) -> &mut Self { self.set_u32(4,cmmid) }
+    pub fn version(&mut self,version:u32) -> &mut Self { self.set_u32(8,version) }
+    pub fn device_class(&mut self,device_class:u32) -> &mut Self { self.set_u32(12,device_class) }
+    pub fn color_space(&mut self,color_space:u32) -> &mut Self { self.set_u32(16,color_space) }
+    pub fn pcs(&mut self,pcs:u32) -> &mut Self { self.set_u32(20,pcs) }
+    pub fn platform(&mut self,platform:u32) -> &mut Self { self.set_u32(40,platform) }
+    pub fn flags(&mut self,flags:u32) -> &mut Self { self.set_u32(44,flags) }
+    pub fn manufacturer(&mut self,manufacturer:u32) -> &mut Self { self.set_u32(48,manufacturer) }
+    pub fn model(&mut self,model:u32) -> &mut Self { self.set_u32(52,model) }
+    pub fn rendering_intent(&mut self,rendering_intent:u32) -> &mut Self { self.set_u32(64,rendering_intent) }
+    pub fn creator(&mut self,creator:u32) -> &mut Self { self.set_u32(80,creator) }
+
+    pub fn attributes(&mut self,attributes:u64) -> &mut Self {
+        self.header[56..64].copy_from_slice(&attributes.to_be_bytes());
+        self
+    }
+
+    pub fn create_date(&mut self,year:u16,month:u16,day:u16,hour:u16,minute:u16,second:u16) -> &mut Self {
+        for (i,val) in [year,month,day,hour,minute,second].iter().enumerate() {
+            self.header[24 + i * 2..26 + i * 2].copy_from_slice(&val.to_be_bytes());
+        }
+        self
+    }
+
+    /// create_date_now sets the current UTC time
+    pub fn create_date_now(&mut self) -> &mut Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let days = (seconds / 86400) as i64;
+        let time = seconds % 86400;
+        // civil date from days since 1970-01-01
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        self.create_date(year as u16,month as u16,day as u16,
+            (time / 3600) as u16,(time / 60 % 60) as u16,(time % 60) as u16)
+    }
+
+    /// illuminate sets the PCS illuminant, normally D50
+    pub fn illuminate(&mut self,x:f64,y:f64,z:f64) -> &mut Self {
+        let xyz = XYZNumber::from_f64(x,y,z);
+        self.header[68..72].copy_from_slice(&xyz.x.to_be_bytes());
+        self.header[72..76].copy_from_slice(&xyz.y.to_be_bytes());
+        self.header[76..80].copy_from_slice(&xyz.z.to_be_bytes());
+        self
+    }
+
+    /// tag encodes data as `data_type` and stores it as `signature`, replacing an existing tag
+    pub fn tag(&mut self,signature:&str,data:&Data,data_type:&str) -> Result<&mut Self> {
+        let raw = data.encode(data_type)?;
+        self.raw_tag(signature,raw)
+    }
+
+    /// raw_tag stores raw tag data including the type signature
+    pub fn raw_tag(&mut self,signature:&str,raw:Vec<u8>) -> Result<&mut Self> {
+        signature_bytes(signature)?;
+        if raw.len() < 8 {
+            return Err(Error::new(ErrorKind::InvalidInput,format!("tag {} data length {} is too short",signature,raw.len())))
+        }
+        match self.tags.iter_mut().find(|(name,_)| name == signature) {
+            Some(tag) => tag.1 = raw,
+            None => self.tags.push((signature.to_string(),raw)),
+        }
+        Ok(self)
+    }
+
+    pub fn remove_tag(&mut self,signature:&str) -> &mut Self {
+        self.tags.retain(|(name,_)| name != signature);
+        self
+    }
+
+    pub fn has_tag(&self,signature:&str) -> bool {
+        self.tags.iter().any(|(name,_)| name == signature)
+    }
+
+    pub(crate) fn is_v4(&self) -> bool {
+        read_u32_be(&self.header,8) >= 0x04000000
+    }
+
+    /// build serializes the profile and sets the length and the profile ID
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let mut data = self.header.clone();
+        write_u32_be(self.tags.len() as u32,&mut data);
+        let table = data.len();
+        data.resize(table + self.tags.len() * 12,0);
+
+        let mut blocks :Vec<(usize,usize)> = vec![];
+        for (i,(signature,raw)) in self.tags.iter().enumerate() {
+            let shared = blocks.iter()
+                .find(|(offset,size)| *size == raw.len() && data[*offset..*offset + *size] == raw[..]);
+            let offset = match shared {
+                Some((offset,_)) => *offset,
+                None => {
+                    data.resize((data.len() + 3) & !3,0);
+                    let offset = data.len();
+                    data.extend_from_slice(raw);
+                    blocks.push((offset,raw.len()));
+                    offset
+                }
+            };
+            let ptr = table + i * 12;
+            data[ptr..ptr + 4].copy_from_slice(&signature_bytes(signature)?);
+            data[ptr + 4..ptr + 8].copy_from_slice(&(offset as u32).to_be_bytes());
+            data[ptr + 8..ptr + 12].copy_from_slice(&(raw.len() as u32).to_be_bytes());
+        }
+        data.resize((data.len() + 3) & !3,0);
+        let length = u32::try_from(data.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput,"profile is larger than 4GiB"))?;
+        data[0..4].copy_from_slice(&length.to_be_bytes());
+
+        // the ID was defined in 2.4, keep it also for older sources that carry one
+        let has_id = read_u128(&self.header,84) != 0;
+        data[84..100].fill(0);
+        if has_id || read_u32_be(&data,8) >= 0x02400000 {
+            let profile_id = compute_profile_id(&data)?;
+            data[84..100].copy_from_slice(&profile_id.to_be_bytes());
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn profile_id() {
+        assert_eq!(crate::md5::md5(b""),0xd41d8cd98f00b204e9800998ecf8427e);
+        assert_eq!(crate::md5::md5(b"abc"),0x900150983cd24fb0d6963f7d28e17f72);
+        assert_eq!(crate::md5::md5(&[b'a';64]),0x014842d480b571495a4a0363793f7367);
+
+        let base = sample();
+        assert_eq!(verify_profile_id(&base).unwrap(),Option::None);
+        let mut builder = crate::ICCProfileBuilder::from_bytes(&base).unwrap();
+        builder.version(0x04400000).rendering_intent(1);
+        builder.tag("gTRC",&Data::Curve(vec![0,0xffff]),"curv").unwrap()
+            .tag("desc",&Data::ASCII("rewritten".to_string()),"mluc").unwrap();
+        let data = builder.build().unwrap();
+        assert_eq!(data.len() % 4,0);
+        assert_eq!(verify_profile_id(&data).unwrap(),Some(true));
+        let mut flagged = data.clone();
+        flagged[47] = 1;
+        flagged[67] = 3;
+        assert_eq!(verify_profile_id(&flagged).unwrap(),Some(true));
+        flagged[200] ^= 1;
+        assert_eq!(verify_profile_id(&flagged).unwrap(),Some(false));
+
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        assert_eq!(decoded.directory.get("gTRC").unwrap().shared_with,["rTRC"]);
+        assert!(matches!(decoded.tags.get("desc"),Some(Data::MultiLocalizedUnicode(mluc)) if mluc.as_string() == "rewritten"));
+        assert_eq!(decoded.tags.len(),DecodedICCProfile::new(&base).unwrap().tags.len() + 1);
+
+        // 2.4 profiles and older sources with an ID keep a valid ID, older ones without stay unset
+        let v2 = crate::ICCProfileBuilder::from_bytes(&base).unwrap().build().unwrap();
+        assert_eq!(verify_profile_id(&v2).unwrap(),Option::None);
+        let mut builder = crate::ICCProfileBuilder::from_bytes(&base).unwrap();
+        builder.version(0x02400000);
+        let v24 = builder.build().unwrap();
+        assert_eq!(verify_profile_id(&v24).unwrap(),Some(true));
+        let mut builder = crate::ICCProfileBuilder::from_bytes(&v24).unwrap();
+        builder.version(0x02100000).rendering_intent(2);
+        assert_eq!(verify_profile_id(&builder.build().unwrap()).unwrap(),Some(true));
+        let (repaired,_) = crate::repair::repair(&v24).unwrap();
+        assert_eq!(verify_profile_id(&repaired).unwrap(),Some(true));
+    }
+}
@@ -0,0 +1,155 @@
+//! Coding-independent code points 'cicp' (ICC v4.4)
+//! The tag stores the ITU-T H.273 quadruple used by video, AVIF and HEIF `nclx`.
+//! CicpType can build a matching RGB profile or a decoding transform from `cms::h273`.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn bt2100_pq() -> std::io::Result<Vec<u8>> {
+//!     let cicp = CicpType::new(9,16,9,false);
+//!     let transform = cicp.transform().unwrap();
+//!     let white = transform.to_linear_rgb([940,512,512],10);
+//!     assert!((white[1] - 1.0).abs() < 1e-3);
+//!     cicp.to_profile("BT.2100 PQ","No copyright")
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::builder::ICCProfileBuilder;
+use crate::cms::h273::{CicpTransform,Primaries,TransferFunction};
+use crate::utils::bound_check;
+
+const RGB: u32 = 0x52474220;    // 'RGB '
+const XYZ: u32 = 0x58595a20;    // 'XYZ '
+const MNTR: u32 = 0x6d6e7472;   // 'mntr'
+
+/// cicpType, code points are defined in ITU-T H.273
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct CicpType {
+    pub color_primaries: u8,
+    pub transfer_characteristics: u8,
+    /// 0 for RGB profiles, other values describe Y'CbCr encoded data
+    pub matrix_coefficients: u8,
+    /// 1 full range, 0 narrow (video) range
+    pub video_full_range_flag: u8,
+}
+
+impl CicpType {
+    pub fn new(color_primaries:u8,transfer_characteristics:u8,matrix_coefficients:u8,full_range:bool) -> Self {
+        Self {
+            color_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            video_full_range_flag: full_range as u8,
+        }
+    }
+
+    pub fn full_range(&self) -> bool {
+        self.video_full_range_flag != 0
+    }
+
+    /// read reads tag data starting with the type signature
+    pub(crate) fn read(data:&[u8]) -> Result<Self> {
+        bound_check(data,8,4)?;
+        Ok(Self {
+            color_primaries: data[8],
+            transfer_characteristics: data[9],
+            matrix_coefficients: data[10],
+            video_full_range_flag: data[11],
+        })
+    }
+
+    /// write appends the tag body after the type signature
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) {
+        buf.extend_from_slice(&[self.color_primaries,self.transfer_characteristics,
+            self.matrix_coefficients,self.video_full_range_flag]);
+    }
+
+    /// transform returns None for unspecified or unsupported code points
+    pub fn transform(&self) -> Option<CicpTransform> {
+        CicpTransform::new(self.color_primaries,self.transfer_characteristics,self.matrix_coefficients,self.full_range())
+    }
+
+    /// to_profile builds a v4.4 RGB display profile with colorants, TRCs and this cicp tag.
+    /// The profile describes R'G'B', so the stored cicp has matrix coefficients 0 and full range.
+    /// PQ, HLG and logarithmic transfers are sampled, PQ 1.0 is 10000 cd/m2.
+    pub fn to_profile(&self,description:&str,copyright:&str) -> Result<Vec<u8>> {
+        let unsupported = || Error::new(ErrorKind::Unsupported,format!("cicp {}/{} has no matching profile",
+            self.color_primaries,self.transfer_characteristics));
+        let primaries = Primaries::from_code(self.color_primaries).ok_or_else(unsupported)?;
+        let transfer = TransferFunction::from_code(self.transfer_characteristics).ok_or_else(unsupported)?;
+        let matrix = primaries.rgb_to_pcs_xyz().ok_or_else(unsupported)?;
+        let white = primaries.white_xyz();
+        let chad = crate::cms::ColorMatrix3D::chromatic_adaptation(white,(0.9642,1.0,0.8249)).ok_or_else(unsupported)?;
+        let trc = match transfer.parametric() {
+            Some((funtion_type,vals)) => (Data::ParametricCurve(ParametricCurve {
+                funtion_type,
+                vals: vals.iter().map(|val| S15Fixed16Number::from_f64(*val)).collect(),
+            }),"para"),
+            None => (Data::Curve((0..1024).map(|i| {
+                let val = transfer.to_linear(i as f64 / 1023.0);
+                (val.clamp(0.0,1.0) * 65535.0).round() as u16
+            }).collect()),"curv"),
+        };
+
+        let mut builder = ICCProfileBuilder::new(MNTR,RGB,XYZ);
+        builder.version(0x04400000);
+        builder.tag("desc",&Data::ASCII(description.to_string()),"mluc")?
+            .tag("cprt",&Data::ASCII(copyright.to_string()),"mluc")?
+            .tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642,1.0,0.8249)),"XYZ ")?
+            .tag("chad",&Data::S15Fixed16NumberArray(chad.e.iter().map(|val| S15Fixed16Number::from_f64(*val)).collect()),"sf32")?;
+        for (i,signature) in ["rXYZ","gXYZ","bXYZ"].iter().enumerate() {
+            let xyz = XYZNumber::from_f64(matrix.e[i],matrix.e[3 + i],matrix.e[6 + i]);
+            builder.tag(signature,&Data::XYZNumber(xyz),"XYZ ")?;
+        }
+        for signature in ["rTRC","gTRC","bTRC"] {
+            builder.tag(signature,&trc.0,trc.1)?;
+        }
+        let cicp = Self::new(self.color_primaries,self.transfer_characteristics,0,true);
+        builder.tag("cicp",&Data::Cicp(cicp),"cicp")?;
+        builder.build()
+    }
+}
+
+impl DecodedICCProfile {
+    /// cicp returns the code points of the cicp tag
+    pub fn cicp(&self) -> Option<CicpType> {
+        match self.tags.get("cicp") {
+            Some(Data::Cicp(cicp)) => Some(*cicp),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cicp_profile() {
+        use crate::cms::h273::*;
+        let data = CicpType::new(1,13,0,true).to_profile("sRGB","No copyright").unwrap();
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        assert_eq!(decoded.version,0x04400000);
+        assert_eq!(decoded.cicp(),Some(CicpType::new(1,13,0,true)));
+        match decoded.tags.get("rXYZ") {
+            Some(Data::XYZNumberArray(xyz)) => {
+                let xyz = &xyz[0];
+                assert!((xyz.x.as_f64() - 0.4361).abs() < 1e-3);
+                assert!((xyz.y.as_f64() - 0.2225).abs() < 1e-3);
+            },
+            other => panic!("{:?}",other),
+        }
+        assert!(crate::validator::validate(&data).iter().all(|finding| finding.check != crate::validator::Check::TagType));
+
+        let transform = CicpType::new(1,1,1,false).transform().unwrap();
+        let white = transform.to_linear_rgb([235,128,128],8);
+        assert!(white.iter().all(|val| (val - 1.0).abs() < 1e-6));
+        let xyz = transform.to_pcs_xyz([235,128,128],8);
+        assert!((xyz[0] - 0.9642).abs() < 1e-3 && (xyz[2] - 0.8249).abs() < 1e-3);
+        assert!(matches!(matrix_coefficients(9),Some(crate::cms::transration::RGBToYUVCoefficient::Other(_))));
+        assert!((TransferFunction::Pq.to_linear(1.0) - 1.0).abs() < 1e-9);
+        assert!(CicpType::new(2,2,2,false).transform().is_none());
+    }
+}
@@ -0,0 +1,83 @@
+//! Color difference ΔE between two L*a*b* colors
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum DeltaE {
+    /// CIE 1976, euclidean distance in L*a*b*
+    Cie76,
+    /// CIEDE2000
+    Ciede2000,
+}
+
+impl DeltaE {
+    pub fn compute(&self,lab1:[f64;3],lab2:[f64;3]) -> f64 {
+        match self {
+            DeltaE::Cie76 => delta_e76(lab1,lab2),
+            DeltaE::Ciede2000 => delta_e2000(lab1,lab2),
+        }
+    }
+}
+
+pub fn delta_e76(lab1:[f64;3],lab2:[f64;3]) -> f64 {
+    let dl = lab1[0] - lab2[0];
+    let da = lab1[1] - lab2[1];
+    let db = lab1[2] - lab2[2];
+    (dl * dl + da * da + db * db).sqrt()
+}
+
+/// delta_e2000 is CIEDE2000 with kL = kC = kH = 1
+pub fn delta_e2000(lab1:[f64;3],lab2:[f64;3]) -> f64 {
+    let [l1,a1,b1] = lab1;
+    let [l2,a2,b2] = lab2;
+    let c_mean = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c7 = c_mean.powi(7);
+    let g = 0.5 * (1.0 - (c7 / (c7 + 25.0_f64.powi(7))).sqrt());
+    let a1 = a1 * (1.0 + g);
+    let a2 = a2 * (1.0 + g);
+    let c1 = (a1 * a1 + b1 * b1).sqrt();
+    let c2 = (a2 * a2 + b2 * b2).sqrt();
+    let hue = |a:f64,b:f64| if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+    let h1 = hue(a1,b1);
+    let h2 = hue(a2,b2);
+
+    let dl = l2 - l1;
+    let dc = c2 - c1;
+    let dh = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else {
+        h2 - h1 + 360.0
+    };
+    let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).to_radians().sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let d_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let c7 = c_mean.powi(7);
+    let rc = 2.0 * (c7 / (c7 + 25.0_f64.powi(7))).sqrt();
+    let l50 = (l_mean - 50.0).powi(2);
+    let sl = 1.0 + 0.015 * l50 / (20.0 + l50).sqrt();
+    let sc = 1.0 + 0.045 * c_mean;
+    let sh = 1.0 + 0.015 * c_mean * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let dl = dl / sl;
+    let dc = dc / sc;
+    let dh = dh / sh;
+    (dl * dl + dc * dc + dh * dh + rt * dc * dh).sqrt()
+}
@@ -0,0 +1,157 @@
+//! Out of gamut detection of output profiles
+//! GamutCheck evaluates the gamut tag 'gamt' of an output profile, 0 is in gamut and non-zero is out of gamut.
+//! Profiles without gamt are checked by converting PCS to device and back and comparing ΔE with a threshold.
+//! ```
+//! use icc_profile::*;
+//! use icc_profile::cms::gamut::GamutCheck;
+//! use icc_profile::cms::transform::Transform;
+//!
+//! fn gamut_warnings(source:&DecodedICCProfile,printer:&DecodedICCProfile,pixels:&[u8]) -> std::io::Result<Vec<bool>> {
+//!     let check = GamutCheck::new(printer,1)?;
+//!     check.gamut_mask_u8(&Transform::to_pcs(source,1)?,pixels)
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::cms::delta_e::DeltaE;
+use crate::cms::pcs::{PcsEncoding,lab_to_xyz,xyz_to_lab};
+use crate::cms::transform::Transform;
+
+const XYZ: u32 = 0x58595a20;    // 'XYZ '
+
+enum Method<'a> {
+    /// xyz_input applies the lut8/lut16 matrix
+    Tag { lut:&'a Data,encoding:PcsEncoding,xyz_input:bool },
+    /// PCS to device to PCS
+    RoundTrip(Transform<'a>),
+}
+
+pub struct GamutCheck<'a> {
+    method: Method<'a>,
+    delta_e: DeltaE,
+    threshold: f64,
+}
+
+impl<'a> GamutCheck<'a> {
+    /// new uses the gamt tag of `output`, or the round trip of `rendering_intent` without gamt.
+    /// The round trip default is ΔE2000 above 2.0.
+    pub fn new(output:&'a DecodedICCProfile,rendering_intent:u32) -> Result<Self> {
+        let method = match output.tags.get("gamt") {
+            Some(lut) if lut.lut_channels().map(|(input,output)| input == 3 && output >= 1).unwrap_or(false) => {
+                let encoding = PcsEncoding::for_tag(lut,output.pcs,output.version);
+                Method::Tag { lut,encoding,xyz_input: output.pcs == XYZ }
+            },
+            Some(_) => return Err(Error::new(ErrorKind::InvalidData,"gamt must be a lut of 3 input channels")),
+            None => Method::RoundTrip(Self::round_trip(output,rendering_intent)?),
+        };
+        Ok(Self { method,delta_e: DeltaE::Ciede2000,threshold: 2.0 })
+    }
+
+    /// new_round_trip ignores the gamt tag and always compares the PCS to device to PCS conversion
+    pub fn new_round_trip(output:&'a DecodedICCProfile,rendering_intent:u32) -> Result<Self> {
+        let method = Method::RoundTrip(Self::round_trip(output,rendering_intent)?);
+        Ok(Self { method,delta_e: DeltaE::Ciede2000,threshold: 2.0 })
+    }
+
+    fn round_trip(output:&'a DecodedICCProfile,rendering_intent:u32) -> Result<Transform<'a>> {
+        Transform::from_pcs(output,rendering_intent)?.then(Transform::to_pcs(output,rendering_intent)?)
+    }
+
+    /// threshold sets the ΔE above which a round trip color is out of gamut
+    pub fn threshold(mut self,delta_e:DeltaE,threshold:f64) -> Self {
+        self.delta_e = delta_e;
+        self.threshold = threshold;
+        self
+    }
+
+    /// uses_gamt returns true when the gamt tag is evaluated
+    pub fn uses_gamt(&self) -> bool {
+        matches!(self.method,Method::Tag { .. })
+    }
+
+    /// is_in_gamut checks a D50 PCS L*a*b* color
+    pub fn is_in_gamut(&self,lab:[f64;3]) -> bool {
+        self.check(lab_to_xyz(lab),lab)
+    }
+
+    /// is_in_gamut_xyz checks a D50 PCS XYZ color, Y = 1.0 for the white
+    pub fn is_in_gamut_xyz(&self,xyz:[f64;3]) -> bool {
+        self.check(xyz,xyz_to_lab(xyz))
+    }
+
+    fn check(&self,xyz:[f64;3],lab:[f64;3]) -> bool {
+        match &self.method {
+            // interpolated outputs below half scale are taken as 0
+            Method::Tag { lut,encoding,xyz_input } => {
+                let input = encoding.from_xyz(xyz);
+                let output = if *xyz_input { lut.eval_lut_xyz(&input) } else { lut.eval_lut(&input) };
+                output.ok().and_then(|output| output.first().copied()).map(|val| val < 0.5).unwrap_or(false)
+            },
+            Method::RoundTrip(transform) => {
+                let pcs = transform.transform(&xyz);
+                let round_trip = xyz_to_lab([pcs[0],pcs[1],pcs[2]]);
+                self.delta_e.compute(lab,round_trip) <= self.threshold
+            },
+        }
+    }
+
+    /// gamut_mask_lab checks interleaved L*a*b* values, true is in gamut
+    pub fn gamut_mask_lab(&self,lab:&[f64]) -> Vec<bool> {
+        lab.chunks_exact(3).map(|lab| self.is_in_gamut([lab[0],lab[1],lab[2]])).collect()
+    }
+
+    /// gamut_mask_u8 converts interleaved 8 bit pixels to PCS with `source` and checks them, true is in gamut
+    pub fn gamut_mask_u8(&self,source:&Transform,pixels:&[u8]) -> Result<Vec<bool>> {
+        let mut pcs = vec![0.0;pixels.len() / source.input_channels().max(1) * 3];
+        let input :Vec<f64> = pixels.iter().map(|val| *val as f64 / 255.0).collect();
+        self.gamut_mask_f64(source,&input,&mut pcs)
+    }
+
+    /// gamut_mask_u16 converts interleaved 16 bit pixels to PCS with `source` and checks them, true is in gamut
+    pub fn gamut_mask_u16(&self,source:&Transform,pixels:&[u16]) -> Result<Vec<bool>> {
+        let mut pcs = vec![0.0;pixels.len() / source.input_channels().max(1) * 3];
+        let input :Vec<f64> = pixels.iter().map(|val| *val as f64 / 65535.0).collect();
+        self.gamut_mask_f64(source,&input,&mut pcs)
+    }
+
+    fn gamut_mask_f64(&self,source:&Transform,input:&[f64],pcs:&mut [f64]) -> Result<Vec<bool>> {
+        if source.output_channels() != 3 {
+            return Err(Error::new(ErrorKind::InvalidInput,"source transform must convert to PCS"))
+        }
+        source.transform_f64(input,pcs)?;
+        Ok(pcs.chunks_exact(3).map(|xyz| self.is_in_gamut_xyz([xyz[0],xyz[1],xyz[2]])).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn gamut_check() {
+        // lut8 gamt that marks a* above 0 as out of gamut
+        let gamt = lut8_fixture(3,1,2,&|p| vec![(p[1] * 255.0) as u8]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"GRAY"),u32::from_be_bytes(*b"Lab "));
+        builder.raw_tag("gamt",tag("mft1",&gamt)).unwrap();
+        let printer = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        let check = GamutCheck::new(&printer,1).unwrap();
+        assert!(check.uses_gamt());
+        assert!(check.is_in_gamut([50.0,-1.0,0.0]) && !check.is_in_gamut([50.0,1.0,0.0]));
+        assert!(check.is_in_gamut([50.0,-20.0,10.0]));
+        assert!(!check.is_in_gamut([50.0,60.0,10.0]));
+        assert_eq!(check.gamut_mask_lab(&[50.0,-20.0,0.0,50.0,60.0,0.0]),vec![true,false]);
+
+        // without gamt, sRGB round trip
+        let srgb = crate::CicpType::new(1,13,0,true).to_profile("sRGB","none").unwrap();
+        let srgb = DecodedICCProfile::new(&srgb).unwrap();
+        let check = GamutCheck::new(&srgb,1).unwrap();
+        assert!(!check.uses_gamt());
+        assert!(check.is_in_gamut([50.0,0.0,0.0]));
+        assert!(!check.is_in_gamut([50.0,120.0,-120.0]));
+        let mask = check.gamut_mask_u8(&Transform::to_pcs(&srgb,1).unwrap(),&[255,0,0,128,128,128]).unwrap();
+        assert_eq!(mask,vec![true,true]);
+    }
+}
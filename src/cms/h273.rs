@@ -0,0 +1,250 @@
+//! ITU-T H.273 coding-independent code points (CICP)
+//! Color primaries, transfer characteristics and matrix coefficients used by the ICC `cicp` tag,
+//! AVIF/HEIF `nclx` and video streams.
+
+use crate::cms::ColorMatrix3D;
+use crate::cms::transration::RGBToYUVCoefficient;
+
+/// CIE xy chromaticities of the primaries and the white point
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Primaries {
+    pub red: (f64,f64),
+    pub green: (f64,f64),
+    pub blue: (f64,f64),
+    pub white: (f64,f64),
+}
+
+const D65: (f64,f64) = (0.3127,0.3290);
+const C: (f64,f64) = (0.310,0.316);
+
+impl Primaries {
+    /// from_code returns the primaries of ColourPrimaries code points 1-12 and 22
+    pub fn from_code(code:u8) -> Option<Self> {
+        let (red,green,blue,white) = match code {
+            1 => ((0.640,0.330),(0.300,0.600),(0.150,0.060),D65),   // BT.709, sRGB
+            4 => ((0.67,0.33),(0.21,0.71),(0.14,0.08),C),           // BT.470 System M
+            5 => ((0.64,0.33),(0.29,0.60),(0.15,0.06),D65),         // BT.470 System B/G, BT.601 625
+            6 | 7 => ((0.630,0.340),(0.310,0.595),(0.155,0.070),D65),   // BT.601 525, SMPTE 240M
+            8 => ((0.681,0.319),(0.243,0.692),(0.145,0.049),C),     // generic film
+            9 => ((0.708,0.292),(0.170,0.797),(0.131,0.046),D65),   // BT.2020, BT.2100
+            10 => ((1.0,0.0),(0.0,1.0),(0.0,0.0),(1.0 / 3.0,1.0 / 3.0)),    // SMPTE ST 428-1 (CIE XYZ)
+            11 => ((0.680,0.320),(0.265,0.690),(0.150,0.060),(0.314,0.351)),  // SMPTE RP 431-2 (DCI-P3)
+            12 => ((0.680,0.320),(0.265,0.690),(0.150,0.060),D65),  // SMPTE EG 432-1 (Display P3)
+            22 => ((0.630,0.340),(0.295,0.605),(0.155,0.077),D65),  // EBU Tech. 3213-E
+            _ => return None,
+        };
+        Some(Self { red,green,blue,white })
+    }
+
+    /// white_xyz returns the white point with Y = 1
+    pub fn white_xyz(&self) -> (f64,f64,f64) {
+        let (x,y) = self.white;
+        (x / y,1.0,(1.0 - x - y) / y)
+    }
+
+    /// rgb_to_xyz returns the linear RGB to XYZ matrix for the native white point
+    pub fn rgb_to_xyz(&self) -> Option<ColorMatrix3D> {
+        let xyz = |(x,y):(f64,f64)| (x / y,1.0,(1.0 - x - y) / y);
+        let (r,g,b) = (xyz(self.red),xyz(self.green),xyz(self.blue));
+        if !(r.0.is_finite() && g.0.is_finite() && b.0.is_finite()) {
+            return None
+        }
+        let primaries = ColorMatrix3D::from(&[r.0,g.0,b.0,r.1,g.1,b.1,r.2,g.2,b.2])?;
+        let inverse = ColorMatrix3D::invese(&primaries)?;
+        let white = self.white_xyz();
+        let (sr,sg,sb) = inverse.convert_3d(white.0,white.1,white.2);
+        ColorMatrix3D::from(&[r.0 * sr,g.0 * sg,b.0 * sb,r.1 * sr,g.1 * sg,b.1 * sb,r.2 * sr,g.2 * sg,b.2 * sb])
+    }
+
+    /// rgb_to_pcs_xyz returns the matrix to D50 PCS XYZ, adapted with Bradford
+    pub fn rgb_to_pcs_xyz(&self) -> Option<ColorMatrix3D> {
+        let adaptation = ColorMatrix3D::chromatic_adaptation(self.white_xyz(),(0.9642,1.0,0.8249))?;
+        Some(adaptation.multiply(&self.rgb_to_xyz()?))
+    }
+}
+
+/// TransferCharacteristics, the non-linear to linear (EOTF-like) direction
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum TransferFunction {
+    /// 1, 6, 14, 15 BT.709, BT.601, BT.2020
+    Bt709,
+    /// 4 gamma 2.2, 5 gamma 2.8
+    Gamma(f64),
+    /// 7 SMPTE 240M
+    Smpte240,
+    /// 8
+    Linear,
+    /// 9 logarithmic 100:1
+    Log100,
+    /// 10 logarithmic 100 * sqrt(10):1
+    Log316,
+    /// 11 IEC 61966-2-4 (xvYCC), BT.709 extended to negative values
+    Xvycc,
+    /// 13 IEC 61966-2-1 (sRGB)
+    Srgb,
+    /// 16 SMPTE ST 2084 (PQ), 1.0 is 10000 cd/m2
+    Pq,
+    /// 17 SMPTE ST 428-1
+    Smpte428,
+    /// 18 ARIB STD-B67 (HLG) inverse OETF, scene linear without the OOTF
+    Hlg,
+}
+
+impl TransferFunction {
+    pub fn from_code(code:u8) -> Option<Self> {
+        let transfer = match code {
+            1 | 6 | 14 | 15 => TransferFunction::Bt709,
+            4 => TransferFunction::Gamma(2.2),
+            5 => TransferFunction::Gamma(2.8),
+            7 => TransferFunction::Smpte240,
+            8 => TransferFunction::Linear,
+            9 => TransferFunction::Log100,
+            10 => TransferFunction::Log316,
+            11 => TransferFunction::Xvycc,
+            13 => TransferFunction::Srgb,
+            16 => TransferFunction::Pq,
+            17 => TransferFunction::Smpte428,
+            18 => TransferFunction::Hlg,
+            _ => return None,
+        };
+        Some(transfer)
+    }
+
+    /// to_linear converts a non-linear value 0.0-1.0 to linear light
+    pub fn to_linear(&self,val:f64) -> f64 {
+        let bt709 = |v:f64| if v < 0.081 { v / 4.5 } else { ((v + 0.099) / 1.099).powf(1.0 / 0.45) };
+        match self {
+            TransferFunction::Bt709 => bt709(val.max(0.0)),
+            TransferFunction::Gamma(gamma) => val.max(0.0).powf(*gamma),
+            TransferFunction::Smpte240 => {
+                let v = val.max(0.0);
+                if v < 0.0913 { v / 4.0 } else { ((v + 0.1115) / 1.1115).powf(1.0 / 0.45) }
+            },
+            TransferFunction::Linear => val,
+            TransferFunction::Log100 => if val <= 0.0 { 0.0 } else { 10.0_f64.powf((val - 1.0) * 2.0) },
+            TransferFunction::Log316 => if val <= 0.0 { 0.0 } else { 10.0_f64.powf((val - 1.0) * 2.5) },
+            TransferFunction::Xvycc => bt709(val.abs()).copysign(val),
+            TransferFunction::Srgb => {
+                let v = val.max(0.0);
+                if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+            },
+            TransferFunction::Pq => {
+                let (m1,m2) = (2610.0 / 16384.0,2523.0 / 4096.0 * 128.0);
+                let (c1,c2,c3) = (3424.0 / 4096.0,2413.0 / 4096.0 * 32.0,2392.0 / 4096.0 * 32.0);
+                let e = val.clamp(0.0,1.0).powf(1.0 / m2);
+                ((e - c1).max(0.0) / (c2 - c3 * e)).powf(1.0 / m1)
+            },
+            TransferFunction::Smpte428 => 52.37 / 48.0 * val.max(0.0).powf(2.6),
+            TransferFunction::Hlg => {
+                let a :f64 = 0.17883277;
+                let b = 1.0 - 4.0 * a;
+                let c = 0.5 - a * (4.0 * a).ln();
+                let v = val.max(0.0);
+                if v <= 0.5 { v * v / 3.0 } else { (((v - c) / a).exp() + b) / 12.0 }
+            },
+        }
+    }
+
+    /// parametric returns the ICC parametric curve type and parameters when the function has one
+    pub fn parametric(&self) -> Option<(u16,Vec<f64>)> {
+        match self {
+            TransferFunction::Bt709 => Some((3,vec![1.0 / 0.45,1.0 / 1.099,0.099 / 1.099,1.0 / 4.5,0.081])),
+            TransferFunction::Smpte240 => Some((3,vec![1.0 / 0.45,1.0 / 1.1115,0.1115 / 1.1115,1.0 / 4.0,0.0913])),
+            TransferFunction::Srgb => Some((3,vec![2.4,1.0 / 1.055,0.055 / 1.055,1.0 / 12.92,0.04045])),
+            TransferFunction::Gamma(gamma) => Some((0,vec![*gamma])),
+            TransferFunction::Linear => Some((0,vec![1.0])),
+            _ => None,
+        }
+    }
+}
+
+/// luma coefficients (Kr,Kb) of MatrixCoefficients code points
+pub fn luma_coefficients(code:u8) -> Option<(f64,f64)> {
+    match code {
+        1 => Some((0.2126,0.0722)),         // BT.709
+        4 => Some((0.30,0.11)),             // FCC
+        5 | 6 => Some((0.299,0.114)),       // BT.601
+        7 => Some((0.212,0.087)),           // SMPTE 240M
+        9 => Some((0.2627,0.0593)),         // BT.2020 non-constant luminance
+        _ => None,
+    }
+}
+
+/// matrix_coefficients returns the RGB to Y'CbCr matrix of a MatrixCoefficients code point.
+/// 0 (identity, GBR) and constant luminance codes have no matrix.
+pub fn matrix_coefficients(code:u8) -> Option<RGBToYUVCoefficient> {
+    match code {
+        1 => Some(RGBToYUVCoefficient::Bt709),
+        5 | 6 => Some(RGBToYUVCoefficient::Bt601),
+        _ => {
+            let (kr,kb) = luma_coefficients(code)?;
+            let kg = 1.0 - kr - kb;
+            let matrix = ColorMatrix3D::from(&[
+                kr,kg,kb,
+                -kr / (2.0 * (1.0 - kb)),-kg / (2.0 * (1.0 - kb)),0.5,
+                0.5,-kg / (2.0 * (1.0 - kr)),-kb / (2.0 * (1.0 - kr))])?;
+            Some(RGBToYUVCoefficient::Other(matrix))
+        }
+    }
+}
+
+/// CicpTransform decodes code values of a CICP quadruple to linear RGB and D50 PCS XYZ
+#[derive(Clone)]
+pub struct CicpTransform {
+    pub primaries: Primaries,
+    pub transfer: TransferFunction,
+    /// Y'CbCr to R'G'B', None for code 0 (G'B'R')
+    pub matrix: Option<ColorMatrix3D>,
+    pub full_range: bool,
+    rgb_to_pcs: ColorMatrix3D,
+}
+
+impl CicpTransform {
+    /// new returns None for unspecified, reserved or unsupported code points
+    pub fn new(color_primaries:u8,transfer_characteristics:u8,matrix_coefficients:u8,full_range:bool) -> Option<Self> {
+        let primaries = Primaries::from_code(color_primaries)?;
+        let transfer = TransferFunction::from_code(transfer_characteristics)?;
+        let matrix = match matrix_coefficients {
+            0 => None,
+            code => Some(ColorMatrix3D::invese(&self::matrix_coefficients(code)?.get())?),
+        };
+        let rgb_to_pcs = primaries.rgb_to_pcs_xyz()?;
+        Some(Self { primaries,transfer,matrix,full_range,rgb_to_pcs })
+    }
+
+    /// normalize converts code values of `bit_depth` bits to Y' 0.0-1.0 and Cb,Cr -0.5-0.5,
+    /// or to G'B'R' 0.0-1.0 without a matrix
+    pub fn normalize(&self,code:[u16;3],bit_depth:u32) -> [f64;3] {
+        let scale = (1_u32 << bit_depth.clamp(8,16).saturating_sub(8)) as f64;
+        let max = scale * 256.0 - 1.0;
+        let mut vals = [0.0;3];
+        for (i,val) in vals.iter_mut().enumerate() {
+            let v = code[i] as f64;
+            let chroma = i > 0 && self.matrix.is_some();
+            *val = match (self.full_range,chroma) {
+                (true,false) => v / max,
+                (true,true) => (v - 128.0 * scale) / max,
+                (false,false) => (v - 16.0 * scale) / (219.0 * scale),
+                (false,true) => (v - 128.0 * scale) / (224.0 * scale),
+            };
+        }
+        vals
+    }
+
+    /// to_linear_rgb returns linear R,G,B, 1.0 is the nominal white (10000 cd/m2 for PQ)
+    pub fn to_linear_rgb(&self,code:[u16;3],bit_depth:u32) -> [f64;3] {
+        let vals = self.normalize(code,bit_depth);
+        let (r,g,b) = match &self.matrix {
+            Some(matrix) => matrix.convert_3d(vals[0],vals[1],vals[2]),
+            None => (vals[2],vals[0],vals[1]),
+        };
+        [self.transfer.to_linear(r),self.transfer.to_linear(g),self.transfer.to_linear(b)]
+    }
+
+    /// to_pcs_xyz returns D50 XYZ
+    pub fn to_pcs_xyz(&self,code:[u16;3],bit_depth:u32) -> [f64;3] {
+        let [r,g,b] = self.to_linear_rgb(code,bit_depth);
+        let (x,y,z) = self.rgb_to_pcs.convert_3d(r,g,b);
+        [x,y,z]
+    }
+}
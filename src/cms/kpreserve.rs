@@ -0,0 +1,247 @@
+//! K-preserving CMYK to CMYK transforms
+//! `BlackOnly` keeps K-only colors (text, gray) on the K channel of the output.
+//! `BlackPlane` also holds the K channel of every other color and solves C, M and Y for the colorimetric target.
+//! Input K is mapped to output K by matching the L* of K-only colors of both profiles.
+//! ```
+//! use icc_profile::*;
+//! use icc_profile::cms::kpreserve::{KPreservation,KPreservingTransform};
+//!
+//! fn retarget(input:&DecodedICCProfile,output:&DecodedICCProfile,cmyk:&[u8]) -> std::io::Result<Vec<u8>> {
+//!     let transform = KPreservingTransform::new(input,output,0,KPreservation::BlackPlane)?;
+//!     let mut buf = vec![0;cmyk.len()];
+//!     transform.transform_u8(cmyk,&mut buf)?;
+//!     Ok(buf)
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::cms::ColorMatrix3D;
+use crate::cms::delta_e::delta_e76;
+use crate::cms::pcs::xyz_to_lab;
+use crate::cms::transform::Transform;
+
+const CMYK: u32 = 0x434d594b;   // 'CMYK'
+/// entries of the K to K curve
+const K_CURVE_SIZE: usize = 256;
+/// ΔE of the black plane solution that is good enough
+const TOLERANCE: f64 = 0.1;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum KPreservation {
+    /// K-only input stays K-only, other colors are converted normally
+    BlackOnly,
+    /// the K channel is held for every color and C, M, Y are solved for the target
+    BlackPlane,
+}
+
+pub struct KPreservingTransform<'a> {
+    mode: KPreservation,
+    /// input CMYK to PCS
+    input: Transform<'a>,
+    /// PCS to output CMYK
+    output: Transform<'a>,
+    /// output CMYK to PCS, relative colorimetric
+    proof: Transform<'a>,
+    /// output K of input K
+    k_curve: Vec<f64>,
+}
+
+fn lightness(transform:&Transform,cmyk:[f64;4]) -> f64 {
+    let xyz = transform.transform(&cmyk);
+    xyz_to_lab([xyz[0],xyz[1],xyz[2]])[0]
+}
+
+impl<'a> KPreservingTransform<'a> {
+    /// new makes a transform between two CMYK profiles for `rendering_intent` 0-3
+    pub fn new(input:&'a DecodedICCProfile,output:&'a DecodedICCProfile,rendering_intent:u32,mode:KPreservation) -> Result<Self> {
+        if input.color_space != CMYK || output.color_space != CMYK {
+            return Err(Error::new(ErrorKind::InvalidInput,"K-preserving transforms need CMYK input and output profiles"))
+        }
+        let transform = Self {
+            mode,
+            input: Transform::to_pcs(input,rendering_intent)?,
+            output: Transform::from_pcs(output,rendering_intent)?,
+            proof: Transform::to_pcs(output,1)?,
+            k_curve: vec![],
+        };
+        let k_curve = transform.k_curve(&Transform::to_pcs(input,1)?);
+        Ok(Self { k_curve,..transform })
+    }
+
+    /// k_curve matches L* of K-only input to L* of K-only output by bisection, L* falls as K rises
+    fn k_curve(&self,input:&Transform) -> Vec<f64> {
+        (0..K_CURVE_SIZE).map(|i| {
+            let target = lightness(input,[0.0,0.0,0.0,i as f64 / (K_CURVE_SIZE - 1) as f64]);
+            let (mut low,mut high) = (0.0,1.0);
+            for _ in 0..24 {
+                let mid = (low + high) / 2.0;
+                if lightness(&self.proof,[0.0,0.0,0.0,mid]) > target {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            (low + high) / 2.0
+        }).collect()
+    }
+
+    /// k maps input K to output K
+    pub fn k(&self,k:f64) -> f64 {
+        let pos = k.clamp(0.0,1.0) * (K_CURVE_SIZE - 1) as f64;
+        let i = (pos.floor() as usize).min(K_CURVE_SIZE - 2);
+        let frac = pos - i as f64;
+        self.k_curve[i] * (1.0 - frac) + self.k_curve[i + 1] * frac
+    }
+
+    pub fn mode(&self) -> KPreservation {
+        self.mode
+    }
+
+    /// transform converts one CMYK color, values 0.0-1.0
+    pub fn transform(&self,input:&[f64]) -> Vec<f64> {
+        let cmyk :Vec<f64> = (0..4).map(|i| input.get(i).copied().unwrap_or(0.0).clamp(0.0,1.0)).collect();
+        if cmyk[..3].iter().all(|val| *val <= 0.5 / 65535.0) {
+            return vec![0.0,0.0,0.0,self.k(cmyk[3])]
+        }
+        let pcs = self.input.transform(&cmyk);
+        let normal = self.output.transform(&pcs);
+        match self.mode {
+            KPreservation::BlackOnly => normal,
+            KPreservation::BlackPlane => self.solve_cmy(xyz_to_lab([pcs[0],pcs[1],pcs[2]]),self.k(cmyk[3]),normal),
+        }
+    }
+
+    fn error(&self,target:[f64;3],cmyk:[f64;4]) -> [f64;3] {
+        let xyz = self.proof.transform(&cmyk);
+        let lab = xyz_to_lab([xyz[0],xyz[1],xyz[2]]);
+        [lab[0] - target[0],lab[1] - target[1],lab[2] - target[2]]
+    }
+
+    /// solve_cmy finds C, M and Y with the fixed K by Newton iteration from the normal result.
+    /// The normal result is kept when it is closer to the target by more than 2 ΔE.
+    fn solve_cmy(&self,target:[f64;3],k:f64,normal:Vec<f64>) -> Vec<f64> {
+        let mut cmyk = [normal[0],normal[1],normal[2],k];
+        let mut error = self.error(target,cmyk);
+        for _ in 0..16 {
+            if delta_e76(error,[0.0;3]) < TOLERANCE {
+                break
+            }
+            let mut jacobian = [0.0;9];
+            for j in 0..3 {
+                let mut step = cmyk;
+                let delta = if step[j] > 0.5 { -1e-3 } else { 1e-3 };
+                step[j] += delta;
+                let moved = self.error(target,step);
+                for i in 0..3 {
+                    jacobian[i * 3 + j] = (moved[i] - error[i]) / delta;
+                }
+            }
+            let inverse = match ColorMatrix3D::from(&jacobian).and_then(|matrix| ColorMatrix3D::invese(&matrix)) {
+                Some(inverse) => inverse,
+                None => break,
+            };
+            let (dc,dm,dy) = inverse.convert_3d(error[0],error[1],error[2]);
+            let next = [(cmyk[0] - dc).clamp(0.0,1.0),(cmyk[1] - dm).clamp(0.0,1.0),(cmyk[2] - dy).clamp(0.0,1.0),k];
+            let next_error = self.error(target,next);
+            if delta_e76(next_error,[0.0;3]) >= delta_e76(error,[0.0;3]) {
+                break
+            }
+            cmyk = next;
+            error = next_error;
+        }
+        let normal_error = delta_e76(self.error(target,[normal[0],normal[1],normal[2],normal[3]]),[0.0;3]);
+        if delta_e76(error,[0.0;3]) > normal_error + 2.0 {
+            return normal
+        }
+        cmyk.to_vec()
+    }
+
+    fn check_buffers(src:usize,dst:usize) -> Result<usize> {
+        if !src.is_multiple_of(4) || dst < src {
+            return Err(Error::new(ErrorKind::InvalidInput,format!("buffers of {} and {} samples do not fit CMYK",src,dst)))
+        }
+        Ok(src / 4)
+    }
+
+    /// transform_f64 converts interleaved CMYK pixels
+    pub fn transform_f64(&self,src:&[f64],dst:&mut [f64]) -> Result<()> {
+        let pixels = Self::check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let output = self.transform(&src[i * 4..(i + 1) * 4]);
+            dst[i * 4..(i + 1) * 4].copy_from_slice(&output);
+        }
+        Ok(())
+    }
+
+    /// transform_u8 converts interleaved 8 bit CMYK pixels
+    pub fn transform_u8(&self,src:&[u8],dst:&mut [u8]) -> Result<()> {
+        let pixels = Self::check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * 4..(i + 1) * 4].iter().map(|val| *val as f64 / 255.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * 4 + j] = (val * 255.0).round().clamp(0.0,255.0) as u8;
+            }
+        }
+        Ok(())
+    }
+
+    /// transform_u16 converts interleaved 16 bit CMYK pixels
+    pub fn transform_u16(&self,src:&[u16],dst:&mut [u16]) -> Result<()> {
+        let pixels = Self::check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * 4..(i + 1) * 4].iter().map(|val| *val as f64 / 65535.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * 4 + j] = (val * 65535.0).round().clamp(0.0,65535.0) as u16;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn k_preserving_transform() {
+        use crate::cms::pcs::PcsEncoding;
+        // presses that differ in the strength of K, B2A0 makes rich black
+        let press = |k_strength:f64| {
+            let a2b0 = lut16_fixture(4,3,2,&|p| PcsEncoding::Lab16Legacy.encode_u16([
+                100.0 - 25.0 * p[0] - 20.0 * p[1] - 10.0 * p[2] - k_strength * p[3],
+                40.0 * p[1] - 30.0 * p[0],50.0 * p[2] - 20.0 * p[0]]).to_vec());
+            let b2a0 = lut16_fixture(3,4,2,&|p| {
+                let ink = ((1.0 - p[0]) * 0xffff as f64) as u16;
+                vec![ink / 2,ink / 2,ink / 2,ink]
+            });
+            let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"CMYK"),u32::from_be_bytes(*b"Lab "));
+            builder.raw_tag("A2B0",tag("mft2",&a2b0)).unwrap().raw_tag("B2A0",tag("mft2",&b2a0)).unwrap();
+            DecodedICCProfile::new(&builder.build().unwrap()).unwrap()
+        };
+        let (input,output) = (press(40.0),press(45.0));
+        let normal = Transform::new(&input,&output,0).unwrap();
+        assert!(normal.transform(&[0.0,0.0,0.0,1.0])[0] > 0.1);
+
+        let black_only = KPreservingTransform::new(&input,&output,0,KPreservation::BlackOnly).unwrap();
+        let black = black_only.transform(&[0.0,0.0,0.0,1.0]);
+        assert_eq!(&black[..3],&[0.0,0.0,0.0]);
+        assert!((black[3] - 40.0 / 45.0).abs() < 1e-3,"{:?}",black);
+        assert_eq!(black_only.transform(&[0.5,0.2,0.1,0.3]),normal.transform(&[0.5,0.2,0.1,0.3]));
+        let mut buf = [0;8];
+        black_only.transform_u8(&[0,0,0,128,0,0,0,0],&mut buf).unwrap();
+        assert_eq!(buf,[0,0,0,(128.0 * 40.0 / 45.0_f64).round() as u8,0,0,0,0]);
+
+        let black_plane = KPreservingTransform::new(&input,&output,0,KPreservation::BlackPlane).unwrap();
+        let cmyk = black_plane.transform(&[0.3,0.2,0.1,0.5]);
+        assert!((cmyk[3] - black_plane.k(0.5)).abs() < 1e-9);
+        let lab = |profile:&DecodedICCProfile,cmyk:&[f64]| {
+            let xyz = Transform::to_pcs(profile,1).unwrap().transform(cmyk);
+            xyz_to_lab([xyz[0],xyz[1],xyz[2]])
+        };
+        let (target,result) = (lab(&input,&[0.3,0.2,0.1,0.5]),lab(&output,&cmyk));
+        assert!(crate::cms::delta_e::delta_e76(target,result) < 0.5,"{:?} {:?} {:?}",cmyk,target,result);
+    }
+}
@@ -0,0 +1,294 @@
+//! Float evaluation of lut8 (mft1), lut16 (mft2), lutAtoB (mAB) and lutBtoA (mBA) tags
+//! Input and output values are normalized to 0.0-1.0 and the clut is interpolated multilinearly
+//! over any number of input channels (1 to 15).
+//! The lut8/lut16 e_params matrix is applied by `eval_xyz`, for PCSXYZ input only.
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+
+fn s15fixed16(val:&S15Fixed16Number) -> f64 {
+    val.int() as f64 + val.decimal() as f64 / 65536.0
+}
+
+/// curve_lookup interpolates a one dimensional table, an empty table is identity
+fn curve_lookup<T:Copy + Into<f64>>(table:&[T],max:f64,val:f64) -> f64 {
+    let val = if val.is_nan() { 0.0 } else { val.clamp(0.0,1.0) };
+    if table.is_empty() {
+        return val
+    }
+    if table.len() == 1 {
+        return table[0].into() / max
+    }
+    let pos = val * (table.len() - 1) as f64;
+    let i = (pos.floor() as usize).min(table.len() - 2);
+    let frac = pos - i as f64;
+    let v0 :f64 = table[i].into();
+    let v1 :f64 = table[i + 1].into();
+    (v0 + (v1 - v0) * frac) / max
+}
+
+/// clut_lookup interpolates `inputs` over the corners of the grid cell, the first input varies slowest
+fn clut_lookup<T:Copy + Into<f64>>(clut:&[T],max:f64,grid_points:&[usize],inputs:&[f64],output_channels:usize) -> Vec<f64> {
+    let mut outputs = vec![0.0;output_channels];
+    if grid_points.is_empty() || grid_points.contains(&0) || output_channels == 0 {
+        return outputs
+    }
+    let mut base = 0;
+    // (stride,fraction) of the inputs between two grid points, the others add no corners
+    let mut active = vec![];
+    let mut stride = output_channels;
+    for (i,grid) in grid_points.iter().enumerate().rev() {
+        let val = inputs.get(i).copied().unwrap_or(0.0);
+        let val = if val.is_nan() { 0.0 } else { val.clamp(0.0,1.0) };
+        if *grid > 1 {
+            let pos = val * (grid - 1) as f64;
+            let index = (pos.floor() as usize).min(grid - 2);
+            let frac = pos - index as f64;
+            base += index * stride;
+            if frac > 0.0 {
+                active.push((stride,frac));
+            }
+        }
+        stride *= grid;
+    }
+    for corner in 0..1_usize << active.len() {
+        let mut weight = 1.0;
+        let mut offset = base;
+        for (bit,(stride,frac)) in active.iter().enumerate() {
+            if corner & (1 << bit) != 0 {
+                weight *= frac;
+                offset += stride;
+            } else {
+                weight *= 1.0 - frac;
+            }
+        }
+        for (j,output) in outputs.iter_mut().enumerate() {
+            let val :f64 = clut.get(offset + j).map(|v| (*v).into()).unwrap_or(0.0);
+            *output += val * weight;
+        }
+    }
+    outputs.iter().map(|val| val / max).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn lut_eval<T:Copy + Into<f64>>(input:&[f64],input_channels:usize,output_channels:usize,grid_points:usize,
+        input_table:&[T],input_entries:usize,clut:&[T],output_table:&[T],output_entries:usize,max:f64) -> Vec<f64> {
+    let inputs :Vec<f64> = (0..input_channels).map(|i| {
+        let table = input_table.get(i * input_entries..(i + 1) * input_entries).unwrap_or(&[]);
+        curve_lookup(table,max,input.get(i).copied().unwrap_or(0.0))
+    }).collect();
+    let outputs = clut_lookup(clut,max,&vec![grid_points;input_channels],&inputs,output_channels);
+    outputs.iter().enumerate().map(|(i,val)| {
+        let table = output_table.get(i * output_entries..(i + 1) * output_entries).unwrap_or(&[]);
+        curve_lookup(table,max,*val)
+    }).collect()
+}
+
+/// e_params_eval multiplies encoded XYZ by the 3x3 matrix and clips the result
+fn e_params_eval(e_params:&[S15Fixed16Number],input:&[f64]) -> Vec<f64> {
+    if e_params.len() < 9 || input.len() < 3 {
+        return input.to_vec()
+    }
+    let e :Vec<f64> = e_params.iter().map(s15fixed16).collect();
+    (0..3).map(|i| (e[i * 3] * input[0] + e[i * 3 + 1] * input[1] + e[i * 3 + 2] * input[2]).clamp(0.0,1.0)).collect()
+}
+
+impl Mft1 {
+    /// eval converts normalized input values to normalized output values
+    pub fn eval(&self,input:&[f64]) -> Vec<f64> {
+        lut_eval(input,self.input_channels as usize,self.output_channels as usize,self.number_of_clut_grid_points as usize,
+            &self.input_table,256,&self.clut_values,&self.output_table,256,255.0)
+    }
+
+    /// eval_xyz applies the e_params matrix to PCSXYZ input before eval
+    pub fn eval_xyz(&self,input:&[f64]) -> Vec<f64> {
+        self.eval(&e_params_eval(&self.e_params,input))
+    }
+}
+
+impl Mft2 {
+    /// eval converts normalized input values to normalized output values
+    pub fn eval(&self,input:&[f64]) -> Vec<f64> {
+        lut_eval(input,self.input_channels as usize,self.output_channels as usize,self.number_of_clut_grid_points as usize,
+            &self.input_table,self.input_table_enteries as usize,&self.clut_values,
+            &self.output_table,self.output_table_enteries as usize,65535.0)
+    }
+
+    /// eval_xyz applies the e_params matrix to PCSXYZ input before eval.
+    /// Input is u1Fixed15 XYZ divided by 65535, 1.0 (0x8000) is 32768 / 65535.
+    pub fn eval_xyz(&self,input:&[f64]) -> Vec<f64> {
+        self.eval(&e_params_eval(&self.e_params,input))
+    }
+}
+
+impl ParametricCurve {
+    /// eval computes the function types 0-4, missing parameters are 0
+    pub fn eval(&self,x:f64) -> f64 {
+        let p = |i:usize| self.vals.get(i).map(s15fixed16).unwrap_or(0.0);
+        let (g,a,b,c,d,e,f) = (p(0),p(1),p(2),p(3),p(4),p(5),p(6));
+        let pow = |base:f64| if base > 0.0 { base.powf(g) } else { 0.0 };
+        let y = match self.funtion_type {
+            0 => pow(x),
+            1 => if a != 0.0 && x >= -b / a { pow(a * x + b) } else { 0.0 },
+            2 => if a != 0.0 && x >= -b / a { pow(a * x + b) + c } else { c },
+            3 => if x >= d { pow(a * x + b) } else { c * x },
+            4 => if x >= d { pow(a * x + b) + e } else { c * x + f },
+            _ => x,
+        };
+        y.clamp(0.0,1.0)
+    }
+}
+
+/// curve_eval evaluates a curv table, no entry is identity and one entry is a u8Fixed8 gamma
+pub fn curve_eval(curve:&[u16],x:f64) -> f64 {
+    match curve.len() {
+        0 => x.clamp(0.0,1.0),
+        1 => x.clamp(0.0,1.0).powf(curve[0] as f64 / 256.0),
+        _ => curve_lookup(curve,65535.0,x),
+    }
+}
+
+impl Curve {
+    pub fn eval(&self,x:f64) -> f64 {
+        match self {
+            Curve::Curve(curve) => curve_eval(curve,x),
+            Curve::ParametricCurve(curve) => curve.eval(x),
+        }
+    }
+}
+
+impl Data {
+    /// eval_curve evaluates a curv or para tag such as rTRC
+    pub fn eval_curve(&self,x:f64) -> Result<f64> {
+        match self {
+            Data::Curve(curve) => Ok(curve_eval(curve,x)),
+            Data::ParametricCurve(curve) => Ok(curve.eval(x)),
+            _ => Err(Error::new(ErrorKind::InvalidData,"not a curve")),
+        }
+    }
+
+    /// eval_lut evaluates a lut8, lut16, lutAtoB or lutBtoA tag with normalized values
+    pub fn eval_lut(&self,input:&[f64]) -> Result<Vec<f64>> {
+        match self {
+            Data::Lut8(lut) => Ok(lut.eval(input)),
+            Data::Lut16(lut) => Ok(lut.eval(input)),
+            Data::LutAtoB(lut) => Ok(lut.eval(input)),
+            Data::LutBtoA(lut) => Ok(lut.eval(input)),
+            _ => Err(Error::new(ErrorKind::InvalidData,"not a lut")),
+        }
+    }
+
+    /// eval_lut_xyz evaluates a lut tag whose input is XYZ, lut8 and lut16 apply the e_params matrix
+    pub fn eval_lut_xyz(&self,input:&[f64]) -> Result<Vec<f64>> {
+        match self {
+            Data::Lut8(lut) => Ok(lut.eval_xyz(input)),
+            Data::Lut16(lut) => Ok(lut.eval_xyz(input)),
+            _ => self.eval_lut(input),
+        }
+    }
+
+    /// lut_channels returns the input and output channels of a lut tag
+    pub fn lut_channels(&self) -> Option<(usize,usize)> {
+        match self {
+            Data::Lut8(lut) => Some((lut.input_channels as usize,lut.output_channels as usize)),
+            Data::Lut16(lut) => Some((lut.input_channels as usize,lut.output_channels as usize)),
+            Data::LutAtoB(lut) => Some((lut.input_channels as usize,lut.output_channels as usize)),
+            Data::LutBtoA(lut) => Some((lut.input_channels as usize,lut.output_channels as usize)),
+            _ => None,
+        }
+    }
+}
+
+fn curves_eval(curves:&[Curve],vals:&mut [f64]) {
+    for (val,curve) in vals.iter_mut().zip(curves) {
+        *val = curve.eval(*val);
+    }
+}
+
+/// matrix_eval applies the 3x3 matrix and the offsets of mAB/mBA
+fn matrix_eval(matrix:&[S15Fixed16Number],vals:&mut [f64]) {
+    if matrix.len() < 12 || vals.len() != 3 {
+        return
+    }
+    let e :Vec<f64> = matrix.iter().map(s15fixed16).collect();
+    let (x,y,z) = (vals[0],vals[1],vals[2]);
+    for (i,val) in vals.iter_mut().enumerate() {
+        *val = (e[i * 3] * x + e[i * 3 + 1] * y + e[i * 3 + 2] * z + e[9 + i]).clamp(0.0,1.0);
+    }
+}
+
+impl MClut {
+    /// eval interpolates normalized inputs, inputs pass through without a CLUT
+    pub fn eval(&self,input:&[f64],output_channels:usize) -> Vec<f64> {
+        if self.grid_points().is_empty() {
+            return input.to_vec()
+        }
+        let grid_points :Vec<usize> = self.grid_points().iter().map(|grid| *grid as usize).collect();
+        match self.clut_data() {
+            Clut::UInt8(clut) => clut_lookup(clut,255.0,&grid_points,input,output_channels),
+            Clut::UInt16(clut) => clut_lookup(clut,65535.0,&grid_points,input,output_channels),
+        }
+    }
+}
+
+impl Mab {
+    /// eval converts normalized values, A curves, CLUT, M curves, matrix and B curves
+    pub fn eval(&self,input:&[f64]) -> Vec<f64> {
+        let mut vals :Vec<f64> = (0..self.input_channels as usize)
+            .map(|i| input.get(i).copied().unwrap_or(0.0).clamp(0.0,1.0)).collect();
+        curves_eval(&self.a_curves,&mut vals);
+        let mut vals = self.clut.eval(&vals,self.output_channels as usize);
+        vals.resize(self.output_channels as usize,0.0);
+        curves_eval(&self.m_curves,&mut vals);
+        matrix_eval(&self.matrix,&mut vals);
+        curves_eval(&self.b_curves,&mut vals);
+        vals
+    }
+}
+
+impl Mba {
+    /// eval converts normalized values, B curves, matrix, M curves, CLUT and A curves
+    pub fn eval(&self,input:&[f64]) -> Vec<f64> {
+        let mut vals :Vec<f64> = (0..self.input_channels as usize)
+            .map(|i| input.get(i).copied().unwrap_or(0.0).clamp(0.0,1.0)).collect();
+        curves_eval(&self.b_curves,&mut vals);
+        matrix_eval(&self.matrix,&mut vals);
+        curves_eval(&self.m_curves,&mut vals);
+        let mut vals = self.clut.eval(&vals,self.output_channels as usize);
+        vals.resize(self.output_channels as usize,0.0);
+        curves_eval(&self.a_curves,&mut vals);
+        vals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn lut16_xyz_matrix() {
+        use crate::cms::transform::Transform;
+        // XYZ to 1 channel: the matrix replaces X by Y, the 3 entry input curves double and clip,
+        // the CLUT returns its first input and the output curve inverts
+        let mut mft2 = lut16_curves_fixture(3,1,2,&[0,0xffff,0xffff],&|p| vec![(p[0] * 65535.0) as u16],&[0xffff,0]);
+        for (i,e) in [0,1,0,0,1,0,0,0,1_u32].iter().enumerate() {
+            mft2[4 + i * 4..8 + i * 4].copy_from_slice(&(e << 16).to_be_bytes());
+        }
+        let lut = match Data::parse(&tag("mft2",&mft2),mft2.len() + 8,0x02100000).unwrap().1 {
+            Data::Lut16(lut) => lut,
+            other => panic!("{:?}",other),
+        };
+        assert!((lut.eval(&[0.1,0.3,0.15])[0] - 0.8).abs() < 1e-9);
+        assert!((lut.eval_xyz(&[0.1,0.3,0.15])[0] - 0.4).abs() < 1e-9);
+        assert!(lut.eval_xyz(&[0.1,0.6,0.15])[0].abs() < 1e-9);
+
+        // Y 0.6 is 0.6 * 32768 / 65535 in u1Fixed15
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"GRAY"),u32::from_be_bytes(*b"XYZ "));
+        builder.raw_tag("B2A0",tag("mft2",&mft2)).unwrap();
+        let printer = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        let output = Transform::from_pcs(&printer,0).unwrap().transform(&[0.2,0.6,0.3]);
+        assert!((output[0] - (1.0 - 2.0 * 0.6 * 32768.0 / 65535.0)).abs() < 1e-9,"{:?}",output);
+    }
+}
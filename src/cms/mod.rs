@@ -5,6 +5,14 @@ use crate::iccprofile::ICCNumber;
 use crate::S15Fixed16Number;
 
 pub mod transration;
+pub mod lut;
+pub mod delta_e;
+pub mod h273;
+pub mod transform;
+pub mod pcs;
+pub mod gamut;
+pub mod proof;
+pub mod kpreserve;
 
 pub enum ColorEntries {
     Rgb24(Vec<u8>),
@@ -177,6 +185,44 @@ impl ColorMatrix3D {
         }
     }
 
+    /// Bradford cone response matrix
+    pub fn bradford() -> Self {
+        Self {
+            e : [ 0.8951,  0.2664, -0.1614,
+                 -0.7502,  1.7135,  0.0367,
+                  0.0389, -0.0685,  1.0296]
+        }
+    }
+
+    /// multiply returns self * other, i.e. other is applied first
+    pub fn multiply(&self,other:&Self) -> Self {
+        let (a,b) = (&self.e,&other.e);
+        let mut e = [0.0;9];
+        for i in 0..3 {
+            for j in 0..3 {
+                e[i * 3 + j] = a[i * 3] * b[j] + a[i * 3 + 1] * b[3 + j] + a[i * 3 + 2] * b[6 + j];
+            }
+        }
+        Self { e }
+    }
+
+    /// chromatic_adaptation returns the Bradford adaptation matrix from a source white to a destination white (XYZ)
+    pub fn chromatic_adaptation(src:(f64,f64,f64),dst:(f64,f64,f64)) -> Option<Self> {
+        let bradford = Self::bradford();
+        let inverse = Self::invese(&bradford)?;
+        let (sr,sg,sb) = bradford.convert_3d(src.0,src.1,src.2);
+        let (dr,dg,db) = bradford.convert_3d(dst.0,dst.1,dst.2);
+        if sr == 0.0 || sg == 0.0 || sb == 0.0 {
+            return None
+        }
+        let scale = Self {
+            e : [dr / sr, 0.0, 0.0,
+                 0.0, dg / sg, 0.0,
+                 0.0, 0.0, db / sb]
+        };
+        Some(inverse.multiply(&scale.multiply(&bradford)))
+    }
+
     pub fn convert_3d(&self,x:f64,y:f64,z:f64) -> (f64,f64,f64) {
         let e = self.e;
         let (x,y,z) = (x,y,z);
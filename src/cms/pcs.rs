@@ -0,0 +1,172 @@
+//! PCS encodings of Lab and XYZ
+//! LUT tags store PCS values as 8 bit, 16 bit or float numbers. The 16 bit Lab encoding of version 2
+//! profiles and of lut16 tags (L 0xFF00 = 100.0) differs from the version 4 encoding (L 0xFFFF = 100.0).
+//! ```
+//! use icc_profile::cms::pcs::PcsEncoding;
+//!
+//! let lab = [50.0,20.0,-30.0];
+//! let v2 = PcsEncoding::Lab16Legacy.encode_u16(lab);
+//! let v4 = PcsEncoding::Lab16.encode_u16(lab);
+//! assert_eq!(v2,[0x7f80,0x9400,0x6200]);
+//! assert_eq!(v4,[0x8000,0x9494,0x6262]);
+//! assert!((PcsEncoding::Lab16.decode_u16(v4)[0] - 50.0).abs() < 0.001);
+//! ```
+
+use crate::iccprofile::Data;
+
+const LAB: u32 = 0x4c616220;    // 'Lab '
+const D50: [f64;3] = [0.9642,1.0,0.8249];
+
+/// lab_to_xyz converts D50 L*a*b* to XYZ
+pub fn lab_to_xyz(lab:[f64;3]) -> [f64;3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+    let f = |t:f64| if t > 6.0 / 29.0 { t * t * t } else { 3.0 * (6.0_f64 / 29.0).powi(2) * (t - 4.0 / 29.0) };
+    [D50[0] * f(fx),D50[1] * f(fy),D50[2] * f(fz)]
+}
+
+/// xyz_to_lab converts XYZ to D50 L*a*b*
+pub fn xyz_to_lab(xyz:[f64;3]) -> [f64;3] {
+    let f = |t:f64| if t > (6.0_f64 / 29.0).powi(3) { t.cbrt() } else { t / (3.0 * (6.0_f64 / 29.0).powi(2)) + 4.0 / 29.0 };
+    let (fx,fy,fz) = (f(xyz[0] / D50[0]),f(xyz[1] / D50[1]),f(xyz[2] / D50[2]));
+    [116.0 * fy - 16.0,500.0 * (fx - fy),200.0 * (fy - fz)]
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum PcsEncoding {
+    /// lut8, L 0-255 = 0.0-100.0, a/b 0-255 = -128.0-127.0
+    Lab8,
+    /// version 2 and lut16, L 0xFF00 = 100.0, a/b 0x8000 = 0.0
+    Lab16Legacy,
+    /// version 4, L 0xFFFF = 100.0, a/b 0x8080 = 0.0
+    Lab16,
+    /// L*a*b* as is
+    LabFloat,
+    /// u1Fixed15, 0x8000 = 1.0, also used for 8 bit XYZ tables
+    Xyz16,
+    /// XYZ as is
+    XyzFloat,
+}
+
+impl PcsEncoding {
+    /// lab16 returns the 16 bit Lab encoding of a profile version
+    pub fn lab16(version:u32) -> Self {
+        if version >= 0x04000000 { PcsEncoding::Lab16 } else { PcsEncoding::Lab16Legacy }
+    }
+
+    /// for_tag returns the encoding of a LUT tag from its type, the profile PCS and version
+    pub fn for_tag(data:&Data,pcs:u32,version:u32) -> Self {
+        let lab = pcs == LAB;
+        match data {
+            Data::Lut8(_) if lab => PcsEncoding::Lab8,
+            Data::Lut16(_) if lab => PcsEncoding::Lab16Legacy,
+            Data::MultiProcessElements(_) if lab => PcsEncoding::LabFloat,
+            Data::MultiProcessElements(_) => PcsEncoding::XyzFloat,
+            _ if lab => PcsEncoding::lab16(version),
+            _ => PcsEncoding::Xyz16,
+        }
+    }
+
+    pub fn is_lab(&self) -> bool {
+        matches!(self,PcsEncoding::Lab8 | PcsEncoding::Lab16Legacy | PcsEncoding::Lab16 | PcsEncoding::LabFloat)
+    }
+
+    /// decode converts normalized values 0.0-1.0 to Lab or XYZ
+    pub fn decode(&self,vals:[f64;3]) -> [f64;3] {
+        match self {
+            PcsEncoding::Lab8 => [vals[0] * 100.0,vals[1] * 255.0 - 128.0,vals[2] * 255.0 - 128.0],
+            PcsEncoding::Lab16Legacy => {
+                let v = vals.map(|val| val * 65535.0);
+                [v[0] * 100.0 / 65280.0,v[1] / 256.0 - 128.0,v[2] / 256.0 - 128.0]
+            },
+            PcsEncoding::Lab16 => {
+                let v = vals.map(|val| val * 65535.0);
+                [v[0] * 100.0 / 65535.0,v[1] / 257.0 - 128.0,v[2] / 257.0 - 128.0]
+            },
+            PcsEncoding::Xyz16 => vals.map(|val| val * 65535.0 / 32768.0),
+            PcsEncoding::LabFloat | PcsEncoding::XyzFloat => vals,
+        }
+    }
+
+    /// encode converts Lab or XYZ to normalized values, clipped to 0.0-1.0 except float encodings
+    pub fn encode(&self,pcs:[f64;3]) -> [f64;3] {
+        let vals = match self {
+            PcsEncoding::Lab8 => [pcs[0] / 100.0,(pcs[1] + 128.0) / 255.0,(pcs[2] + 128.0) / 255.0],
+            PcsEncoding::Lab16Legacy => [pcs[0] * 65280.0 / 100.0,(pcs[1] + 128.0) * 256.0,(pcs[2] + 128.0) * 256.0].map(|val| val / 65535.0),
+            PcsEncoding::Lab16 => [pcs[0] * 65535.0 / 100.0,(pcs[1] + 128.0) * 257.0,(pcs[2] + 128.0) * 257.0].map(|val| val / 65535.0),
+            PcsEncoding::Xyz16 => pcs.map(|val| val * 32768.0 / 65535.0),
+            PcsEncoding::LabFloat | PcsEncoding::XyzFloat => return pcs,
+        };
+        vals.map(|val| val.clamp(0.0,1.0))
+    }
+
+    pub fn decode_u8(&self,vals:[u8;3]) -> [f64;3] {
+        self.decode(vals.map(|val| val as f64 / 255.0))
+    }
+
+    pub fn encode_u8(&self,pcs:[f64;3]) -> [u8;3] {
+        self.encode(pcs).map(|val| (val * 255.0).round().clamp(0.0,255.0) as u8)
+    }
+
+    pub fn decode_u16(&self,vals:[u16;3]) -> [f64;3] {
+        self.decode(vals.map(|val| val as f64 / 65535.0))
+    }
+
+    pub fn encode_u16(&self,pcs:[f64;3]) -> [u16;3] {
+        self.encode(pcs).map(|val| (val * 65535.0).round().clamp(0.0,65535.0) as u16)
+    }
+
+    /// to_xyz decodes normalized values to XYZ, converting Lab with D50
+    pub fn to_xyz(&self,vals:[f64;3]) -> [f64;3] {
+        let pcs = self.decode(vals);
+        if self.is_lab() { lab_to_xyz(pcs) } else { pcs }
+    }
+
+    /// from_xyz encodes XYZ, converting to Lab with D50
+    pub fn from_xyz(&self,xyz:[f64;3]) -> [f64;3] {
+        self.encode(if self.is_lab() { xyz_to_lab(xyz) } else { xyz })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iccprofile::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn pcs_encodings() {
+        use crate::cms::transform::Transform;
+        assert_eq!(PcsEncoding::Lab16Legacy.decode_u16([0xff00,0x8000,0x8000]),[100.0,0.0,0.0]);
+        assert_eq!(PcsEncoding::Lab16.decode_u16([0xffff,0x8080,0x8080]),[100.0,0.0,0.0]);
+        for lab in [[0.0,-128.0,-128.0],[33.3,12.5,-60.25],[100.0,127.0,127.0]] {
+            for encoding in [PcsEncoding::Lab16Legacy,PcsEncoding::Lab16] {
+                let decoded = encoding.decode_u16(encoding.encode_u16(lab));
+                assert!((0..3).all(|i| (decoded[i] - lab[i]).abs() < 0.01),"{:?} {:?}",encoding,decoded);
+            }
+        }
+        assert!((PcsEncoding::Xyz16.decode_u16([0x8000,0x8000,0x8000])[1] - 1.0).abs() < 1e-12);
+
+        // a v4 gray profile with a mAB A2B0 chained to a v2 gray profile with a lut8 B2A0
+        let gray = u32::from_be_bytes(*b"GRAY");
+        let lab = u32::from_be_bytes(*b"Lab ");
+        let a2b0 = mab_fixture(1,3,2,&|p| vec![(p[0] * 65535.0) as u16,0x8080,0x8080]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"scnr"),gray,lab);
+        builder.version(0x04300000);
+        builder.raw_tag("A2B0",tag("mAB ",&a2b0)).unwrap();
+        let input = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        assert_eq!(PcsEncoding::for_tag(input.tags.get("A2B0").unwrap(),input.pcs,input.version),PcsEncoding::Lab16);
+        let lab_out = Transform::to_pcs(&input,0).unwrap().transform(&[0.5]);
+        assert!((xyz_to_lab(lab_out.try_into().unwrap())[0] - 50.0).abs() < 1e-6);
+
+        let b2a0 = lut8_fixture(3,1,2,&|p| vec![(p[0] * 255.0) as u8]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),gray,lab);
+        builder.version(0x02100000);
+        builder.raw_tag("B2A0",tag("mft1",&b2a0)).unwrap();
+        let output = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        assert_eq!(PcsEncoding::for_tag(output.tags.get("B2A0").unwrap(),output.pcs,output.version),PcsEncoding::Lab8);
+        let gray_out = Transform::new(&input,&output,0).unwrap().transform(&[0.5]);
+        assert!((gray_out[0] - 0.5).abs() < 1e-6,"{:?}",gray_out);
+    }
+}
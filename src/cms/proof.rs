@@ -0,0 +1,174 @@
+//! Soft proofing, simulating print on a display
+//! Source → PCS → printer → PCS → display, with the printer preview tags pre0-pre2 when present.
+//! Paper white simulation shows the printer media white on the display (absolute colorimetric),
+//! and out of gamut source colors can be painted with an alarm color.
+//! ```
+//! use icc_profile::*;
+//! use icc_profile::cms::proof::{Proof,ProofOptions};
+//!
+//! fn soft_proof(source:&DecodedICCProfile,printer:&DecodedICCProfile,display:&DecodedICCProfile,pixels:&[u8]) -> std::io::Result<Vec<u8>> {
+//!     let options = ProofOptions { paper_white: true,gamut_alarm: Some(vec![0.0,1.0,0.0]),..ProofOptions::default() };
+//!     let proof = Proof::new(source,printer,display,&options)?;
+//!     let mut rgb = vec![0;pixels.len() / proof.input_channels() * proof.output_channels()];
+//!     proof.transform_u8(pixels,&mut rgb)?;
+//!     Ok(rgb)
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::cms::gamut::GamutCheck;
+use crate::cms::transform::Transform;
+
+#[derive(Debug,Clone,Default)]
+pub struct ProofOptions {
+    /// rendering intent of source to printer, 0-3
+    pub intent: u32,
+    /// show the printer media white instead of the display white (absolute colorimetric onto the display)
+    pub paper_white: bool,
+    /// display device values 0.0-1.0 of out of gamut pixels, None disables the gamut alarm
+    pub gamut_alarm: Option<Vec<f64>>,
+}
+
+pub struct Proof<'a> {
+    /// source to PCS
+    source: Transform<'a>,
+    /// PCS to display through the printer
+    proof: Transform<'a>,
+    /// gamut check with the source PCS made relative, and the alarm color
+    gamut: Option<(GamutCheck<'a>,Option<Transform<'a>>,Vec<f64>)>,
+}
+
+impl<'a> Proof<'a> {
+    pub fn new(source:&'a DecodedICCProfile,printer:&'a DecodedICCProfile,display:&'a DecodedICCProfile,options:&ProofOptions) -> Result<Self> {
+        let intent = options.intent;
+        let absolute = intent == 3;
+        let mut proof = Transform::preview(printer,intent)?;
+        if options.paper_white != absolute {
+            proof = proof.then(Transform::media_white(printer,options.paper_white))?;
+        }
+        let proof = proof.then(Transform::from_pcs(display,if options.paper_white { 3 } else { 1 })?)?;
+        let gamut = match &options.gamut_alarm {
+            Some(alarm) => {
+                if alarm.len() != proof.output_channels() {
+                    return Err(Error::new(ErrorKind::InvalidInput,
+                        format!("gamut alarm has {} channels, the display has {}",alarm.len(),proof.output_channels())))
+                }
+                let relative = if absolute { Some(Transform::media_white(printer,false)) } else { None };
+                let check = GamutCheck::new(printer,if absolute { 1 } else { intent })?;
+                Some((check,relative,alarm.clone()))
+            },
+            None => None,
+        };
+        Ok(Self { source: Transform::to_pcs(source,intent)?,proof,gamut })
+    }
+
+    pub fn input_channels(&self) -> usize {
+        self.source.input_channels()
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.proof.output_channels()
+    }
+
+    /// transform converts the values of one pixel
+    pub fn transform(&self,input:&[f64]) -> Vec<f64> {
+        let pcs = self.source.transform(input);
+        if let Some((check,relative,alarm)) = &self.gamut {
+            let xyz = match relative {
+                Some(relative) => relative.transform(&pcs),
+                None => pcs.clone(),
+            };
+            if !check.is_in_gamut_xyz([xyz[0],xyz[1],xyz[2]]) {
+                return alarm.clone()
+            }
+        }
+        self.proof.transform(&pcs)
+    }
+
+    fn check_buffers(&self,src:usize,dst:usize) -> Result<usize> {
+        let (input_channels,output_channels) = (self.input_channels(),self.output_channels());
+        let pixels = src / input_channels;
+        if !src.is_multiple_of(input_channels) || dst < pixels * output_channels {
+            return Err(Error::new(ErrorKind::InvalidInput,format!("buffers of {} and {} samples do not fit {} to {} channels",
+                src,dst,input_channels,output_channels)))
+        }
+        Ok(pixels)
+    }
+
+    /// transform_f64 converts interleaved pixels
+    pub fn transform_f64(&self,src:&[f64],dst:&mut [f64]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        let (input_channels,output_channels) = (self.input_channels(),self.output_channels());
+        for i in 0..pixels {
+            let output = self.transform(&src[i * input_channels..(i + 1) * input_channels]);
+            dst[i * output_channels..(i + 1) * output_channels].copy_from_slice(&output);
+        }
+        Ok(())
+    }
+
+    /// transform_u8 converts interleaved 8 bit pixels
+    pub fn transform_u8(&self,src:&[u8],dst:&mut [u8]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        let (input_channels,output_channels) = (self.input_channels(),self.output_channels());
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * input_channels..(i + 1) * input_channels].iter()
+                .map(|val| *val as f64 / 255.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * output_channels + j] = (val * 255.0).round().clamp(0.0,255.0) as u8;
+            }
+        }
+        Ok(())
+    }
+
+    /// transform_u16 converts interleaved 16 bit pixels
+    pub fn transform_u16(&self,src:&[u16],dst:&mut [u16]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        let (input_channels,output_channels) = (self.input_channels(),self.output_channels());
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * input_channels..(i + 1) * input_channels].iter()
+                .map(|val| *val as f64 / 65535.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * output_channels + j] = (val * 65535.0).round().clamp(0.0,65535.0) as u16;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn soft_proof() {
+        let srgb = crate::CicpType::new(1,13,0,true).to_profile("sRGB","none").unwrap();
+        let srgb = DecodedICCProfile::new(&srgb).unwrap();
+        // a grayscale press, out of gamut when a* is above 0, on a darker paper
+        let pre1 = lut16_fixture(3,3,2,&|p| vec![(p[0] * 65535.0) as u16,0x8000,0x8000]);
+        let gamt = lut16_fixture(3,1,3,&|p| vec![if p[1] == 1.0 { 0xffff } else { 0 }]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"Lab "));
+        builder.raw_tag("pre1",tag("mft2",&pre1)).unwrap()
+            .raw_tag("gamt",tag("mft2",&gamt)).unwrap()
+            .tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642 * 0.8,0.8,0.8249 * 0.8)),"XYZ ").unwrap();
+        let printer = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+
+        let options = ProofOptions { intent: 1,..ProofOptions::default() };
+        let proof = Proof::new(&srgb,&printer,&srgb,&options).unwrap();
+        let gray = proof.transform(&[0.5,0.5,0.5]);
+        assert!(gray.iter().all(|val| (val - 0.5).abs() < 0.01),"{:?}",gray);
+        let red = proof.transform(&[1.0,0.0,0.0]);
+        assert!((red[0] - red[1]).abs() < 0.01 && (red[1] - red[2]).abs() < 0.01,"{:?}",red);
+
+        let options = ProofOptions { intent: 1,paper_white: true,gamut_alarm: Some(vec![0.0,1.0,0.0]) };
+        let proof = Proof::new(&srgb,&printer,&srgb,&options).unwrap();
+        let mut rgb = [0;9];
+        proof.transform_u8(&[255,255,255,255,0,0,0,255,0],&mut rgb).unwrap();
+        assert!(rgb[0] > 200 && rgb[0] < 240 && (rgb[0] as i32 - rgb[2] as i32).abs() <= 1,"{:?}",rgb);
+        assert_eq!(&rgb[3..6],&[0,255,0]);
+        assert!((rgb[6] as i32 - rgb[7] as i32).abs() <= 1,"{:?}",rgb);
+        assert!(Proof::new(&srgb,&printer,&srgb,&ProofOptions { gamut_alarm: Some(vec![0.0]),..ProofOptions::default() }).is_err());
+    }
+}
@@ -0,0 +1,506 @@
+//! Profile to profile transforms for any device space, Gray, RGB, CMYK and 2CLR-15CLR
+//! A Transform chains device to PCS and PCS to device stages of profiles.
+//! Device values are normalized to 0.0-1.0, PCS values are D50 XYZ with Y = 1.0 for the white.
+//! ```
+//! use icc_profile::*;
+//! use icc_profile::cms::transform::Transform;
+//!
+//! fn convert(input:&DecodedICCProfile,output:&DecodedICCProfile,pixels:&[u8]) -> std::io::Result<Vec<u8>> {
+//!     let transform = Transform::new(input,output,0)?;
+//!     let mut buf = vec![0;pixels.len() / transform.input_channels() * transform.output_channels()];
+//!     transform.transform_u8(pixels,&mut buf)?;
+//!     Ok(buf)
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::cms::ColorMatrix3D;
+use crate::cms::pcs::PcsEncoding;
+pub use crate::cms::pcs::{lab_to_xyz,xyz_to_lab};
+
+const LAB: u32 = 0x4c616220;    // 'Lab '
+const XYZ: u32 = 0x58595a20;    // 'XYZ '
+const D50: [f64;3] = [0.9642,1.0,0.8249];
+/// black of the version 4 perceptual reference medium
+const PERCEPTUAL_BLACK: [f64;3] = [0.00336,0.0034731,0.00287];
+
+/// color_space_channels returns the number of channels of a color space signature
+pub fn color_space_channels(color_space:u32) -> Option<usize> {
+    match &color_space.to_be_bytes() {
+        b"GRAY" => Some(1),
+        b"XYZ " | b"Lab " | b"Luv " | b"YCbr" | b"Yxy " | b"RGB " | b"HSV " | b"HLS " | b"CMY " => Some(3),
+        b"CMYK" => Some(4),
+        [n,b'C',b'L',b'R'] => match (*n as char).to_digit(16) {
+            Some(channels) if channels >= 2 => Some(channels as usize),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn tag_xyz(profile:&DecodedICCProfile,signature:&str) -> Option<[f64;3]> {
+    let xyz = match profile.tags.get(signature)? {
+        Data::XYZNumber(xyz) => xyz,
+        Data::XYZNumberArray(xyzs) => xyzs.first()?,
+        _ => return None,
+    };
+    Some([xyz.x.as_f64(),xyz.y.as_f64(),xyz.z.as_f64()])
+}
+
+fn is_curve(data:Option<&Data>) -> bool {
+    matches!(data,Some(Data::Curve(_)) | Some(Data::ParametricCurve(_)))
+}
+
+/// invert_curve finds x of a monotonic curve by bisection
+fn invert_curve(curve:&Data,y:f64) -> f64 {
+    let f = |x:f64| curve.eval_curve(x).unwrap_or(x);
+    let increasing = f(1.0) >= f(0.0);
+    let (mut low,mut high) = (0.0,1.0);
+    for _ in 0..32 {
+        let mid = (low + high) / 2.0;
+        if (f(mid) < y) == increasing {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// device side of a profile
+enum Model<'a> {
+    /// xyz_input applies the lut8/lut16 matrix
+    Lut { lut:&'a Data,encoding:PcsEncoding,xyz_input:bool },
+    /// colorant matrix and its inverse, red, green and blue TRCs
+    MatrixTrc { matrix:ColorMatrix3D,inverse:ColorMatrix3D,trc:[&'a Data;3] },
+    Gray { trc:&'a Data,lab:bool },
+}
+
+fn eval_lut(lut:&Data,xyz_input:bool,vals:&[f64]) -> Vec<f64> {
+    if xyz_input { lut.eval_lut_xyz(vals) } else { lut.eval_lut(vals) }.unwrap_or_default()
+}
+
+enum Stage<'a> {
+    ToPcs(Model<'a>),
+    FromPcs(Model<'a>),
+    Link { lut:&'a Data,xyz_input:bool },
+    /// PCS to PCS of a preview tag
+    Preview { lut:&'a Data,encoding:PcsEncoding,xyz_input:bool },
+    /// XYZ scale of absolute colorimetric intent
+    Scale([f64;3]),
+    /// XYZ scale and offset of black point compensation
+    Bpc { scale:[f64;3],offset:[f64;3] },
+}
+
+impl Stage<'_> {
+    fn eval(&self,vals:&[f64]) -> Vec<f64> {
+        match self {
+            Stage::ToPcs(Model::Lut { lut,encoding,xyz_input }) => {
+                let pcs = eval_lut(lut,*xyz_input,vals);
+                let v = |i:usize| pcs.get(i).copied().unwrap_or(0.0);
+                encoding.to_xyz([v(0),v(1),v(2)]).to_vec()
+            },
+            Stage::FromPcs(Model::Lut { lut,encoding,xyz_input }) => {
+                let v = |i:usize| vals.get(i).copied().unwrap_or(0.0);
+                eval_lut(lut,*xyz_input,&encoding.from_xyz([v(0),v(1),v(2)]))
+            },
+            Stage::ToPcs(Model::MatrixTrc { matrix,trc,.. }) => {
+                let linear :Vec<f64> = (0..3).map(|i| trc[i].eval_curve(vals.get(i).copied().unwrap_or(0.0)).unwrap_or(0.0)).collect();
+                let (x,y,z) = matrix.convert_3d(linear[0],linear[1],linear[2]);
+                vec![x,y,z]
+            },
+            Stage::FromPcs(Model::MatrixTrc { inverse,trc,.. }) => {
+                let v = |i:usize| vals.get(i).copied().unwrap_or(0.0);
+                let (r,g,b) = inverse.convert_3d(v(0),v(1),v(2));
+                [r,g,b].iter().enumerate().map(|(i,val)| invert_curve(trc[i],val.clamp(0.0,1.0))).collect()
+            },
+            Stage::ToPcs(Model::Gray { trc,lab }) => {
+                let val = trc.eval_curve(vals.first().copied().unwrap_or(0.0)).unwrap_or(0.0);
+                if *lab {
+                    lab_to_xyz([val * 100.0,0.0,0.0]).to_vec()
+                } else {
+                    D50.iter().map(|white| white * val).collect()
+                }
+            },
+            Stage::FromPcs(Model::Gray { trc,lab }) => {
+                let y = vals.get(1).copied().unwrap_or(0.0);
+                let val = if *lab { xyz_to_lab([0.0,y,0.0])[0] / 100.0 } else { y };
+                vec![invert_curve(trc,val.clamp(0.0,1.0))]
+            },
+            Stage::Link { lut,xyz_input } => eval_lut(lut,*xyz_input,vals),
+            Stage::Preview { lut,encoding,xyz_input } => {
+                let v = |i:usize| vals.get(i).copied().unwrap_or(0.0);
+                let pcs = eval_lut(lut,*xyz_input,&encoding.from_xyz([v(0),v(1),v(2)]));
+                let v = |i:usize| pcs.get(i).copied().unwrap_or(0.0);
+                encoding.to_xyz([v(0),v(1),v(2)]).to_vec()
+            },
+            Stage::Scale(scale) => vals.iter().zip(scale).map(|(val,scale)| val * scale).collect(),
+            Stage::Bpc { scale,offset } => (0..3).map(|i| vals.get(i).copied().unwrap_or(0.0) * scale[i] + offset[i]).collect(),
+        }
+    }
+}
+
+/// lut_tag returns the intent tag or the 0 tag, intent 3 (absolute) uses the relative colorimetric tag
+fn lut_tag<'a>(profile:&'a DecodedICCProfile,prefix:&str,intent:u32) -> Option<&'a Data> {
+    let intent = if intent == 3 { 1 } else { intent.min(2) };
+    profile.tags.get(&format!("{}{}",prefix,intent)).or_else(|| profile.tags.get(&format!("{}0",prefix)))
+}
+
+/// check_lut_channels rejects LUTs without input or output channels or with more than 15
+fn check_lut_channels(lut:&Data) -> Result<(usize,usize)> {
+    match lut.lut_channels() {
+        Some((inputs,outputs)) if (1..=15).contains(&inputs) && (1..=15).contains(&outputs) => Ok((inputs,outputs)),
+        Some((inputs,outputs)) => Err(Error::new(ErrorKind::InvalidData,format!("LUT has {} input and {} output channels",inputs,outputs))),
+        None => Err(Error::new(ErrorKind::InvalidData,"not a LUT tag")),
+    }
+}
+
+fn model<'a>(profile:&'a DecodedICCProfile,prefix:&str,intent:u32) -> Result<Model<'a>> {
+    let lab = match profile.pcs {
+        LAB => true,
+        XYZ => false,
+        pcs => return Err(Error::new(ErrorKind::InvalidData,format!("unknown PCS {:08x}",pcs))),
+    };
+    if let Some(lut) = lut_tag(profile,prefix,intent) {
+        if lut.lut_channels().is_some() {
+            check_lut_channels(lut)?;
+            let xyz_input = if prefix == "B2A" { !lab } else { profile.color_space == XYZ };
+            let encoding = PcsEncoding::for_tag(lut,profile.pcs,profile.version);
+            return Ok(Model::Lut { lut,encoding,xyz_input })
+        }
+    }
+    let tags = &profile.tags;
+    if is_curve(tags.get("rTRC")) && is_curve(tags.get("gTRC")) && is_curve(tags.get("bTRC")) {
+        if let (Some(r),Some(g),Some(b)) = (tag_xyz(profile,"rXYZ"),tag_xyz(profile,"gXYZ"),tag_xyz(profile,"bXYZ")) {
+            let matrix = ColorMatrix3D::from(&[r[0],g[0],b[0],r[1],g[1],b[1],r[2],g[2],b[2]])
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,"colorant matrix"))?;
+            let inverse = ColorMatrix3D::invese(&matrix)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,"colorant matrix is not invertible"))?;
+            return Ok(Model::MatrixTrc { matrix,inverse,trc: [&tags["rTRC"],&tags["gTRC"],&tags["bTRC"]] })
+        }
+    }
+    if let Some(trc) = tags.get("kTRC").filter(|trc| is_curve(Some(trc))) {
+        return Ok(Model::Gray { trc,lab })
+    }
+    Err(Error::new(ErrorKind::Unsupported,format!("profile has no {} tag, colorant matrix or gray TRC",prefix)))
+}
+
+/// absolute colorimetric scale, media white / D50
+fn absolute_scale(profile:&DecodedICCProfile) -> [f64;3] {
+    let white = tag_xyz(profile,"wtpt").unwrap_or(D50);
+    [white[0] / D50[0],white[1] / D50[1],white[2] / D50[2]]
+}
+
+/// device_black returns the darkest device values, maximum colorant for subtractive spaces
+fn device_black(color_space:u32,channels:usize) -> Vec<f64> {
+    let subtractive = matches!(&color_space.to_be_bytes(),b"CMYK" | b"CMY " | [_,b'C',b'L',b'R']);
+    vec![if subtractive { 1.0 } else { 0.0 };channels]
+}
+
+/// neutral_black keeps L* of a PCS black and drops a* and b*, L* above 50 is no black
+fn neutral_black(xyz:&[f64]) -> Option<[f64;3]> {
+    let lab = xyz_to_lab([xyz[0],xyz[1],xyz[2]]);
+    if lab[0] > 50.0 || lab[0].is_nan() {
+        return Option::None
+    }
+    Some(lab_to_xyz([lab[0].max(0.0),0.0,0.0]))
+}
+
+/// black_point detects the D50 relative black point of a profile for intents 0-2.
+/// Version 4 perceptual and saturation tables use the reference medium black, relative colorimetric uses
+/// the bkpt tag when present. Otherwise the device black is converted to PCS,
+/// and output profiles round trip L* 0 through B2Ax and A2Bx when possible.
+/// A black point lighter than L* 50 is returned as 0, no compensation.
+pub fn black_point(profile:&DecodedICCProfile,intent:u32,output:bool) -> Result<[f64;3]> {
+    if profile.version >= 0x04000000 && (intent == 0 || intent == 2) {
+        return Ok(PERCEPTUAL_BLACK)
+    }
+    if let Some(black) = tag_xyz(profile,"bkpt").filter(|_| intent == 1) {
+        // bkpt is measured on the media, relative colorimetric maps the media white to D50
+        let scale = absolute_scale(profile);
+        return Ok([black[0] / scale[0],black[1] / scale[1],black[2] / scale[2]])
+    }
+    if output {
+        let round_trip = Transform::from_pcs(profile,intent).and_then(|from| from.then(Transform::to_pcs(profile,intent)?));
+        if let Some(black) = round_trip.ok().and_then(|round_trip| neutral_black(&round_trip.transform(&[0.0,0.0,0.0]))) {
+            return Ok(black)
+        }
+    }
+    let to_pcs = Transform::to_pcs(profile,intent)?;
+    Ok(neutral_black(&to_pcs.transform(&device_black(profile.color_space,to_pcs.input_channels))).unwrap_or([0.0;3]))
+}
+
+/// Options of `Transform::new_with_options`
+#[derive(Debug,Clone,Default)]
+pub struct TransformOptions {
+    /// black point compensation (ISO 18619), not applied to absolute colorimetric
+    pub black_point_compensation: bool,
+}
+
+pub struct Transform<'a> {
+    stages: Vec<Stage<'a>>,
+    input_channels: usize,
+    output_channels: usize,
+}
+
+impl<'a> Transform<'a> {
+    /// new converts input device values to output device values.
+    /// intent 0 perceptual, 1 relative colorimetric, 2 saturation, 3 absolute colorimetric.
+    pub fn new(input:&'a DecodedICCProfile,output:&'a DecodedICCProfile,intent:u32) -> Result<Self> {
+        Self::to_pcs(input,intent)?.then(Self::from_pcs(output,intent)?)
+    }
+
+    pub fn new_with_options(input:&'a DecodedICCProfile,output:&'a DecodedICCProfile,intent:u32,options:&TransformOptions) -> Result<Self> {
+        let mut transform = Self::to_pcs(input,intent)?;
+        if options.black_point_compensation && intent != 3 {
+            transform = transform.then(Self::black_point_compensation(
+                black_point(input,intent,false)?,black_point(output,intent,true)?))?;
+        }
+        transform.then(Self::from_pcs(output,intent)?)
+    }
+
+    /// black_point_compensation scales PCS XYZ so that the source black maps to the destination black
+    /// and the D50 white stays, as ISO 18619
+    pub fn black_point_compensation(source:[f64;3],destination:[f64;3]) -> Self {
+        let mut scale = [1.0;3];
+        let mut offset = [0.0;3];
+        for i in 0..3 {
+            if D50[i] - source[i] > 0.0 {
+                scale[i] = (D50[i] - destination[i]) / (D50[i] - source[i]);
+                offset[i] = D50[i] * (1.0 - scale[i]);
+            }
+        }
+        Self { stages: vec![Stage::Bpc { scale,offset }],input_channels: 3,output_channels: 3 }
+    }
+
+    /// to_pcs converts device values to PCS XYZ with A2Bx, the colorant matrix or the gray TRC
+    pub fn to_pcs(profile:&'a DecodedICCProfile,intent:u32) -> Result<Self> {
+        let input_channels = Self::channels(profile.color_space)?;
+        let mut stages = vec![Stage::ToPcs(model(profile,"A2B",intent)?)];
+        if intent == 3 {
+            stages.push(Stage::Scale(absolute_scale(profile)));
+        }
+        Ok(Self { stages,input_channels,output_channels: 3 })
+    }
+
+    /// from_pcs converts PCS XYZ to device values with B2Ax or the inverse of the colorant matrix and TRCs
+    pub fn from_pcs(profile:&'a DecodedICCProfile,intent:u32) -> Result<Self> {
+        let output_channels = Self::channels(profile.color_space)?;
+        let mut stages = vec![];
+        if intent == 3 {
+            stages.push(Stage::Scale(absolute_scale(profile).map(|scale| 1.0 / scale)));
+        }
+        stages.push(Stage::FromPcs(model(profile,"B2A",intent)?));
+        Ok(Self { stages,input_channels: 3,output_channels })
+    }
+
+    /// device_link converts with the A2B0 tag of a device link profile
+    pub fn device_link(link:&'a DecodedICCProfile) -> Result<Self> {
+        let lut = link.tags.get("A2B0").filter(|lut| lut.lut_channels().is_some())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData,"device link has no A2B0 tag"))?;
+        let (input_channels,output_channels) = check_lut_channels(lut)?;
+        let xyz_input = link.color_space == XYZ;
+        Ok(Self { stages: vec![Stage::Link { lut,xyz_input }],input_channels,output_channels })
+    }
+
+    /// preview converts PCS to PCS as printed on an output profile, with pre0-pre2 or the B2Ax/A2Bx round trip
+    pub fn preview(output:&'a DecodedICCProfile,intent:u32) -> Result<Self> {
+        let lut = lut_tag(output,"pre",intent).filter(|lut| lut.lut_channels() == Some((3,3)));
+        let lut = match lut {
+            Some(lut) => lut,
+            None => return Self::from_pcs(output,intent)?.then(Self::to_pcs(output,intent)?),
+        };
+        let encoding = PcsEncoding::for_tag(lut,output.pcs,output.version);
+        let preview = Stage::Preview { lut,encoding,xyz_input: output.pcs == XYZ };
+        let stages = if intent == 3 {
+            let scale = absolute_scale(output);
+            vec![Stage::Scale(scale.map(|scale| 1.0 / scale)),preview,Stage::Scale(scale)]
+        } else {
+            vec![preview]
+        };
+        Ok(Self { stages,input_channels: 3,output_channels: 3 })
+    }
+
+    /// media_white converts relative colorimetric PCS to absolute PCS of the profile media white,
+    /// or back when `to_absolute` is false
+    pub fn media_white(profile:&DecodedICCProfile,to_absolute:bool) -> Self {
+        let scale = absolute_scale(profile);
+        let scale = if to_absolute { scale } else { scale.map(|scale| 1.0 / scale) };
+        Self { stages: vec![Stage::Scale(scale)],input_channels: 3,output_channels: 3 }
+    }
+
+    fn channels(color_space:u32) -> Result<usize> {
+        color_space_channels(color_space).ok_or_else(|| Error::new(ErrorKind::Unsupported,
+            format!("color space {}",String::from_utf8_lossy(&color_space.to_be_bytes()))))
+    }
+
+    /// then appends the stages of `next`, the channels must match
+    pub fn then(mut self,next:Transform<'a>) -> Result<Self> {
+        if self.output_channels != next.input_channels {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("{} output channels do not match {} input channels",self.output_channels,next.input_channels)))
+        }
+        self.stages.extend(next.stages);
+        self.output_channels = next.output_channels;
+        Ok(self)
+    }
+
+    pub fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    /// transform converts the values of one pixel
+    pub fn transform(&self,input:&[f64]) -> Vec<f64> {
+        let mut vals = input.to_vec();
+        vals.resize(self.input_channels,0.0);
+        for stage in &self.stages {
+            vals = stage.eval(&vals);
+        }
+        vals.resize(self.output_channels,0.0);
+        vals
+    }
+
+    fn check_buffers(&self,src:usize,dst:usize) -> Result<usize> {
+        let pixels = src / self.input_channels;
+        if !src.is_multiple_of(self.input_channels) || dst < pixels * self.output_channels {
+            return Err(Error::new(ErrorKind::InvalidInput,format!("buffers of {} and {} samples do not fit {} to {} channels",
+                src,dst,self.input_channels,self.output_channels)))
+        }
+        Ok(pixels)
+    }
+
+    /// transform_f64 converts interleaved pixels
+    pub fn transform_f64(&self,src:&[f64],dst:&mut [f64]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let output = self.transform(&src[i * self.input_channels..(i + 1) * self.input_channels]);
+            dst[i * self.output_channels..(i + 1) * self.output_channels].copy_from_slice(&output);
+        }
+        Ok(())
+    }
+
+    /// transform_u8 converts interleaved 8 bit pixels
+    pub fn transform_u8(&self,src:&[u8],dst:&mut [u8]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * self.input_channels..(i + 1) * self.input_channels].iter()
+                .map(|val| *val as f64 / 255.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * self.output_channels + j] = (val * 255.0).round().clamp(0.0,255.0) as u8;
+            }
+        }
+        Ok(())
+    }
+
+    /// transform_u16 converts interleaved 16 bit pixels
+    pub fn transform_u16(&self,src:&[u16],dst:&mut [u16]) -> Result<()> {
+        let pixels = self.check_buffers(src.len(),dst.len())?;
+        for i in 0..pixels {
+            let input :Vec<f64> = src[i * self.input_channels..(i + 1) * self.input_channels].iter()
+                .map(|val| *val as f64 / 65535.0).collect();
+            for (j,val) in self.transform(&input).iter().enumerate() {
+                dst[i * self.output_channels + j] = (val * 65535.0).round().clamp(0.0,65535.0) as u16;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn n_channel_transform() {
+        assert_eq!(color_space_channels(u32::from_be_bytes(*b"FCLR")),Some(15));
+        assert_eq!(color_space_channels(u32::from_be_bytes(*b"6CLR")),Some(6));
+
+        // 6 inks, Lab PCS, L = 100 * (1 - mean of the inks)
+        let mft2 = lut16_fixture(6,3,2,&|p| vec![(65280.0 * (1.0 - p.iter().sum::<f64>() / 6.0)).round() as u16,0x8000,0x8000]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"6CLR"),u32::from_be_bytes(*b"Lab "));
+        builder.tag("A2B0",&Data::Raw("mft2".to_string(),mft2),"mft2").unwrap();
+        let printer = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        let display = DecodedICCProfile::new(&CicpType::new(1,13,0,true).to_profile("sRGB","No copyright").unwrap()).unwrap();
+
+        let to_pcs = Transform::to_pcs(&printer,0).unwrap();
+        assert_eq!(to_pcs.input_channels(),6);
+        let lab = xyz_to_lab(to_pcs.transform(&[1.0,0.0,0.0,0.0,0.0,0.5]).try_into().unwrap());
+        assert!((lab[0] - 75.0).abs() < 0.01,"{:?}",lab);
+        assert!(matches!(Transform::from_pcs(&printer,0),Err(err) if err.kind() == std::io::ErrorKind::Unsupported));
+
+        let transform = Transform::new(&printer,&display,1).unwrap();
+        let mut rgb = [0;6];
+        transform.transform_u8(&[0,0,0,0,0,0,255,255,255,255,255,255],&mut rgb).unwrap();
+        assert_eq!(rgb,[255,255,255,0,0,0]);
+        let back = Transform::to_pcs(&display,1).unwrap().then(Transform::from_pcs(&display,1).unwrap()).unwrap();
+        let rgb = back.transform(&[0.2,0.5,0.8]);
+        assert!(rgb.iter().zip([0.2,0.5,0.8]).all(|(a,b)| (a - b).abs() < 1e-3),"{:?}",rgb);
+    }
+
+    #[test]
+    fn lut_channel_limits() {
+        // the same tables serve as a device link and as A2B0/B2A0 of a Lab printer
+        let link = |inputs:usize,outputs:usize| {
+            let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"Lab "));
+            let mft2 = lut16_fixture(inputs,outputs,2,&|_| vec![0;outputs]);
+            builder.tag("A2B0",&Data::Raw("mft2".to_string(),mft2.clone()),"mft2").unwrap()
+                .tag("B2A0",&Data::Raw("mft2".to_string(),mft2),"mft2").unwrap();
+            DecodedICCProfile::new(&builder.build().unwrap()).unwrap()
+        };
+        assert_eq!(Transform::device_link(&link(3,4)).unwrap().output_channels(),4);
+        for (inputs,outputs) in [(3,0),(0,3),(16,1)] {
+            let profile = link(inputs,outputs);
+            assert!(profile.tags.get("A2B0").unwrap().lut_channels().is_some());
+            assert!(matches!(Transform::device_link(&profile),Err(err) if err.kind() == std::io::ErrorKind::InvalidData));
+            assert!(matches!(Transform::to_pcs(&profile,0),Err(err) if err.kind() == std::io::ErrorKind::InvalidData));
+            assert!(matches!(Transform::from_pcs(&profile,0),Err(err) if err.kind() == std::io::ErrorKind::InvalidData));
+        }
+    }
+
+    #[test]
+    fn black_point_compensation() {
+        let gray = |black:u16,bkpt:Option<f64>| {
+            let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"GRAY"),u32::from_be_bytes(*b"XYZ "));
+            builder.tag("kTRC",&Data::Curve(vec![black,0xffff]),"curv").unwrap();
+            if let Some(y) = bkpt {
+                builder.tag("bkpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642 * y,y,0.8249 * y)),"XYZ ").unwrap();
+            }
+            builder.build().unwrap()
+        };
+        let (newsprint,glossy,measured) = (gray(0x1999,Option::None),gray(0,Option::None),gray(0,Some(0.2)));
+        let newsprint = DecodedICCProfile::new(&newsprint).unwrap();
+        let glossy = DecodedICCProfile::new(&glossy).unwrap();
+        let measured = DecodedICCProfile::new(&measured).unwrap();
+        assert!((black_point(&newsprint,1,false).unwrap()[1] - 0.1).abs() < 1e-4);
+        assert!(black_point(&glossy,1,true).unwrap()[1].abs() < 1e-4);
+        assert!((black_point(&measured,1,true).unwrap()[1] - 0.2).abs() < 1e-4);
+
+        let options = TransformOptions { black_point_compensation: true };
+        let plain = Transform::new(&newsprint,&glossy,1).unwrap();
+        let bpc = Transform::new_with_options(&newsprint,&glossy,1,&options).unwrap();
+        assert!((plain.transform(&[0.0])[0] - 0.1).abs() < 1e-3);
+        assert!(bpc.transform(&[0.0])[0].abs() < 1e-3);
+        assert!((bpc.transform(&[0.5])[0] - 0.5).abs() < 1e-3);
+        assert!((bpc.transform(&[1.0])[0] - 1.0).abs() < 1e-3);
+        let bpc = Transform::new_with_options(&glossy,&measured,1,&options).unwrap();
+        assert!((bpc.transform(&[0.0])[0] - 0.2).abs() < 1e-3);
+        let absolute = Transform::new_with_options(&newsprint,&glossy,3,&options).unwrap();
+        assert!((absolute.transform(&[0.0])[0] - 0.1).abs() < 1e-3);
+
+        // a black lighter than L* 50 is no black point, the other side is still compensated
+        let light = DecodedICCProfile::new(&gray(0x9999,Option::None)).unwrap();
+        assert_eq!(black_point(&light,1,false).unwrap(),[0.0;3]);
+        assert_eq!(black_point(&light,1,true).unwrap(),[0.0;3]);
+        let bpc = Transform::new_with_options(&light,&glossy,1,&options).unwrap();
+        assert!((bpc.transform(&[0.0])[0] - 0.6).abs() < 1e-3);
+        let bpc = Transform::new_with_options(&newsprint,&light,1,&options).unwrap();
+        assert!(bpc.transform(&[0.0])[0] < 1e-3);
+    }
+}
@@ -1,45 +1,40 @@
 // icc profile reader
-use crate::cms::transration::d4_to_d3_lut8;
-use crate::cms::transration::d4_to_d3_lut16;
+use crate::cms::pcs::PcsEncoding;
 pub use crate::iccprofile::*;
 use std::io::Result;
 use std::io::{Error,ErrorKind};
 
 
-// La*b* 0-100 -127-127 -127-127
+/// cmyk_eval_lut16 converts CMYK to Lab, lut16 uses the legacy 16 bit Lab encoding
+fn cmyk_eval_lut16(c:u8,m:u8,y:u8,k:u8,lut:&Mft2) -> [f64;3] {
+    let out = lut.eval(&[c,m,y,k].map(|val| val as f64 / 255.0));
+    PcsEncoding::Lab16Legacy.decode([0,1,2].map(|i| out.get(i).copied().unwrap_or(0.0)))
+}
+
+fn cmyk_eval_lut8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1) -> [f64;3] {
+    let out = lut.eval(&[c,m,y,k].map(|val| val as f64 / 255.0));
+    PcsEncoding::Lab8.decode([0,1,2].map(|i| out.get(i).copied().unwrap_or(0.0)))
+}
+
+// u8 La*b* is the lut8 encoding, L 255 = 100.0 and a/b 128 = 0.0
 pub fn cmyk_to_lab_lut16_u8(c:u8,m:u8,y:u8,k:u8,lut:&Mft2) -> (u8,u8,u8) {
-    let (l,a,b) = cmyk_to_lab_lut16(c ,m ,y , k,lut);
-    let l = ((l * 255.0 / 100.0) as i16 ).clamp(0,255) as u8;
-    let a = ((a +128.0) as i16).clamp(0,255) as u8;
-    let b = ((b +128.0) as i16).clamp(0,255) as u8;
+    let [l,a,b] = PcsEncoding::Lab8.encode_u8(cmyk_eval_lut16(c,m,y,k,lut));
     (l,a,b)
 }
 
-// YMCK -> Lab conversion 0-65536
+// CMYK -> La*b* 0-100 -128-127 -128-127
 pub fn cmyk_to_lab_lut16(c:u8,m:u8,y:u8,k:u8,lut:&Mft2) -> (f64,f64,f64) {
-
-    let (l,a,b) = d4_to_d3_lut16(c,m,y,k,lut);
-
-    let l = l * 100.0 / 65535.0;
-    let a = a * 255.0 / 65535.0 - 127.5;
-    let b = b * 255.0 / 65535.0 - 127.5;
+    let [l,a,b] = cmyk_eval_lut16(c,m,y,k,lut);
     (l,a,b)
 }
 
-// La*b* 0-100 -127-127 -127-127
 pub fn cmyk_to_lab_lut8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1) -> (f64,f64,f64) {
-    let (l,a,b) = d4_to_d3_lut8(c,m,y,k,lut);
-    let l = l as f64 / 255.0 * 100.0;
-    let a = a as f64 - 127.0;
-    let b = b as f64 - 127.0;
-    
+    let [l,a,b] = cmyk_eval_lut8(c,m,y,k,lut);
     (l,a,b)
 }
 
-// no test
 pub fn cmyk_to_lab_lut8_u8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1) -> (u8,u8,u8) {
-
-    let (l,a,b) = d4_to_d3_lut8(c,m,y,k,lut);
+    let [l,a,b] = PcsEncoding::Lab8.encode_u8(cmyk_eval_lut8(c,m,y,k,lut));
     (l,a,b)
 }
 
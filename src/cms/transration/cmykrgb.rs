@@ -1,16 +1,147 @@
-
+//! Naive CMYK <--> RGB without profiles
+//! RGB = (1 - C)(1 - K), (1 - M)(1 - K), (1 - Y)(1 - K).
+//! RGB to CMYK replaces the gray component min(C,M,Y) with K by a black generation (GCR) curve,
+//! removes the under color from C, M and Y, then limits K and the total area coverage.
+//! Use profiles for anything color critical.
 use crate::cms::transration::*;
 use crate::Mft2;
+use std::io::Result;
+use std::io::{Error,ErrorKind};
+
+/// black generation, how much of the gray component min(C,M,Y) becomes K
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum BlackGeneration {
+    /// no K, CMY only
+    None,
+    /// K in the shadows, from 50% gray, up to 50% K
+    Light,
+    /// K from 25% gray, up to 75% K
+    #[default]
+    Medium,
+    /// K from 10% gray, up to 90% K
+    Heavy,
+    /// all of the gray component is K
+    Max,
+    /// K from `start` gray rising linearly to `strength` K at 100% gray, both 0.0-1.0
+    Custom { start: f64,strength: f64 },
+}
+
+impl BlackGeneration {
+    /// black returns K for a gray component 0.0-1.0
+    pub fn black(&self,gray:f64) -> f64 {
+        let (start,strength) = match self {
+            BlackGeneration::None => return 0.0,
+            BlackGeneration::Light => (0.5,0.5),
+            BlackGeneration::Medium => (0.25,0.75),
+            BlackGeneration::Heavy => (0.1,0.9),
+            BlackGeneration::Max => (0.0,1.0),
+            BlackGeneration::Custom { start,strength } => (start.clamp(0.0,1.0),strength.clamp(0.0,1.0)),
+        };
+        if start >= 1.0 {
+            return 0.0
+        }
+        strength * ((gray - start) / (1.0 - start)).clamp(0.0,1.0)
+    }
+}
 
-pub fn cmyk_to_rgb(y:u8,m:u8,c:u8,k:u8) -> (u8,u8,u8) {
-    let r = c + k - 255;
-    let g = m + k - 255;
-    let b = y + k - 255;
+#[derive(Debug,Clone)]
+pub struct SeparationOptions {
+    pub black_generation: BlackGeneration,
+    /// total area coverage limit, C + M + Y + K, 3.0 = 300%
+    pub total_ink_limit: f64,
+    /// max K, 0.0-1.0
+    pub max_black: f64,
+    /// under color removal, the share of K taken out of C, M and Y. 1.0 keeps the color,
+    /// 0.0 prints K over the full CMY.
+    pub under_color_removal: f64,
+}
+
+impl Default for SeparationOptions {
+    fn default() -> Self {
+        Self {
+            black_generation: BlackGeneration::Medium,
+            total_ink_limit: 3.0,
+            max_black: 1.0,
+            under_color_removal: 1.0,
+        }
+    }
+}
+
+/// cmyk_to_rgb_f64 converts CMYK 0.0-1.0 to RGB 0.0-1.0
+pub fn cmyk_to_rgb_f64(cmyk:[f64;4]) -> [f64;3] {
+    let [c,m,y,k] = cmyk.map(|val| val.clamp(0.0,1.0));
+    [(1.0 - c) * (1.0 - k),(1.0 - m) * (1.0 - k),(1.0 - y) * (1.0 - k)]
+}
+
+/// rgb_to_cmyk_f64 converts RGB 0.0-1.0 to CMYK 0.0-1.0.
+/// CMY above the total ink limit is scaled down, which makes the color lighter.
+pub fn rgb_to_cmyk_f64(rgb:[f64;3],options:&SeparationOptions) -> [f64;4] {
+    let [c,m,y] = rgb.map(|val| 1.0 - val.clamp(0.0,1.0));
+    let gray = c.min(m).min(y);
+    let k = options.black_generation.black(gray).min(gray).min(options.max_black.clamp(0.0,1.0));
+    // (1 - c) = (1 - c')(1 - k) with full removal
+    let ucr = options.under_color_removal.clamp(0.0,1.0);
+    let remove = |val:f64| {
+        let removed = if k < 1.0 { ((val - k) / (1.0 - k)).clamp(0.0,1.0) } else { 0.0 };
+        val - (val - removed) * ucr
+    };
+    let (mut c,mut m,mut y) = (remove(c),remove(m),remove(y));
+    let limit = options.total_ink_limit.max(0.0);
+    if c + m + y + k > limit {
+        let scale = if c + m + y > 0.0 { (limit - k).max(0.0) / (c + m + y) } else { 0.0 };
+        c *= scale;
+        m *= scale;
+        y *= scale;
+        return [c,m,y,k.min(limit)]
+    }
+    [c,m,y,k]
+}
+
+/// cmyk_to_rgb is the naive conversion of 8 bit CMYK to RGB
+pub fn cmyk_to_rgb(c:u8,m:u8,y:u8,k:u8) -> (u8,u8,u8) {
+    let rgb = cmyk_to_rgb_f64([c,m,y,k].map(|val| val as f64 / 255.0));
+    let [r,g,b] = rgb.map(|val| (val * 255.0).round() as u8);
     (r,g,b)
 }
 
+/// rgb_to_cmyk is the naive conversion of 8 bit RGB to CMYK
+pub fn rgb_to_cmyk(r:u8,g:u8,b:u8,options:&SeparationOptions) -> (u8,u8,u8,u8) {
+    let cmyk = rgb_to_cmyk_f64([r,g,b].map(|val| val as f64 / 255.0),options);
+    let [c,m,y,k] = cmyk.map(|val| (val * 255.0).round() as u8);
+    (c,m,y,k)
+}
+
+pub fn cmyk_to_rgb_entries(buf:&[u8],entries: usize) -> Result<Vec<u8>> {
+    if buf.len() < entries * 4 {
+        return Err(Error::new(ErrorKind::InvalidInput,"Data shortage"))
+    }
+    let mut buffer = Vec::with_capacity(entries * 3);
+    for cmyk in buf[..entries * 4].chunks_exact(4) {
+        let (r,g,b) = cmyk_to_rgb(cmyk[0],cmyk[1],cmyk[2],cmyk[3]);
+        buffer.push(r);
+        buffer.push(g);
+        buffer.push(b);
+    }
+    Ok(buffer)
+}
+
+pub fn rgb_to_cmyk_entries(buf:&[u8],entries: usize,options:&SeparationOptions) -> Result<Vec<u8>> {
+    if buf.len() < entries * 3 {
+        return Err(Error::new(ErrorKind::InvalidInput,"Data shortage"))
+    }
+    let mut buffer = Vec::with_capacity(entries * 4);
+    for rgb in buf[..entries * 3].chunks_exact(3) {
+        let (c,m,y,k) = rgb_to_cmyk(rgb[0],rgb[1],rgb[2],options);
+        buffer.push(c);
+        buffer.push(m);
+        buffer.push(y);
+        buffer.push(k);
+    }
+    Ok(buffer)
+}
+
 pub fn cmyk_to_rgb_lut16(c:u8,m:u8,y:u8,k:u8,lut:&Mft2,wp:&WhitePoint) -> (u8,u8,u8) {
-    let (l,a,b) = cmyk_to_lab_lut16(y,m,c,k,lut);
+    let (l,a,b) = cmyk_to_lab_lut16(c,m,y,k,lut);
     let (x,y,z) = lab_to_xyz_wp(l,a,b,wp);
     let (r,g,b) = xyz_to_rgb(x as f64,y as f64,z as f64);
 
@@ -18,7 +149,7 @@ pub fn cmyk_to_rgb_lut16(c:u8,m:u8,y:u8,k:u8,lut:&Mft2,wp:&WhitePoint) -> (u8,u8
 }
 
 pub fn cmyk_to_rgb_lut8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1,wp:&WhitePoint) -> (u8,u8,u8) {
-    let (l,a,b) = cmyk_to_lab_lut8(y,m,c,k,lut);
+    let (l,a,b) = cmyk_to_lab_lut8(c,m,y,k,lut);
     let (x,y,z) = lab_to_xyz_wp(l,a,b,wp);
     let (r,g,b) = xyz_to_rgb(x as f64,y as f64,z as f64);
 
@@ -26,6 +157,8 @@ pub fn cmyk_to_rgb_lut8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1,wp:&WhitePoint) -> (u8,u8,
 }
 
 
+/// cmyk_to_rgb_from_profile converts with the lut8/lut16 A2B0 tag of a CMYK profile,
+/// other profiles and profiles without A2B0 use the naive `cmyk_to_rgb`
 pub fn cmyk_to_rgb_from_profile(c:u8,m:u8,y:u8,k:u8,decoded:&DecodedICCProfile) -> (u8,u8,u8) {
     if decoded.color_space == 0x434d594b {  // CMYK
         let lut = decoded.tags.get("A2B0");
@@ -44,6 +177,46 @@ pub fn cmyk_to_rgb_from_profile(c:u8,m:u8,y:u8,k:u8,decoded:&DecodedICCProfile)
             }
         }
     }
-    // not has profile
+    // no usable A2B0
     cmyk_to_rgb(c, m, y, k)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn naive_cmyk_rgb() {
+        assert_eq!(cmyk_to_rgb(0,0,0,0),(255,255,255));
+        assert_eq!(cmyk_to_rgb(255,255,255,255),(0,0,0));
+        assert_eq!(cmyk_to_rgb(255,0,0,0),(0,255,255));
+        assert_eq!(cmyk_to_rgb(0,0,0,128),(127,127,127));
+
+        let options = |black_generation| SeparationOptions { black_generation,..SeparationOptions::default() };
+        assert_eq!(rgb_to_cmyk(128,128,128,&options(BlackGeneration::None)),(127,127,127,0));
+        assert_eq!(rgb_to_cmyk(128,128,128,&options(BlackGeneration::Max)),(0,0,0,127));
+        assert_eq!(rgb_to_cmyk(0,0,0,&SeparationOptions::default()),(191,191,191,191));
+        let limited = SeparationOptions { black_generation: BlackGeneration::Max,total_ink_limit: 2.5,max_black: 0.5,under_color_removal: 1.0 };
+        let cmyk = rgb_to_cmyk_f64([0.0,0.0,0.0],&limited);
+        assert!((cmyk.iter().sum::<f64>() - 2.5).abs() < 1e-9 && cmyk[3] == 0.5,"{:?}",cmyk);
+        for generation in [BlackGeneration::None,BlackGeneration::Light,BlackGeneration::Medium,BlackGeneration::Heavy,BlackGeneration::Max] {
+            let (c,m,y,k) = rgb_to_cmyk(200,100,50,&options(generation));
+            let (r,g,b) = cmyk_to_rgb(c,m,y,k);
+            assert!((r as i32 - 200).abs() <= 2 && (g as i32 - 100).abs() <= 2 && (b as i32 - 50).abs() <= 2,"{:?}",(r,g,b));
+        }
+        let custom = BlackGeneration::Custom { start: 0.25,strength: 0.75 };
+        for gray in [0.0,0.2,0.5,0.9,1.0] {
+            assert_eq!(custom.black(gray),BlackGeneration::Medium.black(gray));
+        }
+        assert_eq!(BlackGeneration::Custom { start: 1.0,strength: 1.0 }.black(1.0),0.0);
+        let ucr = |under_color_removal| SeparationOptions { black_generation: BlackGeneration::Max,under_color_removal,..SeparationOptions::default() };
+        assert_eq!(rgb_to_cmyk(128,128,128,&ucr(0.0)),(127,127,127,127));
+        assert_eq!(rgb_to_cmyk(128,128,128,&ucr(0.5)),(64,64,64,127));
+        assert_eq!(cmyk_to_rgb_entries(&[255,0,0,0,0,0,0,255],2).unwrap(),vec![0,255,255,0,0,0]);
+
+        // no A2B0 falls back to the naive conversion
+        let builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"CMYK"),u32::from_be_bytes(*b"Lab "));
+        let decoded = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        assert_eq!(cmyk_to_rgb_from_profile(0,255,255,0,&decoded),(255,0,0));
+    }
+}
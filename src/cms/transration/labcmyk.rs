@@ -1,76 +1,63 @@
-// La*b* 0-100 -127-127 -127-127
-use crate::cms::transration::d3_to_d4_lut8;
-use crate::cms::transration::d3_to_d4_lut16;
+// La*b* 0-100 -128-127 -128-127, u8 Lab is the lut8 encoding (L 255 = 100.0, a/b 128 = 0.0)
+use crate::cms::pcs::PcsEncoding;
 use crate::{Mft1,Mft2};
 use std::io::Result;
 use std::io::{Error,ErrorKind};
 
-pub fn lab_to_cmyk_lut16_u8(l:u8,a:u8,b:u8,lut:&Mft2) -> (u8,u8,u8,u8) {
+/// lab_eval_lut16 converts Lab to normalized CMYK, lut16 uses the legacy 16 bit Lab encoding
+fn lab_eval_lut16(lab:[f64;3],lut:&Mft2) -> [f64;4] {
+    let out = lut.eval(&PcsEncoding::Lab16Legacy.encode(lab));
+    [0,1,2,3].map(|i| out.get(i).copied().unwrap_or(0.0))
+}
+
+fn lab_eval_lut8(lab:[f64;3],lut:&Mft1) -> [f64;4] {
+    let out = lut.eval(&PcsEncoding::Lab8.encode(lab));
+    [0,1,2,3].map(|i| out.get(i).copied().unwrap_or(0.0))
+}
 
-    let (c,m,y,k) = lab_to_cmyk_lut16(l ,a ,b, lut);
-    
-    let c = (c / 65535.0 * 255.0 + 0.5) as u8;
-    let m = (m / 65535.0 * 255.0 + 0.5) as u8;
-    let y = (y / 65535.0 * 255.0 + 0.5) as u8;
-    let k = (k / 65535.0 * 255.0 + 0.5) as u8;
+/// lab_to_cmyk_lut16_u8 converts u8 Lab in the lut8 encoding (a/b 128 = 0.0) to u8 CMYK
+pub fn lab_to_cmyk_lut16_u8(l:u8,a:u8,b:u8,lut:&Mft2) -> (u8,u8,u8,u8) {
+    let [c,m,y,k] = lab_eval_lut16(PcsEncoding::Lab8.decode_u8([l,a,b]),lut)
+        .map(|val| (val * 255.0).round() as u8);
     (c,m,y,k)
 }
 
+/// lab_to_cmyk_lut16 converts u8 Lab in the lut8 encoding (a/b 128 = 0.0) to CMYK 0.0-100.0
 pub fn lab_to_cmyk_lut16(l:u8,a:u8,b:u8,lut:&Mft2) -> (f64,f64,f64,f64) {
-
-    let (c,m,y,k) = d3_to_d4_lut16(l,a,b,lut);
-
-    let c = c / 65535.0 * 100.0;
-    let m = m / 65535.0 * 100.0;
-    let y = y / 65535.0 * 100.0;
-    let k = k / 65535.0 * 100.0;
-    (c,m,y,k)
+    lab_f64_to_cmyk_lut16_array(PcsEncoding::Lab8.decode_u8([l,a,b]),lut)
 }
 
 pub fn lab_f64_to_cmyk_lut16(l:f64,a:f64,b:f64,lut:&Mft2) -> (f64,f64,f64,f64) {
-    let l = (l / 100.0 * 255.0) as u8;
-    let a = (a + 127.5) as u8;
-    let b = (b + 127.5) as u8;
-    let (c,m,y,k) = d3_to_d4_lut16(l,a,b,lut);
-
-    let c = c / 65535.0 * 100.0;
-    let m = m / 65535.0 * 100.0;
-    let y = y / 65535.0 * 100.0;
-    let k = k / 65535.0 * 100.0;
-    (c,m,y,k)
+    lab_f64_to_cmyk_lut16_array([l,a,b],lut)
 }
 
+fn lab_f64_to_cmyk_lut16_array(lab:[f64;3],lut:&Mft2) -> (f64,f64,f64,f64) {
+    let [c,m,y,k] = lab_eval_lut16(lab,lut).map(|val| val * 100.0);
+    (c,m,y,k)
+}
 
 pub fn lab_to_cmyk_lut8(l:u8,a:u8,b:u8,lut:&Mft1) -> (f64,f64,f64,f64) {
-    let (c,m,y,k) = d3_to_d4_lut8(l,a,b,lut);
-
-    let c = c as f64 / 255.0 * 100.0;
-    let m = m as f64 / 255.0 * 100.0;
-    let y = y as f64 / 255.0 * 100.0;
-    let k = k as f64 / 255.0 * 100.0;
-    (c,m,y,k)
+    let [l,a,b] = PcsEncoding::Lab8.decode_u8([l,a,b]);
+    lab_f64_to_cmyk_lut8(l,a,b,lut)
 }
 
 pub fn lab_f64_to_cmyk_lut8(l:f64,a:f64,b:f64,lut:&Mft1) -> (f64,f64,f64,f64) {
-    let l = (l / 100.0 * 255.0 + 0.5) as u8;
-    let a = (a + 127.5) as u8;
-    let b = (b + 127.5) as u8;
-    lab_to_cmyk_lut8(l,a,b,lut)
+    let [c,m,y,k] = lab_eval_lut8([l,a,b],lut).map(|val| val * 100.0);
+    (c,m,y,k)
 }
 
-// no test
 pub fn lab_to_cmyk_lut8_u8(l:u8,a:u8,b:u8,lut:&Mft1) -> (u8,u8,u8,u8) {
-    d3_to_d4_lut8(l,a,b,lut)
+    let [l,a,b] = PcsEncoding::Lab8.decode_u8([l,a,b]);
+    lab_f64_to_cmyk_lut8_u8(l,a,b,lut)
 }
 
 pub fn lab_f64_to_cmyk_lut8_u8(l:f64,a:f64,b:f64,lut:&Mft1) -> (u8,u8,u8,u8) {
-    let l = (l / 100.0 * 255.0 + 0.5) as u8;
-    let a = (a + 127.5) as u8;
-    let b = (b + 127.5) as u8;
-    d3_to_d4_lut8(l,a,b,lut)
+    let [c,m,y,k] = lab_eval_lut8([l,a,b],lut).map(|val| (val * 255.0).round() as u8);
+    (c,m,y,k)
 }
 
 
+/// lab_to_cmyk_entries_lut16_u8 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_to_cmyk_entries_lut16_u8(buf:&[u8],entries: usize,lut:&Mft2) -> Result<Vec<u8>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -78,7 +65,7 @@ pub fn lab_to_cmyk_entries_lut16_u8(buf:&[u8],entries: usize,lut:&Mft2) -> Resul
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
@@ -93,6 +80,7 @@ pub fn lab_to_cmyk_entries_lut16_u8(buf:&[u8],entries: usize,lut:&Mft2) -> Resul
     Ok(buffer)
 }
 
+/// lab_to_cmyk_entries_lut16 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_to_cmyk_entries_lut16(buf:&[u8],entries: usize,lut:&Mft2) -> Result<Vec<f64>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -100,7 +88,7 @@ pub fn lab_to_cmyk_entries_lut16(buf:&[u8],entries: usize,lut:&Mft2) -> Result<V
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
@@ -115,6 +103,7 @@ pub fn lab_to_cmyk_entries_lut16(buf:&[u8],entries: usize,lut:&Mft2) -> Result<V
     Ok(buffer)
 }
 
+/// lab_to_cmyk_entries_lut8 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_to_cmyk_entries_lut8(buf:&[u8],entries: usize,lut:&Mft1) -> Result<Vec<f64>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -122,7 +111,7 @@ pub fn lab_to_cmyk_entries_lut8(buf:&[u8],entries: usize,lut:&Mft1) -> Result<Ve
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
@@ -138,6 +127,7 @@ pub fn lab_to_cmyk_entries_lut8(buf:&[u8],entries: usize,lut:&Mft1) -> Result<Ve
 }
 
 
+/// lab_to_cmyk_entries_lut8_u8 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_to_cmyk_entries_lut8_u8(buf:&[u8],entries: usize,lut:&Mft1) -> Result<Vec<u8>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -145,7 +135,7 @@ pub fn lab_to_cmyk_entries_lut8_u8(buf:&[u8],entries: usize,lut:&Mft1) -> Result
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
@@ -160,6 +150,7 @@ pub fn lab_to_cmyk_entries_lut8_u8(buf:&[u8],entries: usize,lut:&Mft1) -> Result
     Ok(buffer)
 }
 
+/// lab_f64_to_cmyk_entries_lut8 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_f64_to_cmyk_entries_lut8(buf:&[f64],entries: usize,lut:&Mft1) -> Result<Vec<f64>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -167,14 +158,11 @@ pub fn lab_f64_to_cmyk_entries_lut8(buf:&[f64],entries: usize,lut:&Mft1) -> Resu
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
-        let l = (l / 100.0 * 255.0 + 0.5) as u8;
-        let a = (a + 127.5) as u8;
-        let b = (b + 127.5) as u8;
-        let (c,m,y,k) = lab_to_cmyk_lut8(l,a,b,lut);
+        let (c,m,y,k) = lab_f64_to_cmyk_lut8(l,a,b,lut);
 
         buffer.push(c);
         buffer.push(m);
@@ -186,6 +174,7 @@ pub fn lab_f64_to_cmyk_entries_lut8(buf:&[f64],entries: usize,lut:&Mft1) -> Resu
 }
 
 
+/// lab_f64_to_cmyk_entries_lut8_u8 reads 3 values (L,a,b) per entry and writes 4 (C,M,Y,K)
 pub fn lab_f64_to_cmyk_entries_lut8_u8(buf:&[f64],entries: usize,lut:&Mft1) -> Result<Vec<u8>> {
     if buf.len() < entries *3 {
         return Err(Error::new(ErrorKind::Other, "Data shotage"))
@@ -193,14 +182,11 @@ pub fn lab_f64_to_cmyk_entries_lut8_u8(buf:&[f64],entries: usize,lut:&Mft1) -> R
     let mut buffer = Vec::with_capacity(entries * 4);
 
     for i in 0..entries {
-        let ptr = i * 4;
+        let ptr = i * 3;
         let l = buf[ptr];
         let a = buf[ptr + 1];
         let b = buf[ptr + 2];
-        let l = (l / 100.0 * 255.0 + 0.5) as u8;
-        let a = (a + 127.5) as u8;
-        let b = (b + 127.5) as u8;
-        let (c,m,y,k) = lab_to_cmyk_lut8_u8(l,a,b,lut);
+        let (c,m,y,k) = lab_f64_to_cmyk_lut8_u8(l,a,b,lut);
 
         buffer.push(c);
         buffer.push(m);
@@ -211,3 +197,37 @@ pub fn lab_f64_to_cmyk_entries_lut8_u8(buf:&[f64],entries: usize,lut:&Mft1) -> R
     Ok(buffer)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iccprofile::*;
+    use crate::test_utils::*;
+
+    /// Lab to CMYK, C, M and Y are the encoded L, a and b, K is 0
+    fn lut16() -> Mft2 {
+        let mft2 = lut16_fixture(3,4,2,&|p| vec![(p[0] * 65535.0) as u16,(p[1] * 65535.0) as u16,(p[2] * 65535.0) as u16,0]);
+        match Data::parse(&tag("mft2",&mft2),mft2.len() + 8,0x02100000).unwrap().1 {
+            Data::Lut16(lut) => lut,
+            _ => panic!("not a lut16"),
+        }
+    }
+
+    #[test]
+    fn lab_entries_stride() {
+        let lut = lut16();
+        let buf = [255,128,128,0,128,255];
+        let cmyk = lab_to_cmyk_entries_lut16_u8(&buf,2,&lut).unwrap();
+        let (c,m,y,k) = lab_to_cmyk_lut16_u8(0,128,255,&lut);
+        assert_eq!(cmyk[4..],[c,m,y,k]);
+        assert_eq!(cmyk.len(),8);
+        assert!(lab_to_cmyk_entries_lut16(&buf[..5],2,&lut).is_err());
+    }
+
+    #[test]
+    fn lab8_input() {
+        let lut = lut16();
+        // a/b 128 is 0.0, not the old +127.5 offset
+        assert_eq!(lab_to_cmyk_lut16(255,128,128,&lut),lab_f64_to_cmyk_lut16(100.0,0.0,0.0,&lut));
+        assert_eq!(lab_to_cmyk_lut16(0,0,255,&lut),lab_f64_to_cmyk_lut16(0.0,-128.0,127.0,&lut));
+    }
+}
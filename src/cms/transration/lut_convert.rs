@@ -1,502 +1,120 @@
-use crate::iccprofile::ICCNumber;
+//! 3 and 4 channel lut8/lut16 lookups on u8 values
+//! These wrap `Mft1::eval` and `Mft2::eval`, use `Data::eval_lut` for any other channel count.
+//! The 4 input lookups take device values (A2Bx), the 3 input lookups take PCS values (B2Ax).
+//! u8 PCS values are 8 bit Lab (`PcsEncoding::Lab8`), lut16 lookups re-encode them as `PcsEncoding::Lab16Legacy`,
+//! which also makes them the high byte of u1Fixed15 XYZ.
+//! lut16 outputs are 0.0-65535.0 and lut8 outputs are 0-255, PCS outputs are in the encoding of the tag.
+//! The 3 input lookups apply the e_params matrix with `eval_xyz`, it is identity unless the input is PCSXYZ.
+use crate::cms::pcs::PcsEncoding;
 use crate::{Mft1,Mft2};
 
-pub fn d4_to_d3_lut16(c1:u8,c2:u8,c3:u8,c4:u8,lut:&Mft2) -> (f64,f64,f64) {
-    let grid_points = lut.number_of_clut_grid_points as usize;
-
-    let delta= 65535 / (lut.input_table_enteries as usize) ;
-    let c1 = (c1 as usize * 256 + c1 as usize) / lut.input_table_enteries as usize;
-    let c1_delta= (c1 as usize * 256 + c1 as usize) % lut.input_table_enteries as usize;
-    let c2 = (c2 as usize * 256 + c2 as usize) / lut.input_table_enteries as usize;
-    let c2_delta= (c2 as usize * 256 + c2 as usize) % lut.input_table_enteries as usize;
-    let c3 = (c3 as usize * 256 + c3 as usize) / lut.input_table_enteries as usize;
-    let c3_delta= (c3 as usize * 256 + c3 as usize) % lut.input_table_enteries as usize;
-    let c4 = (c4 as usize * 256 + c4 as usize) / lut.input_table_enteries as usize;
-    let c4_delta= (c4 as usize * 256 + c4 as usize) % lut.input_table_enteries as usize;
-
-    let c11 = lut.input_table [c1 as usize];
-    let c21 = lut.input_table [c2 as usize + lut.input_table_enteries as usize];
-    let c31 = lut.input_table [c3 as usize + lut.input_table_enteries as usize * 2];
-    let c41 = lut.input_table [c4 as usize + lut.input_table_enteries as usize * 3];
-
-    let c12 = if c1 < lut.input_table_enteries as usize - 1 {lut.input_table [c1 as usize + 1]} else {c11};
-    let c22 = if c2 < lut.input_table_enteries as usize - 1 {lut.input_table [c2 as usize + 1 + lut.input_table_enteries as usize]} else {c21};
-    let c32 = if c3 < lut.input_table_enteries as usize - 1 {lut.input_table [c3 as usize + 1 + lut.input_table_enteries as usize * 2]} else {c31};
-    let c42 = if c4 < lut.input_table_enteries as usize - 1 {lut.input_table [c4 as usize + 1 + lut.input_table_enteries as usize * 3]} else {c41};
-
-    let c1_delta= c1_delta as f64 / delta as f64;
-    let c2_delta= c2_delta as f64 / delta as f64;
-    let c3_delta= c3_delta as f64 / delta as f64;
-    let c4_delta= c4_delta as f64 / delta as f64;
-
-    let c1 = c11 as isize + ((c12 as isize - c11 as isize) as f64 * c1_delta) as isize;
-    let c2 = c21 as isize + ((c22 as isize - c21 as isize) as f64 * c2_delta) as isize;
-    let c3 = c31 as isize + ((c32 as isize - c31 as isize) as f64 * c3_delta) as isize;
-    let c4 = c41 as isize + ((c42 as isize - c41 as isize) as f64 * c4_delta) as isize;
-
-    let c1_grid = ((c1 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c4_grid = ((c4 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-
-    let c1_grid_delta= ((c1 as f64 / 65536.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta= ((c2 as f64 / 65536.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta= ((c3 as f64 / 65536.0) * grid_points as f64) - c3_grid as f64;
-    let c4_grid_delta= ((c4 as f64 / 65536.0) * grid_points as f64) - c4_grid as f64;
-    let grid_delta= c1_grid_delta* c2_grid_delta* c3_grid_delta* c4_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(3) + c2_grid * grid_points.pow(2)  + c3_grid * grid_points + c4_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let c4_grid2 = if c4_grid < grid_points - 1 {c4_grid + 1} else {c4_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(3) + c2_grid2 * grid_points.pow(2)  + c3_grid2 * grid_points + c4_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-    
-    let dev = 65535.0 / (lut.output_table_enteries as f64 -1.0);
-
-    let od1 = (d1 / dev) as usize;
-    let od2 = (d2 / dev) as usize;
-    let od3 = (d3 / dev) as usize;
+fn lut16_out<const N:usize>(out:Vec<f64>) -> [f64;N] {
+    std::array::from_fn(|i| out.get(i).copied().unwrap_or(0.0) * 65535.0)
+}
 
-    let od1_delta= d1 / dev - od1 as f64;
-    let od2_delta= d2 / dev - od2 as f64;
-    let od3_delta= d3 / dev - od3 as f64;
+fn lut8_out<const N:usize>(out:Vec<f64>) -> [u8;N] {
+    std::array::from_fn(|i| (out.get(i).copied().unwrap_or(0.0) * 255.0).round().clamp(0.0,255.0) as u8)
+}
 
-    let d11 = lut.output_table [od1 as usize];
-    let d21 = lut.output_table [od2 as usize + lut.output_table_enteries as usize];
-    let d31 = lut.output_table [od3 as usize + lut.output_table_enteries as usize * 2];
-    let d12 = if d11 < lut.output_table_enteries - 1 {lut.output_table[od1 as usize + 1]} else {d11};
-    let d22 = if d21 < lut.output_table_enteries - 1 {lut.output_table[od2 as usize + 1 + lut.output_table_enteries as usize]} else {d21};
-    let d32 = if d31 < lut.output_table_enteries - 1 {lut.output_table[od3 as usize + 1 + lut.output_table_enteries as usize * 2]} else {d31};
+fn normalize<const N:usize>(vals:[u8;N]) -> [f64;N] {
+    vals.map(|val| val as f64 / 255.0)
+}
 
-    let d1 = d11 as f64 * (1.0 - od1_delta) + d12 as f64 * od1_delta;
-    let d2 = d21 as f64 * (1.0 - od2_delta) + d22 as f64 * od2_delta;
-    let d3 = d31 as f64 * (1.0 - od3_delta) + d32 as f64 * od3_delta;
+/// lut16_pcs converts 8 bit PCS values to the lut16 encoding
+fn lut16_pcs(vals:[u8;3]) -> [f64;3] {
+    PcsEncoding::Lab16Legacy.encode(PcsEncoding::Lab8.decode_u8(vals))
+}
 
+pub fn d4_to_d3_lut16(c1:u8,c2:u8,c3:u8,c4:u8,lut:&Mft2) -> (f64,f64,f64) {
+    let [d1,d2,d3] = lut16_out(lut.eval(&normalize([c1,c2,c3,c4])));
     (d1,d2,d3)
 }
 
-
 pub fn d4_to_d3_lut8(c:u8,m:u8,y:u8,k:u8,lut:&Mft1) -> (u8,u8,u8) {
-    let grid_points = lut.number_of_clut_grid_points as usize;
-
-    let c = lut.input_table [c as usize];
-    let m = lut.input_table [m as usize + 256];
-    let y = lut.input_table [y as usize + 256 * 2];
-    let k = lut.input_table [k as usize + 256 * 3];
-
-    let c_grid = ((y as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let m_grid = ((m as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let y_grid = ((c as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let k_grid = ((k as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c_grid_delta = ((y as f64 / 255.0) * grid_points as f64) - c_grid as f64;
-    let m_grid_delta = ((m as f64 / 255.0) * grid_points as f64) - m_grid as f64;
-    let y_grid_delta = ((c as f64 / 255.0) * grid_points as f64) - y_grid as f64;
-    let k_grid_delta = ((k as f64 / 255.0) * grid_points as f64) - k_grid as f64;
-    let grid_delta = c_grid_delta * m_grid_delta * y_grid_delta * k_grid_delta;
-
-    let grid = c_grid * grid_points.pow(3) + m_grid * grid_points.pow(2)  + y_grid * grid_points + k_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c_grid2 = if c_grid < grid_points - 1 {c_grid + 1} else {c_grid}; 
-    let m_grid2 = if m_grid < grid_points - 1 {m_grid + 1} else {m_grid}; 
-    let y_grid2 = if y_grid < grid_points - 1 {y_grid + 1} else {y_grid}; 
-    let k_grid2 = if k_grid < grid_points - 1 {k_grid + 1} else {k_grid}; 
-    let grid2 = c_grid2 * grid_points.pow(3) + m_grid2 * grid_points.pow(2)  + y_grid2 * grid_points + k_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-    let d1 = lut.output_table[(d1 as usize).clamp(0,255)];
-    let d2 = lut.output_table[(d2 as usize).clamp(0,255) + 256];
-    let d3 = lut.output_table[(d3 as usize).clamp(0,255) + 512];
-
+    let [d1,d2,d3] = lut8_out(lut.eval(&normalize([c,m,y,k])));
     (d1,d2,d3)
 }
 
-
 pub fn d3_to_d4_lut16(c1:u8,c2:u8,c3:u8,lut:&Mft2) -> (f64,f64,f64,f64) {
-    let c1 = c1 as f64;
-    let c2 = c2 as f64;
-    let c3 = c3 as f64;
-
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 + e[1].as_f64() * c2 + e[2].as_f64() * c3;
-    let c2_1 = e[3].as_f64() * c1 + e[4].as_f64() * c2 + e[5].as_f64() * c3;
-    let c3_1 = e[6].as_f64() * c1 + e[7].as_f64() * c2 + e[8].as_f64() * c3;
-
-    let (c1,c2,c3) = (c1_1,c2_1,c3_1);
-
-
-    let grid_points = lut.number_of_clut_grid_points as usize;
-
-    let delta= 65535 / (lut.input_table_enteries as usize) ;
-    let c1 = (c1 as usize * 256 + c1 as usize) / lut.input_table_enteries as usize;
-    let c1_delta= (c1 as usize * 256 + c1 as usize) % lut.input_table_enteries as usize;
-    let c2 = (c2 as usize * 256 + c2 as usize) / lut.input_table_enteries as usize;
-    let c2_delta= (c2 as usize * 256 + c2 as usize) % lut.input_table_enteries as usize;
-    let c3 = (c3 as usize * 256 + c3 as usize) / lut.input_table_enteries as usize;
-    let c3_delta= (c3 as usize * 256 + c3 as usize) % lut.input_table_enteries as usize;
-
-    let c11 = lut.input_table [c1 as usize];
-    let c21 = lut.input_table [c2 as usize + lut.input_table_enteries as usize];
-    let c31 = lut.input_table [c3 as usize + lut.input_table_enteries as usize * 2];
-
-    let c12 = if c1 < lut.input_table_enteries as usize - 1 {lut.input_table [c1 as usize + 1]} else {c11};
-    let c22 = if c2 < lut.input_table_enteries as usize - 1 {lut.input_table [c2 as usize + 1 + lut.input_table_enteries as usize]} else {c21};
-    let c32 = if c3 < lut.input_table_enteries as usize - 1 {lut.input_table [c3 as usize + 1 + lut.input_table_enteries as usize * 2]} else {c31};
-
-    let c1_delta= c1_delta as f64 / delta as f64;
-    let c2_delta= c2_delta as f64 / delta as f64;
-    let c3_delta= c3_delta as f64 / delta as f64;
-
-    let c1 = c11 as isize + ((c12 as isize - c11 as isize) as f64 * c1_delta) as isize;
-    let c2 = c21 as isize + ((c22 as isize - c21 as isize) as f64 * c2_delta) as isize;
-    let c3 = c31 as isize + ((c32 as isize - c31 as isize) as f64 * c3_delta) as isize;
-
-    let c1_grid = ((c1 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-
-    let c1_grid_delta= ((c1 as f64 / 65536.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta= ((c2 as f64 / 65536.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta= ((c3 as f64 / 65536.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta= c1_grid_delta* c2_grid_delta* c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2) + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-    let d4 = lut.clut_values[grid+3] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+3] as f64 * grid_delta;
-    
-    let dev = 65535.0 / (lut.output_table_enteries as f64 -1.0);
-
-    let od1 = (d1 / dev) as usize;
-    let od2 = (d2 / dev) as usize;
-    let od3 = (d3 / dev) as usize;
-    let od4 = (d4 / dev) as usize;
-
-    let od1_delta= d1 / dev - od1 as f64;
-    let od2_delta= d2 / dev - od2 as f64;
-    let od3_delta= d3 / dev - od3 as f64;
-    let od4_delta= d4 / dev - od4 as f64;
-
-    let d11 = lut.output_table [od1 as usize];
-    let d21 = lut.output_table [od2 as usize + lut.output_table_enteries as usize];
-    let d31 = lut.output_table [od3 as usize + lut.output_table_enteries as usize * 2];
-    let d41 = lut.output_table [od3 as usize + lut.output_table_enteries as usize * 3];
-
-    let d12 = if d11 < lut.output_table_enteries - 1 {lut.output_table[od1 as usize + 1]} else {d11};
-    let d22 = if d21 < lut.output_table_enteries - 1 {lut.output_table[od2 as usize + 1 + lut.output_table_enteries as usize]} else {d21};
-    let d32 = if d31 < lut.output_table_enteries - 1 {lut.output_table[od3 as usize + 1 + lut.output_table_enteries as usize * 2]} else {d31};
-    let d42 = if d41 < lut.output_table_enteries - 1 {lut.output_table[od4 as usize + 1 + lut.output_table_enteries as usize * 3]} else {d41};
-
-    let d1 = d11 as f64 * (1.0 - od1_delta) + d12 as f64 * od1_delta;
-    let d2 = d21 as f64 * (1.0 - od2_delta) + d22 as f64 * od2_delta;
-    let d3 = d31 as f64 * (1.0 - od3_delta) + d32 as f64 * od3_delta;
-    let d4 = d41 as f64 * (1.0 - od4_delta) + d42 as f64 * od4_delta;
-
+    let [d1,d2,d3,d4] = lut16_out(lut.eval_xyz(&lut16_pcs([c1,c2,c3])));
     (d1,d2,d3,d4)
 }
 
 pub fn d3_to_d3_lut16(c1:u8,c2:u8,c3:u8,lut:&Mft2) -> (f64,f64,f64) {
-    let c1 = c1 as f64;
-    let c2 = c2 as f64;
-    let c3 = c3 as f64;
-
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 + e[1].as_f64() * c2 + e[2].as_f64() * c3;
-    let c2_1 = e[3].as_f64() * c1 + e[4].as_f64() * c2 + e[5].as_f64() * c3;
-    let c3_1 = e[6].as_f64() * c1 + e[7].as_f64() * c2 + e[8].as_f64() * c3;
-
-    let (c1,c2,c3) = (c1_1,c2_1,c3_1);
-
-
-    let grid_points = lut.number_of_clut_grid_points as usize;
-
-    let delta= 65535 / (lut.input_table_enteries as usize) ;
-    let c1 = (c1 as usize * 256 + c1 as usize) / lut.input_table_enteries as usize;
-    let c1_delta= (c1 as usize * 256 + c1 as usize) % lut.input_table_enteries as usize;
-    let c2 = (c2 as usize * 256 + c2 as usize) / lut.input_table_enteries as usize;
-    let c2_delta= (c2 as usize * 256 + c2 as usize) % lut.input_table_enteries as usize;
-    let c3 = (c3 as usize * 256 + c3 as usize) / lut.input_table_enteries as usize;
-    let c3_delta= (c3 as usize * 256 + c3 as usize) % lut.input_table_enteries as usize;
-
-    let c11 = lut.input_table [c1 as usize];
-    let c21 = lut.input_table [c2 as usize + lut.input_table_enteries as usize];
-    let c31 = lut.input_table [c3 as usize + lut.input_table_enteries as usize * 2];
-
-    let c12 = if c1 < lut.input_table_enteries as usize - 1 {lut.input_table [c1 as usize + 1]} else {c11};
-    let c22 = if c2 < lut.input_table_enteries as usize - 1 {lut.input_table [c2 as usize + 1 + lut.input_table_enteries as usize]} else {c21};
-    let c32 = if c3 < lut.input_table_enteries as usize - 1 {lut.input_table [c3 as usize + 1 + lut.input_table_enteries as usize * 2]} else {c31};
-
-    let c1_delta= c1_delta as f64 / delta as f64;
-    let c2_delta= c2_delta as f64 / delta as f64;
-    let c3_delta= c3_delta as f64 / delta as f64;
-
-    let c1 = c11 as isize + ((c12 as isize - c11 as isize) as f64 * c1_delta) as isize;
-    let c2 = c21 as isize + ((c22 as isize - c21 as isize) as f64 * c2_delta) as isize;
-    let c3 = c31 as isize + ((c32 as isize - c31 as isize) as f64 * c3_delta) as isize;
-
-    let c1_grid = ((c1 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-
-    let c1_grid_delta= ((c1 as f64 / 65536.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta= ((c2 as f64 / 65536.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta= ((c3 as f64 / 65536.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta= c1_grid_delta* c2_grid_delta* c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2) + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-    
-    let dev = 65535.0 / (lut.output_table_enteries as f64 -1.0);
-
-    let od1 = (d1 / dev) as usize;
-    let od2 = (d2 / dev) as usize;
-    let od3 = (d3 / dev) as usize;
- 
-    let od1_delta= d1 / dev - od1 as f64;
-    let od2_delta= d2 / dev - od2 as f64;
-    let od3_delta= d3 / dev - od3 as f64;
-
-    let d11 = lut.output_table [od1 as usize];
-    let d21 = lut.output_table [od2 as usize + lut.output_table_enteries as usize];
-    let d31 = lut.output_table [od3 as usize + lut.output_table_enteries as usize * 2];
-
-    let d12 = if d11 < lut.output_table_enteries - 1 {lut.output_table[od1 as usize + 1]} else {d11};
-    let d22 = if d21 < lut.output_table_enteries - 1 {lut.output_table[od2 as usize + 1 + lut.output_table_enteries as usize]} else {d21};
-    let d32 = if d31 < lut.output_table_enteries - 1 {lut.output_table[od3 as usize + 1 + lut.output_table_enteries as usize * 2]} else {d31};
-
-    let d1 = d11 as f64 * (1.0 - od1_delta) + d12 as f64 * od1_delta;
-    let d2 = d21 as f64 * (1.0 - od2_delta) + d22 as f64 * od2_delta;
-    let d3 = d31 as f64 * (1.0 - od3_delta) + d32 as f64 * od3_delta;
-
+    let [d1,d2,d3] = lut16_out(lut.eval_xyz(&lut16_pcs([c1,c2,c3])));
     (d1,d2,d3)
 }
 
 pub fn d3_to_d1_lut16(c1:u8,c2:u8,c3:u8,lut:&Mft2) -> f64 {
-    let c1 = c1 as f64;
-    let c2 = c2 as f64;
-    let c3 = c3 as f64;
-
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 + e[1].as_f64() * c2 + e[2].as_f64() * c3;
-    let c2_1 = e[3].as_f64() * c1 + e[4].as_f64() * c2 + e[5].as_f64() * c3;
-    let c3_1 = e[6].as_f64() * c1 + e[7].as_f64() * c2 + e[8].as_f64() * c3;
-
-    let (c1,c2,c3) = (c1_1,c2_1,c3_1);
-
-
-    let grid_points = lut.number_of_clut_grid_points as usize;
-
-    let delta= 65535 / (lut.input_table_enteries as usize) ;
-    let c1 = (c1 as usize * 256 + c1 as usize) / lut.input_table_enteries as usize;
-    let c1_delta= (c1 as usize * 256 + c1 as usize) % lut.input_table_enteries as usize;
-    let c2 = (c2 as usize * 256 + c2 as usize) / lut.input_table_enteries as usize;
-    let c2_delta= (c2 as usize * 256 + c2 as usize) % lut.input_table_enteries as usize;
-    let c3 = (c3 as usize * 256 + c3 as usize) / lut.input_table_enteries as usize;
-    let c3_delta= (c3 as usize * 256 + c3 as usize) % lut.input_table_enteries as usize;
-
-    let c11 = lut.input_table [c1 as usize];
-    let c21 = lut.input_table [c2 as usize + lut.input_table_enteries as usize];
-    let c31 = lut.input_table [c3 as usize + lut.input_table_enteries as usize * 2];
-
-    let c12 = if c1 < lut.input_table_enteries as usize - 1 {lut.input_table [c1 as usize + 1]} else {c11};
-    let c22 = if c2 < lut.input_table_enteries as usize - 1 {lut.input_table [c2 as usize + 1 + lut.input_table_enteries as usize]} else {c21};
-    let c32 = if c3 < lut.input_table_enteries as usize - 1 {lut.input_table [c3 as usize + 1 + lut.input_table_enteries as usize * 2]} else {c31};
-
-    let c1_delta= c1_delta as f64 / delta as f64;
-    let c2_delta= c2_delta as f64 / delta as f64;
-    let c3_delta= c3_delta as f64 / delta as f64;
-
-    let c1 = c11 as isize + ((c12 as isize - c11 as isize) as f64 * c1_delta) as isize;
-    let c2 = c21 as isize + ((c22 as isize - c21 as isize) as f64 * c2_delta) as isize;
-    let c3 = c31 as isize + ((c32 as isize - c31 as isize) as f64 * c3_delta) as isize;
-
-    let c1_grid = ((c1 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 65536.0) * grid_points as f64).floor() as usize;
-
-    let c1_grid_delta= ((c1 as f64 / 65536.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta= ((c2 as f64 / 65536.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta= ((c3 as f64 / 65536.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta= c1_grid_delta* c2_grid_delta* c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2) + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    
-    let dev = 65535.0 / (lut.output_table_enteries as f64 -1.0);
-
-    let od1 = (d1 / dev) as usize;
-
-    let od1_delta= d1 / dev - od1 as f64;
-
-    let d11 = lut.output_table [od1 as usize];
-
-    let d12 = if d11 < lut.output_table_enteries - 1 {lut.output_table[od1 as usize + 1]} else {d11};
-
-    let d1 = d11 as f64 * (1.0 - od1_delta) + d12 as f64 * od1_delta;
-
+    let [d1] = lut16_out(lut.eval_xyz(&lut16_pcs([c1,c2,c3])));
     d1
 }
 
 pub fn d3_to_d4_lut8(c1:u8,c2:u8,c3:u8,lut:&Mft1) -> (u8,u8,u8,u8) {
-    let grid_points = lut.number_of_clut_grid_points as usize;
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 as f64 + e[1].as_f64() * c2 as f64 + e[2].as_f64() * c3 as f64;
-    let c2_1 = e[3].as_f64() * c1 as f64 + e[4].as_f64() * c2 as f64 + e[5].as_f64() * c3 as f64;
-    let c3_1 = e[6].as_f64() * c1 as f64 + e[7].as_f64() * c2 as f64 + e[8].as_f64() * c3 as f64;
-
-    let c1 = c1_1 as usize;
-    let c2 = c2_1 as usize;
-    let c3 = c3_1 as usize;
-
-    let c1 = lut.input_table [c1 as usize];
-    let c2 = lut.input_table [c2 as usize + 256];
-    let c3 = lut.input_table [c3 as usize + 256 * 2];
-
-    let c1_grid = ((c1 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c1_grid_delta = ((c1 as f64 / 255.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta = ((c2 as f64 / 255.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta = ((c3 as f64 / 255.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta = c1_grid_delta * c2_grid_delta * c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid * grid_points + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2)  + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-    let d4 = lut.clut_values[grid+3] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+3] as f64 * grid_delta;
-
-    let d1 = lut.output_table[(d1 as usize).clamp(0,255)];
-    let d2 = lut.output_table[(d2 as usize).clamp(0,255) + 256];
-    let d3 = lut.output_table[(d3 as usize).clamp(0,255) + 512];
-    let d4 = lut.output_table[(d4 as usize).clamp(0,255) + 768];
-
+    let [d1,d2,d3,d4] = lut8_out(lut.eval_xyz(&normalize([c1,c2,c3])));
     (d1,d2,d3,d4)
 }
 
 pub fn d3_to_d3_lut8(c1:u8,c2:u8,c3:u8,lut:&Mft1) -> (u8,u8,u8) {
-    let grid_points = lut.number_of_clut_grid_points as usize;
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 as f64 + e[1].as_f64() * c2 as f64 + e[2].as_f64() * c3 as f64;
-    let c2_1 = e[3].as_f64() * c1 as f64 + e[4].as_f64() * c2 as f64 + e[5].as_f64() * c3 as f64;
-    let c3_1 = e[6].as_f64() * c1 as f64 + e[7].as_f64() * c2 as f64 + e[8].as_f64() * c3 as f64;
-
-    let c1 = c1_1 as usize;
-    let c2 = c2_1 as usize;
-    let c3 = c3_1 as usize;
-
-    let c1 = lut.input_table [c1 as usize];
-    let c2 = lut.input_table [c2 as usize + 256];
-    let c3 = lut.input_table [c3 as usize + 256 * 2];
-
-    let c1_grid = ((c1 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c1_grid_delta = ((c1 as f64 / 255.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta = ((c2 as f64 / 255.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta = ((c3 as f64 / 255.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta = c1_grid_delta * c2_grid_delta * c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid * grid_points + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2)  + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-    let d2 = lut.clut_values[grid+1] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+1] as f64 * grid_delta;
-    let d3 = lut.clut_values[grid+2] as f64 * (1.0  -grid_delta) + lut.clut_values[grid2+2] as f64 * grid_delta;
-
-    let d1 = lut.output_table[(d1 as usize).clamp(0,255)];
-    let d2 = lut.output_table[(d2 as usize).clamp(0,255) + 256];
-    let d3 = lut.output_table[(d3 as usize).clamp(0,255) + 512];
-
+    let [d1,d2,d3] = lut8_out(lut.eval_xyz(&normalize([c1,c2,c3])));
     (d1,d2,d3)
 }
 
 pub fn d3_to_d1_lut8(c1:u8,c2:u8,c3:u8,lut:&Mft1) -> u8 {
-    let grid_points = lut.number_of_clut_grid_points as usize;
-    let e = &lut.e_params;
-
-    let c1_1 = e[0].as_f64() * c1 as f64 + e[1].as_f64() * c2 as f64 + e[2].as_f64() * c3 as f64;
-    let c2_1 = e[3].as_f64() * c1 as f64 + e[4].as_f64() * c2 as f64 + e[5].as_f64() * c3 as f64;
-    let c3_1 = e[6].as_f64() * c1 as f64 + e[7].as_f64() * c2 as f64 + e[8].as_f64() * c3 as f64;
-
-    let c1 = c1_1 as usize;
-    let c2 = c2_1 as usize;
-    let c3 = c3_1 as usize;
-
-    let c1 = lut.input_table [c1 as usize];
-    let c2 = lut.input_table [c2 as usize + 256];
-    let c3 = lut.input_table [c3 as usize + 256 * 2];
-
-    let c1_grid = ((c1 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c2_grid = ((c2 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c3_grid = ((c3 as f64 / 255.0) * grid_points as f64).floor() as usize;
-    let c1_grid_delta = ((c1 as f64 / 255.0) * grid_points as f64) - c1_grid as f64;
-    let c2_grid_delta = ((c2 as f64 / 255.0) * grid_points as f64) - c2_grid as f64;
-    let c3_grid_delta = ((c3 as f64 / 255.0) * grid_points as f64) - c3_grid as f64;
-    let grid_delta = c1_grid_delta * c2_grid_delta * c3_grid_delta;
-
-    let grid = c1_grid * grid_points.pow(2) + c2_grid * grid_points + c3_grid;
-    let grid = grid * lut.output_channels as usize;
-    let c1_grid2 = if c1_grid < grid_points - 1 {c1_grid + 1} else {c1_grid}; 
-    let c2_grid2 = if c2_grid < grid_points - 1 {c2_grid + 1} else {c2_grid}; 
-    let c3_grid2 = if c3_grid < grid_points - 1 {c3_grid + 1} else {c3_grid}; 
-    let grid2 = c1_grid2 * grid_points.pow(2)  + c2_grid2 * grid_points + c3_grid2;
-    let grid2 = grid2 * lut.output_channels as usize;
- 
-    let d1 = lut.clut_values[grid]   as f64 * (1.0  -grid_delta) + lut.clut_values[grid2]   as f64 * grid_delta;
-
-    
-    let d1 = lut.output_table[(d1 as usize).clamp(0,255)];
-
+    let [d1] = lut8_out(lut.eval_xyz(&normalize([c1,c2,c3])));
     d1
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iccprofile::*;
+    use crate::test_utils::*;
+
+    fn lut16(inputs:usize,outputs:usize) -> Mft2 {
+        parse_lut16(&lut16_fixture(inputs,outputs,3,&|p| (0..outputs).map(|i| (p[i % inputs] * 65535.0) as u16).collect()))
+    }
+
+    fn parse_lut16(mft2:&[u8]) -> Mft2 {
+        match Data::parse(&tag("mft2",mft2),mft2.len() + 8,0x02100000).unwrap().1 {
+            Data::Lut16(lut) => lut,
+            _ => panic!("not a lut16"),
+        }
+    }
+
+    #[test]
+    fn lut_convert_eval() {
+        let (c,m,y) = d4_to_d3_lut16(0,128,255,64,&lut16(4,3));
+        assert!(c.abs() < 1.0 && (m - 128.0 * 257.0).abs() < 1.0 && (y - 65535.0).abs() < 1.0);
+        // 8 bit PCS values become the high bytes of the lut16 input
+        let (c,m,y,k) = d3_to_d4_lut16(255,0,51,&lut16(3,4));
+        assert!((c - 65280.0).abs() < 1.0 && m.abs() < 1.0 && (y - 51.0 * 256.0).abs() < 1.0 && (k - 65280.0).abs() < 1.0);
+        let (r,g,b) = d3_to_d3_lut16(10,20,30,&lut16(3,3));
+        assert!((r - 10.0 * 256.0).abs() < 1.0 && (g - 20.0 * 256.0).abs() < 1.0 && (b - 30.0 * 256.0).abs() < 1.0);
+        assert!((d3_to_d1_lut16(200,0,0,&lut16(3,1)) - 200.0 * 256.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn lut_convert_pcs_encoding() {
+        // a Lab to Lab identity lut16 returns the 8 bit Lab input in the legacy 16 bit encoding
+        let lab8 = PcsEncoding::Lab8.encode_u8([50.0,20.0,-30.0]);
+        let (l,a,b) = d3_to_d3_lut16(lab8[0],lab8[1],lab8[2],&lut16(3,3));
+        let lab = PcsEncoding::Lab16Legacy.decode_u16([l,a,b].map(|val| val.round() as u16));
+        let expected = PcsEncoding::Lab8.decode_u8(lab8);
+        assert!(lab.iter().zip(expected).all(|(a,b)| (a - b).abs() < 0.01),"{:?} {:?}",lab,expected);
+        assert_eq!(PcsEncoding::Lab16Legacy.encode_u16(expected),[l,a,b].map(|val| val.round() as u16));
+    }
+
+    #[test]
+    fn lut_convert_e_params() {
+        // XYZ input, the matrix rotates X -> Y -> Z -> X before the identity CLUT
+        let mut mft2 = lut16_fixture(3,3,3,&|p| p.iter().map(|val| (val * 65535.0) as u16).collect());
+        for (i,e) in [0,1,0,0,0,1,1,0,0_u32].iter().enumerate() {
+            mft2[4 + i * 4..8 + i * 4].copy_from_slice(&(e << 16).to_be_bytes());
+        }
+        let (x,y,z) = d3_to_d3_lut16(10,20,30,&parse_lut16(&mft2));
+        assert!((x - 20.0 * 256.0).abs() < 1.0 && (y - 30.0 * 256.0).abs() < 1.0 && (z - 10.0 * 256.0).abs() < 1.0,"{} {} {}",x,y,z);
+        let (x,y,z) = d4_to_d3_lut16(10,20,30,0,&lut16(4,3));
+        assert!((x - 10.0 * 257.0).abs() < 1.0 && (y - 20.0 * 257.0).abs() < 1.0 && (z - 30.0 * 257.0).abs() < 1.0);
+    }
+}
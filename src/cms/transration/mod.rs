@@ -3,7 +3,7 @@
 //! - RGB <--> XYZ
 //! - XYZ <--> L*a*b     It need XYZ white point(default d65)
 //! - L*a*b <--> CMYK    But it must need YCMK color space ICC Profile.
-//! - CMYK --> RGB
+//! - CMYK <--> RGB    Naive, without profiles, with black generation and ink limits
 //! 
 //! # Color space transrator entries
 //! There functions trunsrat color space,number of entry pixels.
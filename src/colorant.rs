@@ -0,0 +1,229 @@
+//! Colorant order 'clro' and colorant tables 'clrt' / 'clot'
+//! clrt names each device channel (clot each output channel of a device link) with its PCS value,
+//! clro lists the channels in the order the colorants are printed.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn black_channel(decoded:&DecodedICCProfile) -> Option<usize> {
+//!     decoded.colorant_table()?.channel("Black")
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::utils::bound_check;
+use crate::cms::pcs::PcsEncoding;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Colorant {
+    pub name: String,
+    /// 16 bit PCS value, XYZ or Lab as the profile PCS
+    pub pcs: [u16;3],
+}
+
+impl Colorant {
+    /// from_lab encodes Lab with the 16 bit encoding of the profile version,
+    /// version 4 0xFFFF = 100.0, version 2 0xFF00 = 100.0
+    pub fn from_lab(name:&str,lab:[f64;3],version:u32) -> Self {
+        Self {
+            name: name.to_string(),
+            pcs: PcsEncoding::lab16(version).encode_u16(lab),
+        }
+    }
+
+    /// from_xyz encodes XYZ as u1Fixed15, 0x8000 = 1.0
+    pub fn from_xyz(name:&str,xyz:[f64;3]) -> Self {
+        Self {
+            name: name.to_string(),
+            pcs: PcsEncoding::Xyz16.encode_u16(xyz),
+        }
+    }
+
+    pub fn lab(&self,version:u32) -> [f64;3] {
+        PcsEncoding::lab16(version).decode_u16(self.pcs)
+    }
+
+    pub fn xyz(&self) -> [f64;3] {
+        PcsEncoding::Xyz16.decode_u16(self.pcs)
+    }
+}
+
+/// colorantTableType 'clrt', colorants in channel order
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ColorantTableType {
+    pub colorants: Vec<Colorant>,
+}
+
+impl ColorantTableType {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self,colorant:Colorant) -> &mut Self {
+        self.colorants.push(colorant);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.colorants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colorants.is_empty()
+    }
+
+    /// channel returns the channel index of a colorant name, ignoring ASCII case
+    pub fn channel(&self,name:&str) -> Option<usize> {
+        self.colorants.iter().position(|colorant| colorant.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get(&self,name:&str) -> Option<&Colorant> {
+        self.channel(name).map(|channel| &self.colorants[channel])
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.colorants.iter().map(|colorant| colorant.name.as_str()).collect()
+    }
+
+    /// colorant_name returns the first colorant name, the only one the former decoder kept
+    #[deprecated(note = "use `names` or `colorants`")]
+    pub fn colorant_name(&self) -> &str {
+        self.colorants.first().map(|colorant| colorant.name.as_str()).unwrap_or("")
+    }
+
+    /// pcs_values returns the PCS values in channel order
+    #[deprecated(note = "use `colorants`")]
+    pub fn pcs_values(&self) -> Vec<[u16;3]> {
+        self.colorants.iter().map(|colorant| colorant.pcs).collect()
+    }
+
+    /// read reads tag data starting with the type signature
+    pub(crate) fn read(data:&[u8]) -> Result<Self> {
+        bound_check(data,8,4)?;
+        let counts = read_u32_be(data,8) as usize;
+        bound_check(data,12,counts.saturating_mul(38))?;
+        let mut colorants = Vec::with_capacity(counts);
+        for i in 0..counts {
+            let ptr = 12 + i * 38;
+            let name = read_ascii_string(data,ptr,32);
+            let pcs = [read_u16_be(data,ptr + 32),read_u16_be(data,ptr + 34),read_u16_be(data,ptr + 36)];
+            colorants.push(Colorant { name,pcs });
+        }
+        Ok(Self { colorants })
+    }
+
+    /// write appends the tag body after the type signature, names are up to 31 ASCII bytes
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) -> Result<()> {
+        write_u32_be(self.colorants.len() as u32,buf);
+        for colorant in &self.colorants {
+            let name = &colorant.name;
+            if !name.is_ascii() || name.len() > 31 {
+                return Err(Error::new(ErrorKind::InvalidInput,format!("colorant name '{}' must be ASCII up to 31 bytes",name)))
+            }
+            let mut bytes = [0;32];
+            bytes[..name.len()].copy_from_slice(name.as_bytes());
+            buf.extend_from_slice(&bytes);
+            for val in colorant.pcs {
+                write_u16_be(val,buf);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// colorantOrderType 'clro', channel numbers in the printing order, first printed first
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct ColorantOrderType {
+    pub order: Vec<u8>,
+}
+
+impl ColorantOrderType {
+    pub(crate) fn read(data:&[u8]) -> Result<Self> {
+        bound_check(data,8,4)?;
+        let counts = read_u32_be(data,8) as usize;
+        bound_check(data,12,counts)?;
+        Ok(Self { order: data[12..12 + counts].to_vec() })
+    }
+
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) {
+        write_u32_be(self.order.len() as u32,buf);
+        buf.extend_from_slice(&self.order);
+    }
+}
+
+impl DecodedICCProfile {
+    /// colorant_table returns the clrt tag, the input channels
+    pub fn colorant_table(&self) -> Option<&ColorantTableType> {
+        match self.tags.get("clrt") {
+            Some(Data::ColorantTable(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// colorant_table_out returns the clot tag, the output channels of a device link
+    pub fn colorant_table_out(&self) -> Option<&ColorantTableType> {
+        match self.tags.get("clot") {
+            Some(Data::ColorantTable(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// colorant_order returns the channel numbers of the clro tag
+    pub fn colorant_order(&self) -> Option<&[u8]> {
+        match self.tags.get("clro") {
+            Some(Data::ColorantOrder(order)) => Some(&order.order),
+            _ => None,
+        }
+    }
+
+    /// printing_order returns (channel,colorant) of clrt in the clro order, or in channel order without clro
+    pub fn printing_order(&self) -> Option<Vec<(usize,&Colorant)>> {
+        let table = self.colorant_table()?;
+        match self.colorant_order() {
+            Some(order) => order.iter()
+                .map(|channel| table.colorants.get(*channel as usize).map(|colorant| (*channel as usize,colorant)))
+                .collect(),
+            None => Some(table.colorants.iter().enumerate().collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colorant_tables() {
+        let version = 0x04300000;
+        let mut table = ColorantTableType::new();
+        for (name,lab) in [("Cyan",[55.0,-37.0,-50.0]),("Magenta",[48.0,74.0,-3.0]),("Yellow",[89.0,-5.0,93.0]),
+                ("Black",[16.0,0.0,0.0]),("Orange",[64.0,50.0,75.0]),("Green",[60.0,-70.0,30.0])] {
+            table.push(Colorant::from_lab(name,lab,version));
+        }
+        let order = ColorantOrderType { order: vec![3,0,1,4,5,2] };
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"6CLR"),u32::from_be_bytes(*b"Lab "));
+        builder.version(version);
+        builder.tag("clrt",&Data::ColorantTable(table.clone()),"clrt").unwrap()
+            .tag("clot",&Data::ColorantTable(table.clone()),"clrt").unwrap()
+            .tag("clro",&Data::ColorantOrder(order.clone()),"clro").unwrap();
+        let data = builder.build().unwrap();
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        assert_eq!(decoded.colorant_table(),Some(&table));
+        assert_eq!(decoded.colorant_table_out(),Some(&table));
+        assert_eq!(decoded.colorant_order(),Some(&order.order[..]));
+        let clrt = decoded.colorant_table().unwrap();
+        assert_eq!(clrt.channel("orange"),Some(4));
+        let lab = clrt.get("Black").unwrap().lab(version);
+        assert!((lab[0] - 16.0).abs() < 0.01 && lab[1].abs() < 0.01);
+        let printing :Vec<&str> = decoded.printing_order().unwrap().iter().map(|(_,colorant)| colorant.name.as_str()).collect();
+        assert_eq!(printing,["Black","Cyan","Magenta","Orange","Green","Yellow"]);
+        #[allow(deprecated)]
+        let (name,pcs) = (clrt.colorant_name(),clrt.pcs_values());
+        assert_eq!(name,"Cyan");
+        assert_eq!(pcs.len(),6);
+        assert_eq!(pcs[3],clrt.colorants[3].pcs);
+        assert!(crate::validator::validate(&data).iter().all(|finding| finding.check != crate::validator::Check::TagType));
+    }
+}
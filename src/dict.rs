@@ -0,0 +1,221 @@
+//! dictType 'dict' (ICC v4.3), used by the `meta` tag
+//! Entries are name/value pairs with optional localized display names and values.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn job_id(decoded:&DecodedICCProfile) -> Option<String> {
+//!     decoded.metadata().get("job_id").cloned()
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::utils::bound_check;
+
+#[derive(Debug,Clone,Default)]
+pub struct DictEntry {
+    pub name: String,
+    /// None is a null value, different from an empty value
+    pub value: Option<String>,
+    pub display_name: Option<MultiLocalizedUnicodeType>,
+    pub display_value: Option<MultiLocalizedUnicodeType>,
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct DictType {
+    pub entries: Vec<DictEntry>,
+}
+
+fn read_utf16(data:&[u8],offset:usize,size:usize) -> Result<String> {
+    bound_check(data,offset,size)?;
+    let units :Vec<u16> = (0..size / 2).map(|i| read_u16_be(data,offset + i * 2)).collect();
+    Ok(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string())
+}
+
+fn read_localized(data:&[u8],offset:usize,size:usize,version:u32) -> Result<MultiLocalizedUnicodeType> {
+    bound_check(data,offset,size)?;
+    match Data::read_embedded_text(&data[offset..offset + size],version)?.1 {
+        TextDescription::Localized(mluc) => Ok(mluc),
+        TextDescription::Text(_) => Err(Error::new(ErrorKind::InvalidData,"dict display names must be mluc")),
+    }
+}
+
+impl DictType {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// insert sets the value of `name`, replacing an existing entry
+    pub fn insert(&mut self,name:&str,value:&str) -> &mut Self {
+        match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => entry.value = Some(value.to_string()),
+            None => self.entries.push(DictEntry {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+                ..DictEntry::default()
+            }),
+        }
+        self
+    }
+
+    pub fn get(&self,name:&str) -> Option<&DictEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// to_map returns the names and values, null values are skipped
+    pub fn to_map(&self) -> BTreeMap<String,String> {
+        self.entries.iter()
+            .filter_map(|entry| entry.value.as_ref().map(|value| (entry.name.to_string(),value.to_string())))
+            .collect()
+    }
+
+    /// to_localized_map returns display names and values for a language, falling back to the plain ones
+    pub fn to_localized_map(&self,lang:&str,country:&str) -> BTreeMap<String,String> {
+        self.entries.iter().filter_map(|entry| {
+            let name = entry.display_name.as_ref()
+                .and_then(|mluc| mluc.localized(lang,country))
+                .unwrap_or(&entry.name);
+            let value = entry.display_value.as_ref()
+                .and_then(|mluc| mluc.localized(lang,country))
+                .or(entry.value.as_deref())?;
+            Some((name.to_string(),value.to_string()))
+        }).collect()
+    }
+
+    pub fn from_map(map:&BTreeMap<String,String>) -> Self {
+        let mut dict = Self::new();
+        for (name,value) in map {
+            dict.insert(name,value);
+        }
+        dict
+    }
+
+    /// read reads tag data starting with the type signature, offsets are relative to the tag
+    pub(crate) fn read(data:&[u8],version:u32) -> Result<Self> {
+        bound_check(data,8,8)?;
+        let count = read_u32_be(data,8) as usize;
+        let record_size = read_u32_be(data,12) as usize;
+        if ![16,24,32].contains(&record_size) {
+            return Err(Error::new(ErrorKind::InvalidData,format!("dict record size {}",record_size)))
+        }
+        bound_check(data,16,count.saturating_mul(record_size))?;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let ptr = 16 + i * record_size;
+            let position = |j:usize| (read_u32_be(data,ptr + j * 8) as usize,read_u32_be(data,ptr + j * 8 + 4) as usize);
+            let (offset,size) = position(0);
+            let name = read_utf16(data,offset,size)?;
+            let value = match position(1) {
+                (0,_) => None,
+                (offset,size) => Some(read_utf16(data,offset,size)?),
+            };
+            let display_name = match record_size {
+                24 | 32 if position(2).0 != 0 => Some(read_localized(data,position(2).0,position(2).1,version)?),
+                _ => None,
+            };
+            let display_value = match record_size {
+                32 if position(3).0 != 0 => Some(read_localized(data,position(3).0,position(3).1,version)?),
+                _ => None,
+            };
+            entries.push(DictEntry { name,value,display_name,display_value });
+        }
+        Ok(Self { entries })
+    }
+
+    /// write appends the tag body to `buf` that starts with the type signature
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) -> Result<()> {
+        let record_size = if self.entries.iter().any(|entry| entry.display_value.is_some()) {
+            32
+        } else if self.entries.iter().any(|entry| entry.display_name.is_some()) {
+            24
+        } else {
+            16
+        };
+        write_u32_be(self.entries.len() as u32,buf);
+        write_u32_be(record_size as u32,buf);
+        let table = buf.len();
+        buf.resize(table + self.entries.len() * record_size,0);
+        for (i,entry) in self.entries.iter().enumerate() {
+            let mut elements = vec![Some(utf16(&entry.name))];
+            elements.push(entry.value.as_ref().map(|value| utf16(value)));
+            for mluc in [&entry.display_name,&entry.display_value] {
+                elements.push(match mluc {
+                    Some(mluc) => Some(Data::MultiLocalizedUnicode(mluc.clone()).encode("mluc")?),
+                    None => None,
+                });
+            }
+            for (j,element) in elements.iter().take(record_size / 8).enumerate() {
+                let (offset,size) = match element {
+                    Some(element) => {
+                        buf.resize((buf.len() + 3) & !3,0);
+                        let offset = buf.len();
+                        buf.extend_from_slice(element);
+                        (offset,element.len())
+                    },
+                    None => (0,0),
+                };
+                let ptr = table + i * record_size + j * 8;
+                buf[ptr..ptr + 4].copy_from_slice(&(offset as u32).to_be_bytes());
+                buf[ptr + 4..ptr + 8].copy_from_slice(&(size as u32).to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn utf16(string:&str) -> Vec<u8> {
+    string.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect()
+}
+
+impl DecodedICCProfile {
+    /// metadata returns the names and values of the `meta` tag, empty without it
+    pub fn metadata(&self) -> BTreeMap<String,String> {
+        match self.tags.get("meta") {
+            Some(Data::Dictionary(dict)) => dict.to_map(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// localized_metadata returns the display names and values of the `meta` tag for a language
+    pub fn localized_metadata(&self,lang:&str,country:&str) -> BTreeMap<String,String> {
+        match self.tags.get("meta") {
+            Some(Data::Dictionary(dict)) => dict.to_localized_map(lang,country),
+            _ => BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dict_metadata() {
+        let mut dict = DictType::new();
+        dict.insert("job_id","J-1024").insert("instrument","i1Pro 3").insert("job_id","J-2048");
+        dict.entries.push(DictEntry { name: "note".to_string(), value: Option::None, ..DictEntry::default() });
+        let mut display = MultiLocalizedUnicodeType::from("Instrument".to_string());
+        display.unicode_strings.push(LocalizedUnicode { lang: "de".to_string(), country: "DE".to_string(), unicode_string: "Messgerät".to_string() });
+        dict.entries[1].display_name = Some(display);
+
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"mntr"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"XYZ "));
+        builder.tag("meta",&Data::Dictionary(dict),"dict").unwrap();
+        let data = builder.build().unwrap();
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        let metadata = decoded.metadata();
+        assert_eq!(metadata.len(),2);
+        assert_eq!(metadata["job_id"],"J-2048");
+        assert_eq!(metadata["instrument"],"i1Pro 3");
+        let localized = decoded.localized_metadata("de","DE");
+        assert_eq!(localized["Messgerät"],"i1Pro 3");
+        assert_eq!(decoded.localized_metadata("en","US")["Instrument"],"i1Pro 3");
+        match decoded.tags.get("meta") {
+            Some(Data::Dictionary(dict)) => assert!(dict.get("note").unwrap().value.is_none()),
+            other => panic!("{:?}",other),
+        }
+        assert!(crate::validator::validate(&data).iter().all(|finding| finding.check != crate::validator::Check::TagType));
+    }
+}
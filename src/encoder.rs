@@ -0,0 +1,215 @@
+//! Tag data encoder
+//! Data::encode writes tag data including the type signature and the reserved field.
+//! Only simple types are supported for now; unsupported types return ErrorKind::Unsupported.
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+
+/// signature_bytes converts "XYZ" or "rTRC" to 4 bytes padded with spaces
+pub(crate) fn signature_bytes(signature:&str) -> Result<[u8;4]> {
+    let bytes = signature.as_bytes();
+    if bytes.is_empty() || bytes.len() > 4 {
+        return Err(Error::new(ErrorKind::InvalidInput,format!("signature '{}' must be 1 to 4 bytes",signature)))
+    }
+    let mut signature = [b' ';4];
+    signature[..bytes.len()].copy_from_slice(bytes);
+    Ok(signature)
+}
+
+fn type_header(data_type:&str) -> Result<Vec<u8>> {
+    let mut buf = signature_bytes(data_type)?.to_vec();
+    buf.extend_from_slice(&[0;4]);
+    Ok(buf)
+}
+
+fn write_utf16_be(string:&str,buf:&mut Vec<u8>) {
+    for val in string.encode_utf16() {
+        write_u16_be(val,buf);
+    }
+}
+
+fn write_multi_localization(mluc:&MultiLocalizedUnicodeType,buf:&mut Vec<u8>) {
+    let count = mluc.unicode_strings.len();
+    write_u32_be(count as u32,buf);
+    write_u32_be(12,buf);
+    let mut offset = 16 + count * 12;
+    for string in &mluc.unicode_strings {
+        let mut code = [0;4];
+        for (i,b) in string.lang.bytes().take(2).enumerate() { code[i] = b; }
+        for (i,b) in string.country.bytes().take(2).enumerate() { code[i + 2] = b; }
+        buf.extend_from_slice(&code);
+        let length = string.unicode_string.encode_utf16().count() * 2;
+        write_u32_be(length as u32,buf);
+        write_u32_be(offset as u32,buf);
+        offset += length;
+    }
+    for string in &mluc.unicode_strings {
+        write_utf16_be(&string.unicode_string,buf);
+    }
+}
+
+/// textDescriptionType with an optional Unicode part and an empty Macintosh part
+fn write_text_description(ascii:&str,lang:&str,local:&str,buf:&mut Vec<u8>) {
+    let ascii :Vec<u8> = ascii.bytes().filter(|b| b.is_ascii()).collect();
+    write_u32_be(ascii.len() as u32 + 1,buf);
+    buf.extend_from_slice(&ascii);
+    buf.push(0);
+    if local.is_empty() {
+        buf.extend_from_slice(&[0;8]);     // unicode language code and count
+    } else {
+        let mut code = [0;4];
+        for (i,b) in lang.bytes().take(4).enumerate() { code[i] = b; }
+        buf.extend_from_slice(&code);
+        write_u32_be(local.encode_utf16().count() as u32 + 1,buf);
+        write_utf16_be(local,buf);
+        write_u16_be(0,buf);
+    }
+    buf.extend_from_slice(&[0;70]);    // scriptcode code, count and string
+}
+
+/// names are 32 byte null terminated ASCII strings
+pub(crate) fn check_color_name(name:&str) -> Result<()> {
+    if !name.is_ascii() || name.len() > 31 {
+        return Err(Error::new(ErrorKind::InvalidInput,format!("color name '{}' must be ASCII up to 31 bytes",name)))
+    }
+    Ok(())
+}
+
+fn write_color_name(name:&str,buf:&mut Vec<u8>) -> Result<()> {
+    check_color_name(name)?;
+    let mut bytes = [0;32];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    buf.extend_from_slice(&bytes);
+    Ok(())
+}
+
+fn write_named_color(named_color:&NamedColor2Type,buf:&mut Vec<u8>) -> Result<()> {
+    let coordinates = named_color.entries.first().map(|entry| entry.coordinate.len()).unwrap_or(0);
+    if named_color.entries.iter().any(|entry| entry.coordinate.len() != coordinates) {
+        return Err(Error::new(ErrorKind::InvalidInput,"every named color must have the same number of device coordinates"))
+    }
+    write_u32_be(named_color.vendor_specific_flag,buf);
+    write_u32_be(named_color.entries.len() as u32,buf);
+    write_u32_be(coordinates as u32,buf);
+    write_color_name(&named_color.prefix_color_name,buf)?;
+    write_color_name(&named_color.suffix_color_name,buf)?;
+    for entry in &named_color.entries {
+        write_color_name(&entry.root_color_name,buf)?;
+        for val in entry.pcs.iter().chain(entry.coordinate.iter()) {
+            write_u16_be(*val,buf);
+        }
+    }
+    Ok(())
+}
+
+impl Data {
+    /// encode writes this data as `data_type` e.g. "curv", "XYZ ", "mluc".
+    /// The result starts with the type signature and can be stored as a tag.
+    pub fn encode(&self,data_type:&str) -> Result<Vec<u8>> {
+        let data_type = std::str::from_utf8(&signature_bytes(data_type)?)
+            .map_err(|err| Error::new(ErrorKind::InvalidInput,err))?
+            .to_string();
+        if let Data::Raw(raw_type,raw) = self {
+            let mut buf = type_header(raw_type)?;
+            buf.extend_from_slice(raw);
+            return Ok(buf)
+        }
+        let mut buf = type_header(&data_type)?;
+        match (&*data_type,self) {
+            ("XYZ ",Data::XYZNumber(xyz)) => {
+                for val in [&xyz.x,&xyz.y,&xyz.z] {
+                    buf.extend_from_slice(&val.to_be_bytes());
+                }
+            },
+            ("XYZ ",Data::XYZNumberArray(xyzs)) => {
+                for xyz in xyzs {
+                    for val in [&xyz.x,&xyz.y,&xyz.z] {
+                        buf.extend_from_slice(&val.to_be_bytes());
+                    }
+                }
+            },
+            ("sf32",Data::S15Fixed16NumberArray(vals)) => {
+                for val in vals {
+                    buf.extend_from_slice(&val.to_be_bytes());
+                }
+            },
+            ("uf32",Data::U16Fixed16NumberArray(vals)) => {
+                for val in vals {
+                    buf.extend_from_slice(&val.to_be_bytes());
+                }
+            },
+            ("ui08",Data::UInt8NumberArray(vals)) => {
+                buf.extend_from_slice(vals);
+            },
+            ("ui16",Data::UInt16NumberArray(vals)) => {
+                for val in vals { write_u16_be(*val,&mut buf); }
+            },
+            ("ui32",Data::UInt32NumberArray(vals)) => {
+                for val in vals { write_u32_be(*val,&mut buf); }
+            },
+            ("ui64",Data::UInt64NumberArray(vals)) => {
+                for val in vals { write_u64_be(*val,&mut buf); }
+            },
+            ("curv",Data::Curve(curve)) => {
+                write_u32_be(curve.len() as u32,&mut buf);
+                for val in curve { write_u16_be(*val,&mut buf); }
+            },
+            ("para",Data::ParametricCurve(curve)) => {
+                write_u16_be(curve.funtion_type,&mut buf);
+                write_u16_be(0,&mut buf);
+                for val in &curve.vals {
+                    buf.extend_from_slice(&val.to_be_bytes());
+                }
+            },
+            ("text",Data::ASCII(string)) => {
+                buf.extend_from_slice(string.as_bytes());
+                buf.push(0);
+            },
+            ("sig ",Data::ASCII(string)) => {
+                buf.extend_from_slice(&signature_bytes(string)?);
+            },
+            ("desc",Data::ASCII(string)) => {
+                write_text_description(string,"","",&mut buf);
+            },
+            ("desc",Data::Descriptor(descriptor)) => {
+                write_text_description(&descriptor.ascii_string,&descriptor.lang,&descriptor.local_string,&mut buf);
+            },
+            ("mluc",Data::MultiLocalizedUnicode(mluc)) => {
+                write_multi_localization(mluc,&mut buf);
+            },
+            ("mluc",Data::ASCII(string)) => {
+                write_multi_localization(&MultiLocalizedUnicodeType::from(string.to_string()),&mut buf);
+            },
+            ("pseq",Data::ProfileSequence(sequence)) => {
+                sequence.write(&mut buf)?;
+            },
+            ("psid",Data::ProfileSequenceId(identifier)) => {
+                identifier.write(&mut buf)?;
+            },
+            ("dict",Data::Dictionary(dict)) => {
+                dict.write(&mut buf)?;
+            },
+            ("cicp",Data::Cicp(cicp)) => {
+                cicp.write(&mut buf);
+            },
+            ("clrt",Data::ColorantTable(table)) => {
+                table.write(&mut buf)?;
+            },
+            ("clro",Data::ColorantOrder(order)) => {
+                order.write(&mut buf);
+            },
+            ("vcgt",Data::VideoCardGamma(vcgt)) => {
+                vcgt.write(&mut buf)?;
+            },
+            ("ncl2",Data::NamedColor(named_color)) => {
+                write_named_color(named_color,&mut buf)?;
+            },
+            _ => {
+                return Err(Error::new(ErrorKind::Unsupported,format!("encoding this data as '{}' is not supported",data_type)))
+            }
+        }
+        Ok(buf)
+    }
+}
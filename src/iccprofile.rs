@@ -1,30 +1,112 @@
 //! ICC Profile reader
 
 use std::collections::HashMap;
-use std::io::{Error,ErrorKind};
+use std::fs::File;
+use std::path::Path;
+use std::io::{Error,ErrorKind,Read};
 use std::io::Result;
 use bin_rs::io::*;
 use bin_rs::Endian;
 use crate::iccprofile::Data::*;
-use crate::utils::bound_check;
+use crate::utils::{bound_check,limit_check,read_to_limit,read_u128};
+pub use crate::tag_directory::{TagDirectory,TagEntry};
+pub use crate::mpet::{CurveSegment,FloatClut,ProcessElement,SegmentedCurve};
+pub use crate::dict::{DictEntry,DictType};
+pub use crate::cicp::CicpType;
+pub use crate::colorant::{Colorant,ColorantOrderType,ColorantTableType};
+pub use crate::vcgt::{VcgtFormula,VideoCardGammaType};
+pub use crate::profile_sequence::{ProfileDescription,ProfileIdentifier,ProfileSequenceDescription,ProfileSequenceIdentifier,TextDescription};
+
+pub fn icc_profile_decode(data :&[u8]) -> Result<DecodedICCProfile> {
+    icc_profile_decode_with_options(data,&DecodeOptions::default())
+}
 
-pub fn icc_profile_decode(data :&Vec<u8>) -> Result<DecodedICCProfile> {
+/// icc_profile_decode_with_options decodes a profile with resource limits.
+/// It never panics on malformed data; broken or oversized profiles return an error.
+/// When `options.lenient` is set, broken tags are kept as `Data::Raw` and reported in `warnings`.
+/// A file signature other than 'acsp' is a `MissingAcsp` warning unless `options.strict` is set.
+pub fn icc_profile_decode_with_options(data :&[u8],options: &DecodeOptions) -> Result<DecodedICCProfile> {
+    limit_check("file size",data.len(),options.max_file_size)?;
     let icc_profile = ICCProfile::new(data)?;
+    let data = &icc_profile.data;
+    let mut warnings = vec![];
+
+    if icc_profile.magicnumber_ascp != 0x61637370 {   // 'acsp'
+        let message = format!("profile file signature is {:08x}, not 'acsp'",icc_profile.magicnumber_ascp);
+        if options.strict {
+            return Err(Error::new(ErrorKind::InvalidData,message))
+        }
+        warnings.push(Warning::new(WarningKind::MissingAcsp,Option::None,message));
+    }
+    if icc_profile.length as usize != data.len() {
+        let message = format!("header size {} but profile is {}bytes",icc_profile.length,data.len());
+        warnings.push(Warning::new(WarningKind::HeaderSize,Option::None,message));
+    }
 
     let mut decoded: HashMap<String,Data> = HashMap::new();
     let header_size = 128;
-    let mut ptr = header_size;
-    let tags = read_u32_be(&icc_profile.data,ptr);
-    ptr +=  4;
-    bound_check(&icc_profile.data,ptr,tags as usize * 12)?;
-    for _ in 0..tags {
-        let tag_name = read_string(&icc_profile.data,ptr,4);
-        ptr +=  4;
-        let tag_offset = read_u32_be(&icc_profile.data,ptr) as usize;
-        ptr +=  4;
-        let tag_length = read_u32_be(&icc_profile.data,ptr) as usize;
-        ptr +=  4;
-        let (_,val) = Data::parse(&icc_profile.data[tag_offset..],tag_length,icc_profile.version)?;
+    let ptr = header_size;
+    bound_check(data,ptr,4)?;
+    let mut tags = read_u32_be(data,ptr) as usize;
+    limit_check("tag count",tags,options.max_tag_count)?;
+    if let Err(err) = bound_check(data,ptr + 4,tags * 12) {
+        if !options.lenient {
+            return Err(err)
+        }
+        let message = format!("tag count {} is over the profile size",tags);
+        warnings.push(Warning::new(WarningKind::TagCount,Option::None,message));
+        tags = (data.len() - ptr - 4) / 12;
+    }
+    let directory = TagDirectory::read(data,tags)?;
+    for entry in &directory {
+        let tag_name = entry.signature.to_string();
+        let tag_offset = entry.offset as usize;
+        let mut tag_length = entry.size as usize;
+        if let Err(err) = bound_check(data,tag_offset,tag_length) {
+            if !options.lenient {
+                return Err(err)
+            }
+            if tag_offset >= data.len() {
+                let message = format!("tag offset {} is out of the profile",tag_offset);
+                warnings.push(Warning::new(WarningKind::BrokenTag,Some(tag_name),message));
+                continue;
+            }
+            let message = format!("tag length {} is over the profile, truncated to {}",tag_length,data.len() - tag_offset);
+            warnings.push(Warning::new(WarningKind::TagLength,Some(tag_name.clone()),message));
+            tag_length = data.len() - tag_offset;
+        }
+        let tag_data = &data[tag_offset..];
+        let val = match Data::parse_with_options(tag_data,tag_length,icc_profile.version,options) {
+            Ok((data_type,val)) => {
+                let v4_types = crate::validator::allowed_types(&tag_name).map(|(_,v4_types)| v4_types);
+                if data_type == "desc" && icc_profile.version >= 0x04000000 && v4_types.is_some_and(|types| !types.contains(&"desc")) {
+                    let message = "textDescriptionType is used in a v4 profile".to_string();
+                    warnings.push(Warning::new(WarningKind::DescriptionInV4,Some(tag_name.clone()),message));
+                }
+                val
+            },
+            Err(err) => {
+                if !options.lenient {
+                    return Err(err)
+                }
+                // a wrong tag length is common, retry with the rest of the profile
+                let retry = if tag_data.len() > tag_length {
+                    Data::parse_with_options(tag_data,tag_data.len(),icc_profile.version,options).ok()
+                } else {
+                    Option::None
+                };
+                if let Some((_,val)) = retry {
+                    let message = format!("tag length {} is too short",tag_length);
+                    warnings.push(Warning::new(WarningKind::TagLength,Some(tag_name.clone()),message));
+                    val
+                } else {
+                    let data_type = if tag_length >= 4 { read_string(tag_data,0,4) } else { "".to_string() };
+                    let raw = tag_data.get(8..tag_length).unwrap_or(&[]).to_vec();
+                    warnings.push(Warning::new(WarningKind::BrokenTag,Some(tag_name.clone()),err.to_string()));
+                    Raw(data_type,raw)
+                }
+            }
+        };
         decoded.insert(tag_name,val);
     }
     Ok(DecodedICCProfile {
@@ -46,9 +128,122 @@ pub fn icc_profile_decode(data :&Vec<u8>) -> Result<DecodedICCProfile> {
         creator: icc_profile.creator,
         profile_id: icc_profile.profile_id,
         tags: decoded,
+        directory,
+        warnings,
     })
 }
 
+/// compute_profile_id calculates the profile ID (MD5) of a profile.
+/// The profile flags, rendering intent and profile ID fields are zeroed before hashing.
+pub fn compute_profile_id(data :&[u8]) -> Result<u128> {
+    bound_check(data,0,128)?;
+    let length = read_u32_be(data,0) as usize;
+    bound_check(data,0,length.max(128))?;
+    let mut buffer = data[..length.max(128)].to_vec();
+    buffer[44..48].fill(0);     // profile flags
+    buffer[64..68].fill(0);     // rendering intent
+    buffer[84..100].fill(0);    // profile ID
+    Ok(crate::md5::md5(&buffer))
+}
+
+/// verify_profile_id checks the stored profile ID.
+/// It returns None when no ID is stored (all zero), which is allowed by the specification.
+pub fn verify_profile_id(data :&[u8]) -> Result<Option<bool>> {
+    bound_check(data,0,128)?;
+    let profile_id = read_u128(data,84);
+    if profile_id == 0 {
+        return Ok(Option::None)
+    }
+    Ok(Some(compute_profile_id(data)? == profile_id))
+}
+
+/// Resource limits for decoding.
+/// The default has no limits; use `DecodeOptions::untrusted()` for user supplied profiles.
+#[derive(Debug,Clone)]
+pub struct DecodeOptions {
+    /// max profile size in bytes
+    pub max_file_size: usize,
+    /// max number of entries in the tag table
+    pub max_tag_count: usize,
+    /// max number of CLUT values (grid points ^ input channels * output channels)
+    pub max_clut_entries: usize,
+    /// max number of entries in a curve or LUT input/output table
+    pub max_curve_length: usize,
+    /// keep going past broken tags and known spec violations, reporting them as warnings
+    pub lenient: bool,
+    /// reject profiles whose file signature is not 'acsp'
+    pub strict: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: usize::MAX,
+            max_tag_count: usize::MAX,
+            max_clut_entries: usize::MAX,
+            max_curve_length: usize::MAX,
+            lenient: false,
+            strict: false,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// limits for profiles embedded in user uploaded images
+    pub fn untrusted() -> Self {
+        Self {
+            max_file_size: 16 * 1024 * 1024,
+            max_tag_count: 1024,
+            max_clut_entries: 1 << 24,
+            max_curve_length: 65536,
+            lenient: false,
+            strict: false,
+        }
+    }
+
+    /// lenient options without limits
+    pub fn lenient() -> Self {
+        Self {
+            lenient: true,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum WarningKind {
+    /// a tag could not be decoded and is kept as `Data::Raw`
+    BrokenTag,
+    /// a tag length does not match its data
+    TagLength,
+    /// the tag count is over the profile size
+    TagCount,
+    /// v2 textDescriptionType ('desc') in a v4 profile, for desc, dmnd, dmdd, vued or scrd
+    DescriptionInV4,
+    /// profile file signature is not 'acsp'
+    MissingAcsp,
+    /// header profile size is not the real size
+    HeaderSize,
+}
+
+/// Warning is a spec violation tolerated by the decoder
+#[derive(Debug,Clone)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub tag: Option<String>,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind,tag: Option<String>,message: String) -> Self {
+        Self {
+            kind,
+            tag,
+            message,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DecodedICCProfile {
     pub length : u32,
@@ -69,13 +264,37 @@ pub struct DecodedICCProfile {
     pub creator: u32,
     pub profile_id: u128,
     pub tags: HashMap<String,Data>,
+    /// tag table in file order
+    pub directory: TagDirectory,
+    /// tolerated spec violations found while decoding
+    pub warnings: Vec<Warning>,
 }
 
 impl DecodedICCProfile {
-    pub fn new(buffer :&Vec<u8>) -> Result<Self> {
+    pub fn new(buffer :&[u8]) -> Result<Self> {
         icc_profile_decode(buffer)
     }
 
+    /// new_with_options decodes with resource limits, e.g. `DecodeOptions::untrusted()`
+    pub fn new_with_options(buffer :&[u8],options: &DecodeOptions) -> Result<Self> {
+        icc_profile_decode_with_options(buffer,options)
+    }
+
+    /// from_reader reads the whole profile from a reader and decodes it
+    pub fn from_reader<R:Read>(reader:R) -> Result<Self> {
+        Self::from_reader_with_options(reader,&DecodeOptions::default())
+    }
+
+    /// from_reader_with_options stops reading when the data exceeds `options.max_file_size`
+    pub fn from_reader_with_options<R:Read>(reader:R,options: &DecodeOptions) -> Result<Self> {
+        let buffer = read_to_limit(reader,options.max_file_size)?;
+        icc_profile_decode_with_options(&buffer,options)
+    }
+
+    pub fn open<P:AsRef<Path>>(filename:P) -> Result<Self> {
+        Self::from_reader(File::open(filename)?)
+    }
+
     pub fn to_string(&self) -> Result<String> {
         crate::utils::decoded_print(self,0)
     }
@@ -110,7 +329,7 @@ pub struct ICCProfile {
 }
 
 impl ICCProfile {    
-    pub fn new(buffer :&Vec<u8>) -> Result<Self> {
+    pub fn new(buffer :&[u8]) -> Result<Self> {
         if buffer.len() < 128 {
             return Err(Error::new(ErrorKind::Other,"ICCProfile data shotage"))
         }
@@ -172,7 +391,7 @@ impl ICCProfile {
 
         let creator = read_u32_be(&buffer,ptr);
         ptr += 4;
-        let profile_id = read_u128_be(&buffer, ptr);
+        let profile_id = read_u128(buffer, ptr);
 //        ptr += 28;  // padding data
 
         let create_date = format!("{:>4}/{:>2}/{:>2} {:>02}:{:>02}:{:>02}",
@@ -199,6 +418,32 @@ impl ICCProfile {
             data : buffer.to_vec(),
         })
     }
+
+    /// from_reader reads the whole profile from a reader
+    pub fn from_reader<R:Read>(mut reader:R) -> Result<Self> {
+        let mut buffer = vec![];
+        reader.read_to_end(&mut buffer)?;
+        Self::new(&buffer)
+    }
+
+    pub fn open<P:AsRef<Path>>(filename:P) -> Result<Self> {
+        Self::from_reader(File::open(filename)?)
+    }
+
+    /// compute_profile_id calculates the profile ID (MD5) of the profile data
+    pub fn compute_profile_id(&self) -> Result<u128> {
+        compute_profile_id(&self.data)
+    }
+
+    /// verify_profile_id returns None when no ID is stored, otherwise whether the stored ID is correct
+    pub fn verify_profile_id(&self) -> Result<Option<bool>> {
+        verify_profile_id(&self.data)
+    }
+
+    /// tag_directory reads the tag table in file order
+    pub fn tag_directory(&self) -> Result<TagDirectory> {
+        TagDirectory::new(&self.data)
+    }
 }
 
 
@@ -222,6 +467,21 @@ impl ICCNumber for S15Fixed16Number {
     fn decimal(&self) -> u32 { self.decimal as u32}
 }
 
+impl S15Fixed16Number {
+    /// from_f64 encodes a value as specified, value * 65536 rounded and clamped to the range
+    pub fn from_f64(val:f64) -> Self {
+        let fixed = (val * 65536.0).round().clamp(i32::MIN as f64,i32::MAX as f64) as i32;
+        Self {
+            integer: (fixed >> 16) as i16,
+            decimal: fixed as u16,
+        }
+    }
+
+    pub fn to_be_bytes(&self) -> [u8;4] {
+        ((self.integer as i32) << 16 | self.decimal as i32).to_be_bytes()
+    }
+}
+
 #[derive(Debug)]
 pub struct U16Fixed16Number {
     integer:u16,
@@ -235,6 +495,21 @@ impl ICCNumber for U16Fixed16Number {
     fn decimal(&self) -> u32 { self.decimal as u32}
 }
 
+impl U16Fixed16Number {
+    /// from_f64 encodes a value as specified, value * 65536 rounded and clamped to the range
+    pub fn from_f64(val:f64) -> Self {
+        let fixed = (val * 65536.0).round().clamp(0.0,u32::MAX as f64) as u32;
+        Self {
+            integer: (fixed >> 16) as u16,
+            decimal: fixed as u16,
+        }
+    }
+
+    pub fn to_be_bytes(&self) -> [u8;4] {
+        ((self.integer as u32) << 16 | self.decimal as u32).to_be_bytes()
+    }
+}
+
 #[derive(Debug)]
 pub struct U1Fixed15Number {
     decimal:u16,
@@ -268,6 +543,14 @@ pub struct XYZNumber {
 }
 
 impl XYZNumber {
+    pub fn from_f64(x:f64,y:f64,z:f64) -> Self {
+        Self {
+            x: S15Fixed16Number::from_f64(x),
+            y: S15Fixed16Number::from_f64(y),
+            z: S15Fixed16Number::from_f64(z),
+        }
+    }
+
     pub fn to_string(&self) -> String {
         format!("X:{} Y:{} Z:{} ",self.x.as_f32(),self.y.as_f32(),self.z.as_f32())
     }
@@ -360,6 +643,24 @@ pub struct MClut {
 }
 
 impl MClut {
+    /// grid_points returns the grid points of each input channel, empty without a CLUT
+    pub fn grid_points(&self) -> &[u8] {
+        &self.grid_points
+    }
+
+    /// precision is 1 for 8 bit and 2 for 16 bit entries
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn clut_data(&self) -> &Clut {
+        &self.clut_data
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn len(&self) -> usize {
         match &self.clut_data {
             Clut::UInt16(clut) => {
@@ -499,12 +800,6 @@ pub struct MeasurementType {
     pub standard_illuminant: u32,
 }
 
-#[derive(Debug)]
-pub struct ColorantTableType {
-    pub colorant_name:String,
-    pub pcs_values:Vec<[u16;3]>,
-}
-
 #[derive(Debug)]
 pub struct PositionNumber {
     pub offset:u32,
@@ -516,6 +811,9 @@ pub struct MultiProcessElementsType {
     pub input_channels: u16,
     pub output_channels: u16,
     pub process_element_positions: Vec<PositionNumber>,
+    pub elements: Vec<ProcessElement>,
+    /// raw bytes after the position table
+    #[deprecated(note = "use `elements`")]
     pub data: Vec<u8>,
 }
 
@@ -527,20 +825,21 @@ pub struct CurveSetType {
     pub data: Vec<u8>,
 }
 
+#[deprecated(note = "'curf' is decoded as `Data::SegmentedCurve`")]
 #[derive(Debug)]
 pub struct OneDimensionalCurvesType {
     pub segments: u16,
     pub dimensional_curves: Vec<Vec<f32>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct MatrixElement {
     pub input_channels: u16,
     pub output_channels: u16,
     pub matrix: Vec<f32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Descriptor {
     pub ascii_string: String,
     pub lang: String,
@@ -553,7 +852,7 @@ impl Descriptor {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct LocalizedUnicode {
     pub lang: String,
     pub country: String,
@@ -566,7 +865,7 @@ impl LocalizedUnicode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct MultiLocalizedUnicodeType {
     pub unicode_strings : Vec<LocalizedUnicode>,
 }
@@ -592,6 +891,14 @@ impl MultiLocalizedUnicodeType {
         }
     }
 
+    /// localized returns the string for a language and country, then for the language, then the first one
+    pub fn localized(&self,lang:&str,country:&str) -> Option<&str> {
+        self.unicode_strings.iter().find(|string| string.lang == lang && string.country == country)
+            .or_else(|| self.unicode_strings.iter().find(|string| string.lang == lang))
+            .or_else(|| self.unicode_strings.first())
+            .map(|string| string.unicode_string.as_str())
+    }
+
     pub fn list_string(&self) -> String {
         let mut str = "".to_string();
         for unicode_string in &self.unicode_strings{
@@ -601,15 +908,6 @@ impl MultiLocalizedUnicodeType {
     }
 }
 
-#[derive(Debug)]
-pub struct ProfileDescription {
-    pub device_manufacturer:u32,
-    pub device_model:u32,
-    pub device_attributes:String,
-    pub technology_information:u32,
-    pub description_device_manufacturer: MultiLocalizedUnicodeType,
-    pub description_device_model: MultiLocalizedUnicodeType,
-}
 
 #[derive(Debug)]
 pub enum Data {
@@ -652,7 +950,11 @@ pub enum Data {
     ViewConditions(ViewingConditions),
     Measurement(MeasurementType),
     CurveSet(CurveSetType),
+    #[deprecated(note = "'pseq' is decoded as `Data::ProfileSequence`")]
     ProfileDescription(Vec<ProfileDescription>),
+    ProfileSequence(ProfileSequenceDescription),
+    ProfileSequenceId(ProfileSequenceIdentifier),
+    Dictionary(DictType),
 
     // Type ICC 4.0
     FormulaCurve(FormulaCurve),
@@ -664,9 +966,19 @@ pub enum Data {
     ResponseCurveSet16(ResponseCurveSet16),
     CrdInfo(Vec<String>),
     ColorantTable(ColorantTableType),
+    ColorantOrder(ColorantOrderType),
     MultiProcessElements(MultiProcessElementsType),
+    #[deprecated(note = "'curf' is decoded as `Data::SegmentedCurve`")]
+    #[allow(deprecated)]
     OneDimenstionalCurves(OneDimensionalCurvesType),
+    SegmentedCurve(SegmentedCurve),
     MatrixElement(MatrixElement),
+
+    // Type ICC 4.4
+    Cicp(CicpType),
+
+    // Private
+    VideoCardGamma(VideoCardGammaType),
     None,
 }
 
@@ -687,11 +999,15 @@ fn illuminant_type_string(measurement_illuminate: u32) -> String {
 
 impl Data {
     pub fn parse(data: &[u8],length:usize,version:u32) -> Result<(String,Data)> {
+        Self::parse_with_options(data,length,version,&DecodeOptions::default())
+    }
+
+    pub fn parse_with_options(data: &[u8],length:usize,version:u32,options: &DecodeOptions) -> Result<(String,Data)> {
         let data_type = Self::read_data_type(data,0)?;
-        Ok((data_type.clone(),Self::get(&data_type,data,length,version)?))
+        Ok((data_type.clone(),Self::get_with_options(&data_type,data,length,version,options)?))
     }
 
-    fn read_parmetic_curve(data:&[u8]) -> Result<Curve> {
+    fn read_parmetic_curve(data:&[u8],options: &DecodeOptions) -> Result<Curve> {
         bound_check(data,0,12)?;
         let data_type = Self::read_data_type(data,0)?;
         if data_type != "para" {
@@ -700,6 +1016,7 @@ impl Data {
             let mut curv = vec![];
             let count = read_u32_be(data, ptr) as usize;
             ptr += 4;
+            limit_check("curve length",count,options.max_curve_length)?;
             bound_check(data, ptr, count * 2)?;
             for _ in 0..count {
                 curv.push(read_u16_be(data, ptr));
@@ -744,8 +1061,25 @@ impl Data {
         Ok(Curve::ParametricCurve(ParametricCurve{funtion_type,vals}))
     }
 
+    /// curves of lutAtoBType and lutBtoAType. Each curve is padded to a 4 byte boundary.
+    fn read_curves(data:&[u8],offset:usize,channels:u8,options: &DecodeOptions) -> Result<Vec<Curve>> {
+        let mut curves = vec![];
+        if offset == 0 {
+            return Ok(curves)
+        }
+        let mut ptr = offset;
+        for _ in 0..channels {
+            let curve_data = data.get(ptr..)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,format!("curve offset {} is out of the tag",ptr)))?;
+            let curve = Self::read_parmetic_curve(curve_data,options)?;
+            ptr += (curve.as_size() + 3) & !3;
+            curves.push(curve);
+        }
+        Ok(curves)
+    }
+
     fn read_formula_curve(data:&[u8]) -> Result<FormulaCurve> {
-        bound_check(data,0,4)?;
+        bound_check(data,0,12)?;
         let mut ptr = 8;
         let funtion_type = read_u16_be(data,ptr);
         ptr += 4;
@@ -776,8 +1110,6 @@ impl Data {
     fn read_localization(data:&[u8],ptr:usize,_version:u32) -> Result<LocalizedUnicode> {
         let mut ptr = ptr;
         bound_check(data, ptr, 12)?;
-        let _ = read_u32_be(data,ptr);  // MUST 12
-        ptr +=4;
         let lang = read_ascii_string(data,ptr,2);
         ptr +=2;
         let country = read_ascii_string(data,ptr,2);
@@ -789,7 +1121,7 @@ impl Data {
         let mut len = 0;
         bound_check(data, name_offset, name_length)?;
         let mut vals = vec![];
-        while len < name_length {
+        while len + 2 <= name_length {
             let val = read_u16_be(data, name_offset + len);
             if val == 0 {
                 break;
@@ -808,26 +1140,118 @@ impl Data {
 
     fn read_multi_localization(data:&[u8],ptr:usize,version:u32) -> Result<MultiLocalizedUnicodeType>{
         let mut ptr = ptr;
-        bound_check(data, ptr, 20)?;
+        bound_check(data, ptr, 8)?;
         let number_of_names = read_u32_be(data,ptr) as usize;
         ptr +=4;
+        let record_size = read_u32_be(data,ptr) as usize;   // MUST 12
+        ptr +=4;
+        if record_size < 12 {
+            return Err(Error::new(ErrorKind::InvalidData,format!("mluc record size {}",record_size)))
+        }
+        bound_check(data, ptr, number_of_names.saturating_mul(record_size))?;
         let mut unicode_strings = Vec::with_capacity(number_of_names);
         for _ in 0..number_of_names {
             let string =Self::read_localization(data, ptr, version)?;
             unicode_strings.push(string);
+            ptr += record_size;
         }
         Ok(MultiLocalizedUnicodeType {
             unicode_strings
         })
     }
 
+    /// textDescriptionType. The Unicode part is optional because many v2 profiles truncate it.
+    fn read_text_description(data:&[u8],length:usize) -> Result<Descriptor> {
+        let mut ptr = 8;
+        bound_check(data, ptr, 4)?;
+        let counts = read_u32_be(data, ptr) as usize;
+        ptr += 4;
+        bound_check(data, ptr, counts)?;
+        let ascii_string = read_ascii_string(data, ptr, counts);
+        ptr += counts;
+        let mut lang = "".to_string();
+        let mut vals = vec![];
+        if ptr + 8 <= length {
+            lang = read_ascii_string(data, ptr,4);
+            ptr += 4;
+            let counts = read_u32_be(data, ptr) as usize;
+            ptr += 4;
+            // Unicode
+            let mut len = 0;
+            while len < counts && ptr + 2 <= length {
+                let val = read_u16_be(data, ptr);
+                if val == 0 {
+                    break;
+                }
+                vals.push(val);
+                ptr += 2;
+                len += 1;
+            }
+        }
+        let local_string = String::from_utf16_lossy(&vals);
+        Ok(Descriptor{
+            ascii_string,
+            lang,
+            local_string,
+            // Macintosh Profile
+        })
+    }
+
+    /// embedded textDescriptionType or multiLocalizedUnicodeType in pseq and psid, returns (size,text)
+    pub(crate) fn read_embedded_text(data:&[u8],version:u32) -> Result<(usize,TextDescription)> {
+        let data_type = Self::read_data_type(data,0)?;
+        let length = match &*data_type {
+            "desc" => {
+                bound_check(data, 8, 4)?;
+                let ascii_count = read_u32_be(data, 8) as usize;
+                let ptr = 12usize.saturating_add(ascii_count);
+                bound_check(data, ptr, 8)?;
+                let unicode_count = read_u32_be(data, ptr + 4) as usize;
+                ptr.saturating_add(8).saturating_add(unicode_count.saturating_mul(2)).saturating_add(70)
+            },
+            "mluc" => {
+                bound_check(data, 8, 8)?;
+                let number_of_names = read_u32_be(data, 8) as usize;
+                let record_size = read_u32_be(data, 12) as usize;
+                let mut length = 16usize.saturating_add(number_of_names.saturating_mul(record_size));
+                bound_check(data, 16, number_of_names.saturating_mul(record_size))?;
+                for i in 0..number_of_names {
+                    let ptr = 16 + i * record_size;
+                    bound_check(data, ptr, 12)?;
+                    let end = (read_u32_be(data, ptr + 4) as usize).saturating_add(read_u32_be(data, ptr + 8) as usize);
+                    length = length.max(end);
+                }
+                length
+            },
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidData,format!("{} is not a text description",data_type)))
+            }
+        };
+        bound_check(data, 0, length)?;
+        let text = if data_type == "desc" {
+            TextDescription::Text(Self::read_text_description(&data[..length],length)?)
+        } else {
+            TextDescription::Localized(Self::read_multi_localization(&data[..length],8,version)?)
+        };
+        Ok((length,text))
+    }
+
     pub fn get(data_type:&str,data: &[u8],length:usize,version:u32) -> Result<Data> {
+        Self::get_with_options(data_type,data,length,version,&DecodeOptions::default())
+    }
+
+    pub fn get_with_options(data_type:&str,data: &[u8],length:usize,version:u32,options: &DecodeOptions) -> Result<Data> {
+        if length < 8 {
+            return Err(Error::new(ErrorKind::InvalidData,format!("{} data length {} is too short",data_type,length)))
+        }
         let len = length - 8;
         let mut ptr = 8;
         bound_check(data,ptr, len)?;
+        // never read past the tag
+        let data = &data[..length];
         match data_type {
             "para" => {
-                let curve = Self::read_parmetic_curve(data)?;
+                let curve = Self::read_parmetic_curve(data,options)?;
                 if let Curve::ParametricCurve(p_curve) = curve {
                     Ok(ParametricCurve(p_curve))
                 } else {
@@ -838,12 +1262,13 @@ impl Data {
                 Ok(FormulaCurve(Self::read_formula_curve(data)?))
             },
             "sig " => {
+                bound_check(data, ptr, 4)?;
                 let string = read_ascii_string(data, ptr, 4);
                 Ok(ASCII(string))
             }
             "XYZ " | "XYZ" => {
                 let mut xyzs :Vec<XYZNumber> = vec![];
-                while  ptr < length {
+                while  ptr + 12 <= length {
                     let xyz = Self::xyz_number(data, ptr)?;
                     xyzs.push(xyz);
                     ptr += 12;
@@ -852,7 +1277,7 @@ impl Data {
             },
             "sf32" => { //s16Fixed16ArrayType
                 let mut vals :Vec<S15Fixed16Number> = vec![];
-                while  ptr < length {
+                while  ptr + 4 <= length {
                     vals.push(S15Fixed16Number{
                         integer: read_i16_be(data, ptr),
                         decimal: read_u16_be(data, ptr+2)
@@ -863,7 +1288,7 @@ impl Data {
             },
             "uf32" => { //U16Fixed16ArrayType
                 let mut vals :Vec<U16Fixed16Number> = vec![];
-                while  ptr < length {
+                while  ptr + 4 <= length {
                     vals.push(U16Fixed16Number{
                         integer: read_u16_be(data, ptr),
                         decimal: read_u16_be(data, ptr+2)
@@ -878,7 +1303,7 @@ impl Data {
             },
             "ui16" => { 
                 let mut vals= vec![];
-                while  ptr < length {
+                while  ptr + 2 <= length {
                     vals.push(read_u16_be(data, ptr));
                     ptr += 2;
                 }
@@ -886,7 +1311,7 @@ impl Data {
             },
             "ui32" => { 
                 let mut vals= vec![];
-                while  ptr < length {
+                while  ptr + 4 <= length {
                     vals.push(read_u32_be(data, ptr));
                     ptr += 4;
                 }
@@ -894,7 +1319,7 @@ impl Data {
             },
             "ui64" => { 
                 let mut vals= vec![];
-                while  ptr < length {
+                while  ptr + 8 <= length {
                     vals.push(read_u64_be(data, ptr));
                     ptr += 8;
                 }
@@ -905,56 +1330,28 @@ impl Data {
                 Ok(Self::ASCII(string))
             },
             "desc" => {
-                if version >= 0x40000000 {
-                    let counts = read_u32_be(data, ptr) as usize;
-                    ptr +=4;
-                    bound_check(data,ptr,counts)?;
-                    let ascii_string = read_ascii_string(data, ptr+4,counts);
-                    ptr += counts;
-                    bound_check(data,ptr,8)?;
-                    let lang = read_ascii_string(data, ptr,4);
-                    ptr += 4;
-                    let counts = read_u32_be(data, ptr) as usize;
-                    ptr +=4;
-                    bound_check(data,ptr,counts)?;
-                    // Unicode
-                    let mut len = 0;
-                    let mut vals = vec![];
-                    while len < counts {
-                        let val = read_u16_be(data, ptr);
-                        if val == 0 {
-                        break;
-                        }
-                        vals.push(val);
-                        ptr += 2;
-                        len += 2;
-                    }
-                    let local_string = String::from_utf16_lossy(&vals);
-                    Ok(Descriptor(Descriptor{
-                        ascii_string,
-                        lang,
-                        local_string,
-                        // Macintosh Profile
-                    }))
+                let descriptor = Self::read_text_description(data,length)?;
+                if version >= 0x04000000 {
+                    Ok(Descriptor(descriptor))
                 } else {
-                    let ascii_string = read_ascii_string(data, ptr+4,len-4);
-                    Ok(ASCII(ascii_string))
+                    Ok(ASCII(descriptor.ascii_string))
                 }
             }, 
             "chrm" => {
+                bound_check(data, ptr, 4)?;
                 let device_number = read_u16_be(data,ptr);
                 let encoded_value = read_u16_be(data,ptr+2);
                 ptr += 4;
                 let mut vals :Vec<(U16Fixed16Number,U16Fixed16Number)> = vec![];
-                while  ptr < length {
+                while  ptr + 8 <= length {
                     vals.push((
                         U16Fixed16Number{
                             integer: read_u16_be(data, ptr),
                             decimal: read_u16_be(data, ptr+2)
                         },
                         U16Fixed16Number{
-                            integer: read_u16_be(data, ptr+2),
-                            decimal: read_u16_be(data, ptr+4)
+                            integer: read_u16_be(data, ptr+4),
+                            decimal: read_u16_be(data, ptr+6)
                         }));
                     ptr += 8;
                 }
@@ -973,7 +1370,7 @@ impl Data {
 
             },
             "view" => {
-                bound_check(data, ptr, 32)?;
+                bound_check(data, ptr, 28)?;
                 let illuminant = Self::xyz_number(data, ptr)?;
                 ptr += 12;
                 let surround = Self::xyz_number(data, ptr)?;
@@ -1016,6 +1413,7 @@ impl Data {
                 let mut curv = vec![];
                 let count = read_u32_be(data, ptr) as usize;
                 ptr += 4;
+                limit_check("curve length",count,options.max_curve_length)?;
                 bound_check(data, ptr, count * 2)?;
                 for _ in 0..count {
                     curv.push(read_u16_be(data, ptr));
@@ -1024,7 +1422,7 @@ impl Data {
                 Ok(Curve(curv))
             },
             "mft1" | "mft2"  => {         
-                bound_check(data, ptr,52)?;
+                bound_check(data, ptr,40)?;
                 let input_channels= read_byte(data, ptr);
                 ptr +=1;
                 let output_channels = read_byte(data, ptr);
@@ -1043,7 +1441,10 @@ impl Data {
                     ptr += 4;
                 }
 
-                let clut_size = ((number_of_clut_grid_points as u32).pow(input_channels as u32) * output_channels as u32) as usize;
+                let clut_size = (number_of_clut_grid_points as usize).checked_pow(input_channels as u32)
+                    .and_then(|size| size.checked_mul(output_channels as usize))
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData,"CLUT size overflow"))?;
+                limit_check("CLUT entries",clut_size,options.max_clut_entries)?;
 
                 if data_type == "mft1" {
                     let mut input_table = vec![];
@@ -1053,7 +1454,7 @@ impl Data {
                     let input_channels_size = input_channels as usize * 256;
                     let output_channels_size = output_channels as usize * 256;
 
-                    bound_check(data, ptr,input_channels_size + clut_size + output_channels_size)?;
+                    bound_check(data, ptr,input_channels_size.saturating_add(clut_size).saturating_add(output_channels_size))?;
                     for _ in 0..input_channels_size {
                         input_table.push(read_byte(data, ptr));
                         ptr += 1;
@@ -1092,9 +1493,11 @@ impl Data {
                     let output_table_enteries = read_u16_be(data, ptr);
                     ptr += 2;
 
+                    limit_check("curve length",input_table_enteries as usize,options.max_curve_length)?;
+                    limit_check("curve length",output_table_enteries as usize,options.max_curve_length)?;
                     let input_channels_size = input_channels as usize * input_table_enteries as usize;
                     let output_channels_size = output_channels as usize * output_table_enteries as usize;
-                    bound_check(data, ptr,(input_channels_size + clut_size + output_channels_size) * 2)?;
+                    bound_check(data, ptr,input_channels_size.saturating_add(clut_size).saturating_add(output_channels_size).saturating_mul(2))?;
 
                     for _ in 0..input_channels_size {
                         input_table.push(read_u16_be(data, ptr));
@@ -1127,7 +1530,7 @@ impl Data {
                 }
             },
             "mBA " | "mAB " => { // no sample 4.0
-                bound_check(data, ptr,32)?;
+                bound_check(data, ptr,24)?;
 
                 let input_channels= read_byte(data, ptr);
                 ptr +=1;
@@ -1144,80 +1547,81 @@ impl Data {
                 ptr +=4;
                 let offset_a_curve = read_u32_be(data, ptr) as usize;
 
-                let mut b_curves = vec![];
-                let mut m_curves = vec![];
-                let mut a_curves = vec![];
-                let mut matrix = vec![];
+                // mAB: A curves(input) -> CLUT -> M curves(output) -> Matrix -> B curves(output)
+                // mBA: B curves(input) -> Matrix -> M curves(input) -> CLUT -> A curves(output)
+                let (a_channels,bm_channels) = if data_type == "mBA " {
+                    (output_channels,input_channels)
+                } else {
+                    (input_channels,output_channels)
+                };
 
-                let mut ptr = offset_b_curve;
-                for _ in 0..input_channels {
-                    let b_curve = Self::read_parmetic_curve(&data[ptr..])?;
-                    ptr += b_curve.as_size();
-                    b_curves.push(b_curve);
-                }
-                let mut ptr = offset_matrix;
-                bound_check(data, ptr,12 *4)?;
-                for _ in 0..12 {
-                    let e = S15Fixed16Number {
-                        integer: read_i16_be(data, ptr),
-                        decimal: read_u16_be(data, ptr+2)
-                    };
-                    matrix.push(e);
-                    ptr += 4;
-                }
-                let mut ptr = offset_m_curve;
-                for _ in 0..input_channels {
-                    let m_curve = Self::read_parmetic_curve(&data[ptr..])?;
-                    ptr += m_curve.as_size();
-                    m_curves.push(m_curve);
-                }
-                let mut ptr = offset_clut;
-                bound_check(data, ptr,16)?;
-
-                let mut grid_points = vec![];
-                let mut clut_size = output_channels as usize;
-                for i in 0..16 {
-                    let grid_point = read_byte(&data,ptr+i);
-                    if grid_point > 0 {
-                        clut_size *= grid_point as usize;
-                    } else {
-                        break;
+                // offset 0 means the element is not present
+                let b_curves = Self::read_curves(data,offset_b_curve,bm_channels,options)?;
+                let m_curves = Self::read_curves(data,offset_m_curve,bm_channels,options)?;
+                let a_curves = Self::read_curves(data,offset_a_curve,a_channels,options)?;
+
+                let mut matrix = vec![];
+                if offset_matrix > 0 {
+                    let mut ptr = offset_matrix;
+                    bound_check(data, ptr,12 *4)?;
+                    for _ in 0..12 {
+                        let e = S15Fixed16Number {
+                            integer: read_i16_be(data, ptr),
+                            decimal: read_u16_be(data, ptr+2)
+                        };
+                        matrix.push(e);
+                        ptr += 4;
                     }
-                    grid_points.push(grid_point);
                 }
-                ptr += 16;
-                let precision = read_byte(&data,ptr);
-                ptr += 4;   // with padding
-                let clut_data = if precision == 1 {
-                    let mut clut_entries:Vec<u8> = vec![];
-                    bound_check(data, ptr,clut_size)?;
-                    for _ in 0..clut_size {
-                        clut_entries.push(read_byte(data, ptr));
-                        ptr += 1;
+
+                let clut = if offset_clut > 0 {
+                    let mut ptr = offset_clut;
+                    bound_check(data, ptr,20)?;
+
+                    let mut grid_points = vec![];
+                    let mut clut_size = output_channels as usize;
+                    for i in 0..(input_channels as usize).min(16) {
+                        let grid_point = read_byte(data,ptr+i);
+                        clut_size = clut_size.checked_mul(grid_point as usize)
+                            .ok_or_else(|| Error::new(ErrorKind::InvalidData,"CLUT size overflow"))?;
+                        grid_points.push(grid_point);
+                    }
+                    limit_check("CLUT entries",clut_size,options.max_clut_entries)?;
+                    ptr += 16;
+                    let precision = read_byte(data,ptr);
+                    ptr += 4;   // with padding
+                    let clut_data = match precision {
+                        1 => {
+                            bound_check(data, ptr,clut_size)?;
+                            Clut::UInt8(read_bytes_as_vec(data, ptr, clut_size))
+                        },
+                        2 => {
+                            let mut clut_entries:Vec<u16> = Vec::with_capacity(clut_size);
+                            bound_check(data, ptr,clut_size.saturating_mul(2))?;
+                            for _ in 0..clut_size {
+                                clut_entries.push(read_u16_be(data, ptr));
+                                ptr += 2;
+                            }
+                            Clut::UInt16(clut_entries)
+                        },
+                        _ => {
+                            return Err(Error::new(ErrorKind::InvalidData,format!("CLUT precision {}",precision)))
+                        }
+                    };
+
+                    MClut {
+                        grid_points,
+                        precision,
+                        clut_data,
                     }
-                    Clut::UInt8(clut_entries)
                 } else {
-                    let mut clut_entries:Vec<u16> = vec![];
-                    bound_check(data, ptr,clut_size * 2)?;
-                    for _ in 0..clut_size {
-                        clut_entries.push(read_u16_be(data, ptr));
-                        ptr += 2;
+                    MClut {
+                        grid_points: vec![],
+                        precision: 2,
+                        clut_data: Clut::UInt16(vec![]),
                     }
-                    Clut::UInt16(clut_entries)
-                };
-
-                let clut = MClut {
-                    grid_points,
-                    precision,
-                    clut_data,
                 };
 
-                let mut ptr = offset_a_curve;
-                for _ in 0..input_channels {
-                    let a_curve = Self::read_parmetic_curve(&data[ptr..])?;
-                    ptr += a_curve.as_size();
-                    a_curves.push(a_curve);
-                }
                 if data_type == "mBA " {
 
                     Ok(LutBtoA(Mba{
@@ -1247,6 +1651,8 @@ impl Data {
                 let number_of_channels = read_u16_be(data, ptr);
                 ptr += 2;
                 let count_of_measirement_types = read_u16_be(data, ptr);
+                ptr += 2;
+                bound_check(data, ptr, count_of_measirement_types as usize * 4)?;
                 let mut count_relative_offsets = vec![];
                 for _ in 0..count_of_measirement_types {
                     count_relative_offsets.push(read_u32_be(data, ptr));
@@ -1327,58 +1733,13 @@ impl Data {
 
             },
             "clro" => {
-                bound_check(data, ptr, 4)?;
-                let counts = read_u32_be(data, ptr) as usize;
-                ptr += 4;
-                let count  = read_u32_be(data, ptr) as usize;
-                bound_check(data, ptr, count)?;
-                let raw = read_bytes_as_vec(data, ptr, counts);
-                Ok(UInt8NumberArray(raw))
+                Ok(ColorantOrder(ColorantOrderType::read(data)?))
             },
             "clrt" => {
-                bound_check(data, ptr, 38)?;
-                let counts = read_u32_be(data, ptr) as usize;
-                ptr += 4;
-                let colorant_name = read_ascii_string(data, ptr, 32);
-                ptr += 32;
-                bound_check(data, ptr, counts*6)?;
-                let mut pcs_values: Vec<[u16;3]> = Vec::with_capacity(counts);
-                for _ in 0..counts {
-                    let mut pcs = [0_u16;3];
-                    pcs[0] = read_u16_be(data, ptr);
-                    pcs[1] = read_u16_be(data, ptr+2);
-                    pcs[2] = read_u16_be(data, ptr+4);
-                    ptr += 6;
-                    pcs_values.push(pcs);
-                }
-
-                Ok(ColorantTable(ColorantTableType{colorant_name,pcs_values}))
-
+                Ok(ColorantTable(ColorantTableType::read(data)?))
             },
             "mpet" => {
-                bound_check(data, ptr, 8)?;
-                let input_channels = read_u16_be(data, ptr);
-                let output_channels = read_u16_be(data, ptr+2);
-                let counts = read_u32_be(data, ptr+4) as usize;
-                ptr += 8;
-                bound_check(data, ptr, counts*8)?;
-                let mut process_element_positions = Vec::with_capacity(counts);
-                for _ in 0..counts {
-                    let offset = read_u32_be(data, ptr);
-                    let size = read_u32_be(data, ptr+4);
-                    ptr += 8;
-                    process_element_positions.push(PositionNumber{offset,size});
-                }
-                let data = read_bytes_as_vec(data,ptr,length - ptr);
-
-                Ok(MultiProcessElements(
-                    MultiProcessElementsType {
-                        input_channels,
-                        output_channels,
-                        process_element_positions,
-                        data
-                    }
-                ))
+                Ok(MultiProcessElements(MultiProcessElementsType::read(data,length,options)?))
             },
             "cvst" => {
                 bound_check(data, ptr, 4)?;
@@ -1406,36 +1767,16 @@ impl Data {
                 ))
             },
             "curf" => { // 4.x
-                bound_check(data, ptr, 4)?;
-                let segments = read_u16_be(data, ptr);
-                ptr += 4;   // skip
-                let counts = segments as usize - 1;
-                let mut dimensional_curves = Vec::with_capacity(segments as usize);
-                for _ in 0..segments as usize {
-                    bound_check(data, ptr, counts*4)?;
-                    let mut  break_points = Vec::with_capacity(counts);
-                    for _ in 0..counts {
-                        let float = read_f32(data, ptr, Endian::BigEndian);
-                        ptr += 4;
-                        break_points.push(float);
-                    }
-                    if ptr + counts * 4 > length {break}
-                    dimensional_curves.push(break_points);
-                }
-
-                Ok(OneDimenstionalCurves(
-                    OneDimensionalCurvesType {
-                        segments,
-                        dimensional_curves,
-                    }
-                ))
+                let (_,curve) = SegmentedCurve::read(data,options)?;
+                Ok(Data::SegmentedCurve(curve))
             },
             "samf" => { // 4.x
                 bound_check(data, ptr, 4)?;
-                let counts = read_u16_be(data, ptr) as usize;
-                ptr += 4;   // skip
+                let counts = read_u32_be(data, ptr) as usize;
+                ptr += 4;
+                limit_check("curve length",counts,options.max_curve_length)?;
                 bound_check(data, ptr, counts * 4)?;
-                let mut curve_entries = vec![];
+                let mut curve_entries = Vec::with_capacity(counts);
                 for _ in 0..counts {
                     let float = read_f32(data, ptr, Endian::BigEndian);
                     ptr += 4;
@@ -1459,7 +1800,7 @@ impl Data {
                 Ok(MatrixElement(MatrixElement{input_channels,output_channels,matrix}))
             },
             "ncl2" => { // ncol obsolute
-                bound_check(data, ptr, 122)?;
+                bound_check(data, ptr, 76)?;
                 let vendor_specific_flag = read_u32_be(data, ptr);
                 ptr += 4;
                 let counts = read_u32_be(data, ptr) as usize;
@@ -1499,74 +1840,20 @@ impl Data {
                 }))
             }
             "pseq" => {
-                bound_check(data,ptr,4)?;
-                let counts = read_u32_be(data, ptr) as usize;
-                ptr += 4;
-                bound_check(data,ptr,counts)?;
-                let mut remain = length - ptr;
-                let mut profiles = vec![];
-                for _ in 0..counts {
-                    bound_check(data,ptr,20)?;
-                    if remain <= 0 {break;}
-                    let device_manufacturer = read_u32_be(data, ptr);
-                    ptr += 4;
-                    let device_model = read_u32_be(data, ptr);
-                    ptr += 4;
-                    let device_attributes = read_ascii_string(data, ptr,8);
-                    ptr += 8;
-                    let technology_information = read_u32_be(data, ptr);
-                    ptr += 4;
-                    remain -=20;
-                    let _tag = read_u32_be(data, ptr); // dmnd
-                    ptr += 4;
-                    let len = read_u32_be(data, ptr) as usize;
-                    ptr += 4;
-                    bound_check(data, ptr, len)?;
-                    let (_,res) = Self::parse(&data[ptr..],len, version)?;
-                    let description_device_manufacturer;
-                    match res {
-                        ASCII(text) => {
-                            description_device_manufacturer = MultiLocalizedUnicodeType::from(text);
-
-                        },
-                        MultiLocalizedUnicode(mlut) => {
-                            description_device_manufacturer = mlut;
-                        }
-                        _ => {
-                            description_device_manufacturer = MultiLocalizedUnicodeType::from("".to_string());
-                        }
-                    }
-                    ptr += len;
-                    let _tag = read_u32_be(data, ptr); // dmnd
-                    ptr += 4;
-                    let len = read_u32_be(data, ptr) as usize;
-                    ptr += 4;
-                    bound_check(data, ptr, len)?;
-                    let (_,res) = Self::parse(&data[ptr..],len, version)?;
-                    let description_device_model;
-                    match res {
-                        ASCII(text) => {
-                            description_device_model = MultiLocalizedUnicodeType::from(text);
-
-                        },
-                        MultiLocalizedUnicode(mlut) => {
-                            description_device_model = mlut;
-                        }
-                        _ => {
-                            description_device_model = MultiLocalizedUnicodeType::from("".to_string());
-                        }
-                    }
-                    profiles.push(ProfileDescription{
-                        device_manufacturer,
-                        device_model,
-                        device_attributes,
-                        technology_information,
-                        description_device_manufacturer,
-                        description_device_model,
-                    })
-                }
-                Ok(ProfileDescription(profiles))
-            }
+                Ok(ProfileSequence(ProfileSequenceDescription::read(data,version)?))
+            },
+            "psid" => {
+                Ok(ProfileSequenceId(ProfileSequenceIdentifier::read(data,version)?))
+            },
+            "dict" => {
+                Ok(Dictionary(DictType::read(data,version)?))
+            },
+            "cicp" => {
+                Ok(Cicp(CicpType::read(data)?))
+            },
+            "vcgt" => {
+                Ok(VideoCardGamma(VideoCardGammaType::read(data)?))
+            },
 
             _ => { // Data type
                 // no impl
@@ -1577,7 +1864,6 @@ impl Data {
                 // "bACS" // 4.0
                 // "eACS" // 4.0
 
-                // "dict" // 5.0
                 // "ehim" // 5.0
                 // "enim" // 5.0
                 // "fl16" // 5.0
@@ -1758,7 +2044,7 @@ impl Data {
                 }
                 str += &format!("input table size {} clut values {}  output table size {}\n",
                     lut.input_table.len(),lut.clut_values.len(),lut.output_table.len());
-                if verbose > 0 {
+                if verbose > 0 && lut.input_channels > 0 && lut.output_channels > 0 && lut.number_of_clut_grid_points > 0 {
                     let len = lut.input_table.len() / lut.input_channels as usize;
                     str += &format!("Input table\n");
                     for j in 0..lut.input_channels as usize {
@@ -1823,7 +2109,7 @@ impl Data {
 
                 str += &format!("input table size {} clut values {}  output table size {}\n",
                     lut.input_table.len(),lut.clut_values.len(),lut.output_table.len());
-                if verbose > 0 {
+                if verbose > 0 && lut.input_channels > 0 && lut.output_channels > 0 && lut.number_of_clut_grid_points > 0 {
                     let len = lut.input_table.len() / lut.input_channels as usize;
                     str += &format!("Input table\n");
                     for j in 0..lut.input_channels as usize {
@@ -1965,6 +2251,96 @@ impl Data {
             None => {
                 "None".to_string()
             },
+            SegmentedCurve(curve) => {
+                let mut str = format!("break points {:?}\n",curve.break_points);
+                for segment in &curve.segments {
+                    str += &match segment {
+                        CurveSegment::Formula{function_type,params} => format!("formula type {} {:?}\n",function_type,params),
+                        CurveSegment::Sampled(entries) if verbose > 0 => format!("sampled {:?}\n",entries),
+                        CurveSegment::Sampled(entries) => format!("sampled {} entries\n",entries.len()),
+                    };
+                }
+                str
+            },
+            MultiProcessElements(mpet) => {
+                let mut str = format!("input channels {} output channels {}\n",mpet.input_channels,mpet.output_channels);
+                for element in &mpet.elements {
+                    str += &match element {
+                        ProcessElement::CurveSet(curves) => format!("curve set {} curves\n",curves.len()),
+                        ProcessElement::Matrix(matrix) => format!("matrix {}x{}\n",matrix.output_channels,matrix.input_channels),
+                        ProcessElement::Clut(clut) => format!("clut grid {:?} output channels {}\n",clut.grid_points,clut.output_channels),
+                        ProcessElement::BeginAcs(_) => "bACS\n".to_string(),
+                        ProcessElement::EndAcs(_) => "eACS\n".to_string(),
+                        ProcessElement::Unknown{signature,..} => format!("{} unknown\n",signature),
+                    };
+                    if verbose > 0 {
+                        str += &format!("{:?}\n",element);
+                    }
+                }
+                str
+            },
+            ProfileSequence(sequence) => {
+                let mut str = "".to_string();
+                for profile in &sequence.profiles {
+                    str += &format!("manufacturer {} model {} attributes {:016x} technology {}\n",
+                        String::from_utf8_lossy(&profile.device_manufacturer.to_be_bytes()),
+                        String::from_utf8_lossy(&profile.device_model.to_be_bytes()),
+                        profile.device_attributes,
+                        String::from_utf8_lossy(&profile.technology_information.to_be_bytes()));
+                    str += &format!("  {} / {}\n",profile.description_device_manufacturer.as_string(),profile.description_device_model.as_string());
+                }
+                str
+            },
+            Dictionary(dict) => {
+                let mut str = "".to_string();
+                for entry in &dict.entries {
+                    str += &format!("{} = {}\n",entry.name,entry.value.as_deref().unwrap_or("(null)"));
+                    if let Some(display_name) = &entry.display_name {
+                        str += &format!("  display name {}",display_name.list_string());
+                    }
+                    if let Some(display_value) = &entry.display_value {
+                        str += &format!("  display value {}",display_value.list_string());
+                    }
+                }
+                str
+            },
+            VideoCardGamma(VideoCardGammaType::Formula(formula)) => {
+                let mut str = "".to_string();
+                for (channel,formula) in ["red","green","blue"].iter().zip(formula) {
+                    str += &format!("{} gamma {} min {} max {}\n",channel,formula.gamma,formula.min,formula.max);
+                }
+                str
+            },
+            VideoCardGamma(VideoCardGammaType::Table { entry_size,channels }) => {
+                let mut str = format!("{} channels {} entries {} bytes\n",channels.len(),channels.first().map(|curve| curve.len()).unwrap_or(0),entry_size);
+                if verbose > 0 {
+                    for curve in channels {
+                        str += &format!("{:?}\n",curve);
+                    }
+                }
+                str
+            },
+            ColorantTable(table) => {
+                let mut str = "".to_string();
+                for (i,colorant) in table.colorants.iter().enumerate() {
+                    str += &format!("{} {} {:?}\n",i,colorant.name,colorant.pcs);
+                }
+                str
+            },
+            ColorantOrder(order) => {
+                format!("{:?}",order.order)
+            },
+            Cicp(cicp) => {
+                format!("primaries {} transfer {} matrix {} full range {}",
+                    cicp.color_primaries,cicp.transfer_characteristics,cicp.matrix_coefficients,cicp.video_full_range_flag)
+            },
+            ProfileSequenceId(identifier) => {
+                let mut str = "".to_string();
+                for profile in &identifier.profiles {
+                    str += &format!("{:032x} {}\n",profile.profile_id,profile.description.as_string());
+                }
+                str
+            },
             _ => {
                 format!("{:?}",*self)
             }
@@ -1999,3 +2375,101 @@ impl Data {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn decode_mutated_profiles_without_panic() {
+        let base = sample();
+        DecodedICCProfile::new(&base).unwrap();
+        let options = DecodeOptions::untrusted();
+        let mut seed = 0x2545f491_u32;
+        for _ in 0..20000 {
+            let mut data = base.clone();
+            for _ in 0..(seed % 8 + 1) {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let pos = seed as usize % data.len();
+                data[pos] = (seed >> 24) as u8;
+            }
+            if seed.is_multiple_of(5) {
+                data.truncate(seed as usize % data.len());
+            }
+            if let Ok(decoded) = DecodedICCProfile::new_with_options(&data,&options) {
+                let _ = decoded.to_string_with_verbose(1);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_lenient() {
+        let mut base = sample();
+        base[36..40].copy_from_slice(b"ascp");
+        base[8..12].copy_from_slice(&0x04200000_u32.to_be_bytes());
+        let mut tags = profile(&[("A2B0",tag("mft2",&[3,3,2,0]))]);
+        base.truncate(base.len() - 4);
+        tags.truncate(tags.len() - 2);
+        assert!(DecodedICCProfile::new(&base).is_err());
+        let decoded = DecodedICCProfile::new_with_options(&base,&DecodeOptions::lenient()).unwrap();
+        let kinds :Vec<WarningKind> = decoded.warnings.iter().map(|w| w.kind).collect();
+        assert!(kinds.contains(&WarningKind::MissingAcsp));
+        assert!(kinds.contains(&WarningKind::HeaderSize));
+        assert!(kinds.contains(&WarningKind::DescriptionInV4));
+        assert!(kinds.contains(&WarningKind::TagLength));
+        let decoded = DecodedICCProfile::new_with_options(&tags,&DecodeOptions::lenient()).unwrap();
+        assert!(matches!(decoded.tags.get("A2B0"),Some(Data::Raw(_,_))));
+        assert_eq!(decoded.warnings.last().unwrap().kind,WarningKind::BrokenTag);
+
+        // the device model, and a private tag that may use any type
+        let mut text = profile(&[("dmdd",tag("desc",&[0,0,0,5,b'm',b'o',b'd',b'l',0])),("priv",tag("desc",&[0,0,0,1,0]))]);
+        text[8..12].copy_from_slice(&0x04200000_u32.to_be_bytes());
+        let decoded = DecodedICCProfile::new_with_options(&text,&DecodeOptions::lenient()).unwrap();
+        let tags :Vec<_> = decoded.warnings.iter().filter(|w| w.kind == WarningKind::DescriptionInV4).map(|w| w.tag.as_deref()).collect();
+        assert_eq!(tags,[Some("dmdd")]);
+
+        let mut magic = sample();
+        magic[36..40].copy_from_slice(b"ascp");
+        let decoded = DecodedICCProfile::new(&magic).unwrap();
+        assert_eq!(decoded.warnings.iter().map(|w| w.kind).collect::<Vec<_>>(),[WarningKind::MissingAcsp]);
+        let strict = DecodeOptions { strict: true, ..DecodeOptions::default() };
+        assert!(DecodedICCProfile::new_with_options(&magic,&strict).is_err());
+    }
+
+    #[test]
+    fn decode_text_description() {
+        // "gray" and a Unicode part of 2 UTF-16 units and a terminator
+        let mut desc = vec![0,0,0,5];
+        desc.extend_from_slice(b"gray\0enUS\0\0\0\x03");
+        for unit in "\u{7070}\u{8272}\0".encode_utf16() {
+            desc.extend_from_slice(&unit.to_be_bytes());
+        }
+        desc.resize(desc.len() + 70,0);
+        let data = tag("desc",&desc);
+        match Data::parse(&data,data.len(),0x04200000).unwrap().1 {
+            Descriptor(descriptor) => {
+                assert_eq!(descriptor.ascii_string,"gray");
+                assert_eq!(descriptor.local_string,"\u{7070}\u{8272}");
+                let encoded = Descriptor(descriptor).encode("desc").unwrap();
+                match Data::parse(&encoded,encoded.len(),0x04200000).unwrap().1 {
+                    Descriptor(decoded) => assert_eq!(decoded.local_string,"\u{7070}\u{8272}"),
+                    other => panic!("{:?}",other),
+                }
+            },
+            other => panic!("{:?}",other),
+        }
+        assert!(matches!(Data::parse(&data,data.len(),0x02100000).unwrap().1,ASCII(text) if text == "gray"));
+    }
+
+    #[test]
+    fn decode_limits() {
+        let base = sample();
+        let options = DecodeOptions { max_clut_entries: 8, ..DecodeOptions::default() };
+        assert!(DecodedICCProfile::new_with_options(&base,&options).is_err());
+        let options = DecodeOptions { max_tag_count: 2, ..DecodeOptions::default() };
+        assert!(DecodedICCProfile::new_with_options(&base,&options).is_err());
+    }
+}
@@ -29,8 +29,29 @@
 //! - CMYK 0..255,0..255,0..255,0..255 (u8)
 
 pub use crate::iccprofile::*;
+pub use crate::profile_ref::IccProfileRef;
+pub use crate::reader::IccProfileReader;
+pub use crate::builder::ICCProfileBuilder;
 pub mod utils;
 pub mod iccprofile;
+pub mod profile_ref;
+pub mod reader;
+pub mod builder;
+pub mod encoder;
+pub mod validator;
+pub mod repair;
+pub mod mpet;
+pub mod named_color;
+pub mod swatch;
+pub mod profile_sequence;
+pub mod dict;
+pub mod cicp;
+pub mod vcgt;
+pub mod colorant;
+mod md5;
+#[cfg(test)]
+mod test_utils;
+pub mod tag_directory;
 pub mod cms;
 
 #[cfg(test)]
@@ -0,0 +1,512 @@
+//! multiProcessElementsType (mpet)
+//! Processing elements of D2Bx/B2Dx tags are decoded into typed elements and evaluated in float.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn d2b0(decoded:&DecodedICCProfile,input:&[f32]) -> std::io::Result<Option<Vec<f32>>> {
+//!     match decoded.tags.get("D2B0") {
+//!         Some(Data::MultiProcessElements(mpet)) => Ok(Some(mpet.eval(input)?)),
+//!         _ => Ok(None),
+//!     }
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use bin_rs::Endian;
+use crate::iccprofile::*;
+use crate::utils::{bound_check,limit_check};
+
+#[derive(Debug,Clone)]
+pub enum CurveSegment {
+    /// formula segment 'parf'. function type 0: (a*x+b)^g+c, 1: a*log10(b*x^g+c)+d, 2: a*b^(c*x+d)+e
+    Formula {
+        function_type: u16,
+        params: Vec<f32>,
+    },
+    /// sampled segment 'samf'. Entries are spaced evenly after the start break point,
+    /// the value at the start break point comes from the previous segment.
+    Sampled(Vec<f32>),
+}
+
+/// segmentedCurveType 'curf'. Segment i covers (break_points[i-1],break_points[i]].
+/// The first segment starts at -infinity and the last segment ends at +infinity.
+#[derive(Debug,Clone)]
+pub struct SegmentedCurve {
+    pub break_points: Vec<f32>,
+    pub segments: Vec<CurveSegment>,
+}
+
+/// CLUT element 'clut' with float nodes
+#[derive(Debug,Clone)]
+pub struct FloatClut {
+    pub input_channels: u16,
+    pub output_channels: u16,
+    pub grid_points: Vec<u8>,
+    pub data: Vec<f32>,
+}
+
+#[derive(Debug,Clone)]
+pub enum ProcessElement {
+    /// 'cvst' one segmented curve for each channel
+    CurveSet(Vec<SegmentedCurve>),
+    /// 'matf'
+    Matrix(MatrixElement),
+    /// 'clut'
+    Clut(FloatClut),
+    /// 'bACS' future expansion, values pass through
+    BeginAcs(u32),
+    /// 'eACS' future expansion, values pass through
+    EndAcs(u32),
+    Unknown {
+        signature: String,
+        input_channels: u16,
+        output_channels: u16,
+        data: Vec<u8>,
+    },
+}
+
+fn read_f32_be(data:&[u8],ptr:usize) -> f32 {
+    read_f32(data,ptr,Endian::BigEndian)
+}
+
+fn read_f32_array(data:&[u8],ptr:usize,counts:usize) -> Result<Vec<f32>> {
+    bound_check(data,ptr,counts.saturating_mul(4))?;
+    Ok((0..counts).map(|i| read_f32_be(data,ptr + i * 4)).collect())
+}
+
+impl CurveSegment {
+    /// read reads a 'parf' or 'samf' record and returns the record size
+    pub(crate) fn read(data:&[u8],options: &DecodeOptions) -> Result<(usize,Self)> {
+        bound_check(data,0,12)?;
+        let signature = read_string(data,0,4);
+        match &*signature {
+            "parf" => {
+                let function_type = read_u16_be(data,8);
+                let counts = match function_type {
+                    0 => 4,
+                    1 | 2 => 5,
+                    _ => return Err(Error::new(ErrorKind::InvalidData,format!("parf function type {}",function_type))),
+                };
+                let params = read_f32_array(data,12,counts)?;
+                Ok((12 + counts * 4,CurveSegment::Formula{function_type,params}))
+            },
+            "samf" => {
+                let counts = read_u32_be(data,8) as usize;
+                limit_check("curve length",counts,options.max_curve_length)?;
+                let entries = read_f32_array(data,12,counts)?;
+                Ok((12 + counts * 4,CurveSegment::Sampled(entries)))
+            },
+            _ => Err(Error::new(ErrorKind::InvalidData,format!("{} is not a curve segment",signature))),
+        }
+    }
+
+    fn eval_formula(function_type:u16,params:&[f32],x:f32) -> f32 {
+        match function_type {
+            0 => {
+                let (g,a,b,c) = (params[0],params[1],params[2],params[3]);
+                // negative bases are clamped to 0 as the spec defines
+                (a * x + b).max(0.0).powf(g) + c
+            },
+            1 => {
+                let (g,a,b,c,d) = (params[0],params[1],params[2],params[3],params[4]);
+                let val = b * x.max(0.0).powf(g) + c;
+                if val <= 0.0 { d } else { a * val.log10() + d }
+            },
+            _ => {
+                let (a,b,c,d,e) = (params[0],params[1],params[2],params[3],params[4]);
+                a * b.powf(c * x + d) + e
+            },
+        }
+    }
+}
+
+impl SegmentedCurve {
+    /// read reads a 'curf' record and returns the record size
+    pub(crate) fn read(data:&[u8],options: &DecodeOptions) -> Result<(usize,Self)> {
+        bound_check(data,0,12)?;
+        let signature = read_string(data,0,4);
+        if signature != "curf" {
+            return Err(Error::new(ErrorKind::InvalidData,format!("{} is not a segmented curve",signature)))
+        }
+        let counts = read_u16_be(data,8) as usize;
+        if counts == 0 {
+            return Err(Error::new(ErrorKind::InvalidData,"segmented curve has no segment"))
+        }
+        let break_points = read_f32_array(data,12,counts - 1)?;
+        if break_points.iter().any(|point| !point.is_finite()) || break_points.windows(2).any(|w| w[0] > w[1]) {
+            return Err(Error::new(ErrorKind::InvalidData,format!("break points {:?} are not increasing",break_points)))
+        }
+        let mut ptr = 12 + (counts - 1) * 4;
+        let mut segments = Vec::with_capacity(counts);
+        for i in 0..counts {
+            let (size,segment) = CurveSegment::read(&data[ptr.min(data.len())..],options)?;
+            if matches!(segment,CurveSegment::Sampled(_)) && (i == 0 || i == counts - 1) {
+                return Err(Error::new(ErrorKind::InvalidData,"sampled segment has an infinite domain"))
+            }
+            ptr += size;
+            segments.push(segment);
+        }
+        Ok((ptr,Self{break_points,segments}))
+    }
+
+    fn segment_index(&self,x:f32) -> usize {
+        self.break_points.iter().position(|&point| x <= point).unwrap_or(self.break_points.len())
+    }
+
+    fn eval_segment(&self,i:usize,x:f32) -> f32 {
+        match &self.segments[i] {
+            CurveSegment::Formula{function_type,params} => CurveSegment::eval_formula(*function_type,params,x),
+            CurveSegment::Sampled(entries) => {
+                if entries.is_empty() || i == 0 || i >= self.break_points.len() {
+                    return entries.last().copied().unwrap_or(0.0)
+                }
+                let start = self.break_points[i - 1];
+                let end = self.break_points[i];
+                let first = self.eval_segment(i - 1,start);
+                if end <= start {
+                    return entries[entries.len() - 1]
+                }
+                // entries[k] is at start + (k + 1) * step, the start value is the previous segment
+                let n = entries.len();
+                let pos = ((x - start) / (end - start) * n as f32).clamp(0.0,n as f32);
+                if pos >= n as f32 {
+                    return entries[n - 1]
+                }
+                let k = pos.floor() as usize;
+                let y0 = if k == 0 { first } else { entries[k - 1] };
+                y0 + (entries[k] - y0) * (pos - k as f32)
+            },
+        }
+    }
+
+    /// eval evaluates the curve for any float input, values outside [0,1] use the first or last segment.
+    /// NaN is returned as is.
+    pub fn eval(&self,x:f32) -> f32 {
+        if x.is_nan() {
+            return x
+        }
+        if self.segments.is_empty() {
+            return x
+        }
+        let i = self.segment_index(x).min(self.segments.len() - 1);
+        self.eval_segment(i,x)
+    }
+}
+
+impl FloatClut {
+    /// eval interpolates the grid multilinear. Inputs are clamped to 0.0..=1.0, missing inputs are 0.0.
+    pub fn eval(&self,input:&[f32]) -> Vec<f32> {
+        let inputs = self.input_channels as usize;
+        let outputs = self.output_channels as usize;
+        let mut base = 0;
+        let mut strides = vec![0;inputs];
+        let mut fractions = vec![0.0_f32;inputs];
+        let mut stride = outputs;
+        for i in (0..inputs).rev() {
+            strides[i] = stride;
+            let grid = self.grid_points[i] as usize;
+            stride *= grid;
+            let pos = input.get(i).copied().unwrap_or(0.0).clamp(0.0,1.0) * (grid - 1) as f32;
+            let index = (pos.floor() as usize).min(grid.saturating_sub(2));
+            fractions[i] = pos - index as f32;
+            base += index * strides[i];
+        }
+        let mut output = vec![0.0_f32;outputs];
+        for corner in 0..1_usize << inputs {
+            let mut weight = 1.0;
+            let mut offset = base;
+            for i in 0..inputs {
+                if corner >> i & 1 == 1 {
+                    if self.grid_points[i] < 2 {
+                        weight = 0.0;
+                        break;
+                    }
+                    weight *= fractions[i];
+                    offset += strides[i];
+                } else {
+                    weight *= 1.0 - fractions[i];
+                }
+            }
+            if weight == 0.0 {
+                continue;
+            }
+            for (j,out) in output.iter_mut().enumerate() {
+                *out += weight * self.data[offset + j];
+            }
+        }
+        output
+    }
+}
+
+impl ProcessElement {
+    pub(crate) fn read(data:&[u8],options: &DecodeOptions) -> Result<Self> {
+        bound_check(data,0,12)?;
+        let signature = read_string(data,0,4);
+        let input_channels = read_u16_be(data,8);
+        let output_channels = read_u16_be(data,10);
+        let element = match &*signature {
+            "cvst" => {
+                if input_channels != output_channels {
+                    return Err(Error::new(ErrorKind::InvalidData,"curve set input and output channels differ"))
+                }
+                let counts = input_channels as usize;
+                bound_check(data,12,counts * 8)?;
+                let mut curves = Vec::with_capacity(counts);
+                for i in 0..counts {
+                    let offset = read_u32_be(data,12 + i * 8) as usize;
+                    let size = read_u32_be(data,16 + i * 8) as usize;
+                    bound_check(data,offset,size)?;
+                    let (_,curve) = SegmentedCurve::read(&data[offset..offset + size],options)?;
+                    curves.push(curve);
+                }
+                ProcessElement::CurveSet(curves)
+            },
+            "matf" => {
+                let counts = (input_channels as usize + 1) * output_channels as usize;
+                let matrix = read_f32_array(data,12,counts)?;
+                ProcessElement::Matrix(MatrixElement{input_channels,output_channels,matrix})
+            },
+            "clut" => {
+                if input_channels as usize > 16 {
+                    return Err(Error::new(ErrorKind::InvalidData,format!("clut input channels {}",input_channels)))
+                }
+                bound_check(data,12,16)?;
+                let grid_points = data[12..12 + input_channels as usize].to_vec();
+                let mut entries :usize = output_channels as usize;
+                for grid in &grid_points {
+                    entries = entries.checked_mul(*grid as usize)
+                        .ok_or_else(|| Error::new(ErrorKind::InvalidData,"clut size overflow"))?;
+                }
+                limit_check("clut entries",entries,options.max_clut_entries)?;
+                if entries == 0 {
+                    return Err(Error::new(ErrorKind::InvalidData,"clut has no entries"))
+                }
+                let data = read_f32_array(data,28,entries)?;
+                ProcessElement::Clut(FloatClut{input_channels,output_channels,grid_points,data})
+            },
+            "bACS" | "eACS" => {
+                bound_check(data,12,4)?;
+                let acs = read_u32_be(data,12);
+                if signature == "bACS" { ProcessElement::BeginAcs(acs) } else { ProcessElement::EndAcs(acs) }
+            },
+            _ => {
+                ProcessElement::Unknown {
+                    signature,
+                    input_channels,
+                    output_channels,
+                    data: data[12..].to_vec(),
+                }
+            }
+        };
+        Ok(element)
+    }
+
+    /// input_channels returns None for bACS/eACS, which pass any number of channels
+    pub fn input_channels(&self) -> Option<usize> {
+        match self {
+            ProcessElement::CurveSet(curves) => Some(curves.len()),
+            ProcessElement::Matrix(matrix) => Some(matrix.input_channels as usize),
+            ProcessElement::Clut(clut) => Some(clut.input_channels as usize),
+            ProcessElement::BeginAcs(_) | ProcessElement::EndAcs(_) => None,
+            ProcessElement::Unknown{input_channels,..} => Some(*input_channels as usize),
+        }
+    }
+
+    pub fn output_channels(&self) -> Option<usize> {
+        match self {
+            ProcessElement::CurveSet(curves) => Some(curves.len()),
+            ProcessElement::Matrix(matrix) => Some(matrix.output_channels as usize),
+            ProcessElement::Clut(clut) => Some(clut.output_channels as usize),
+            ProcessElement::BeginAcs(_) | ProcessElement::EndAcs(_) => None,
+            ProcessElement::Unknown{output_channels,..} => Some(*output_channels as usize),
+        }
+    }
+
+    pub fn eval(&self,input:&[f32]) -> Result<Vec<f32>> {
+        if let Some(channels) = self.input_channels() {
+            if input.len() != channels {
+                return Err(Error::new(ErrorKind::InvalidInput,format!("element needs {} channels, not {}",channels,input.len())))
+            }
+        }
+        match self {
+            ProcessElement::CurveSet(curves) => {
+                Ok(curves.iter().zip(input).map(|(curve,x)| curve.eval(*x)).collect())
+            },
+            ProcessElement::Matrix(matrix) => {
+                let inputs = matrix.input_channels as usize;
+                let outputs = matrix.output_channels as usize;
+                let offsets = &matrix.matrix[inputs * outputs..];
+                Ok((0..outputs).map(|j| {
+                    let row = &matrix.matrix[j * inputs..(j + 1) * inputs];
+                    row.iter().zip(input).map(|(e,x)| e * x).sum::<f32>() + offsets[j]
+                }).collect())
+            },
+            ProcessElement::Clut(clut) => Ok(clut.eval(input)),
+            ProcessElement::BeginAcs(_) | ProcessElement::EndAcs(_) => Ok(input.to_vec()),
+            ProcessElement::Unknown{signature,..} => {
+                Err(Error::new(ErrorKind::Unsupported,format!("process element '{}' is not supported",signature)))
+            }
+        }
+    }
+}
+
+impl MultiProcessElementsType {
+    /// read decodes the elements, `length` is the tag size
+    pub(crate) fn read(data:&[u8],length:usize,options: &DecodeOptions) -> Result<Self> {
+        bound_check(data,8,8)?;
+        let input_channels = read_u16_be(data,8);
+        let output_channels = read_u16_be(data,10);
+        let counts = read_u32_be(data,12) as usize;
+        let mut ptr = 16;
+        bound_check(data,ptr,counts.saturating_mul(8))?;
+        let mut process_element_positions = Vec::with_capacity(counts);
+        let mut elements = Vec::with_capacity(counts);
+        let mut channels = input_channels as usize;
+        for _ in 0..counts {
+            let offset = read_u32_be(data,ptr);
+            let size = read_u32_be(data,ptr + 4);
+            ptr += 8;
+            bound_check(data,offset as usize,size as usize)?;
+            let element = ProcessElement::read(&data[offset as usize..offset as usize + size as usize],options)?;
+            if let Some(inputs) = element.input_channels() {
+                if inputs != channels {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                        format!("process element needs {} channels, previous element has {}",inputs,channels)))
+                }
+            }
+            channels = element.output_channels().unwrap_or(channels);
+            process_element_positions.push(PositionNumber{offset,size});
+            elements.push(element);
+        }
+        if channels != output_channels as usize {
+            return Err(Error::new(ErrorKind::InvalidData,
+                format!("process elements output {} channels, tag has {}",channels,output_channels)))
+        }
+        #[allow(deprecated)]
+        Ok(Self {
+            input_channels,
+            output_channels,
+            process_element_positions,
+            elements,
+            data: data.get(ptr..length).unwrap_or(&[]).to_vec(),
+        })
+    }
+
+    /// eval runs all processing elements in order
+    pub fn eval(&self,input:&[f32]) -> Result<Vec<f32>> {
+        if input.len() != self.input_channels as usize {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("mpet needs {} channels, not {}",self.input_channels,input.len())))
+        }
+        let mut values = input.to_vec();
+        for element in &self.elements {
+            values = element.eval(&values)?;
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// element with a position table of (offset,size) relative to the element start
+    fn element(sig:&str,inputs:u16,outputs:u16,table:&[Vec<u8>],body:&[u8]) -> Vec<u8> {
+        let mut buf = sig.as_bytes().to_vec();
+        buf.extend_from_slice(&[0;4]);
+        buf.extend_from_slice(&inputs.to_be_bytes());
+        buf.extend_from_slice(&outputs.to_be_bytes());
+        let mut offset = 12 + table.len() * 8;
+        for item in table {
+            buf.extend_from_slice(&(offset as u32).to_be_bytes());
+            buf.extend_from_slice(&(item.len() as u32).to_be_bytes());
+            offset += item.len();
+        }
+        buf.extend_from_slice(body);
+        for item in table {
+            buf.extend_from_slice(item);
+        }
+        buf
+    }
+
+    #[test]
+    fn mpet_eval() {
+        let cvst = element("cvst",2,2,&[segmented_curve(),segmented_curve()],&[]);
+        // out0 = in0 + in1, out1 = in0 - in1 + 0.5
+        let matf = element("matf",2,2,&[],&floats(&[1.0,1.0,1.0,-1.0,0.0,0.5]));
+        let mut grid = vec![2,2];
+        grid.resize(16,0);
+        grid.extend_from_slice(&floats(&[0.0,0.0,0.0,1.0]));
+        let clut = element("clut",2,1,&[],&grid);
+        let bacs = element("bACS",2,2,&[],&[0,0,0,0]);
+        let mut body = vec![0,2,0,1,0,0,0,4];
+        let mut offset = 16 + 4 * 8;
+        for item in [&cvst,&bacs,&matf,&clut] {
+            body.extend_from_slice(&(offset as u32).to_be_bytes());
+            body.extend_from_slice(&(item.len() as u32).to_be_bytes());
+            offset += item.len();
+        }
+        for item in [&cvst,&bacs,&matf,&clut] {
+            body.extend_from_slice(item);
+        }
+        let data = profile(&[("D2B0",tag("mpet",&body))]);
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        let mpet = match decoded.tags.get("D2B0") {
+            Some(Data::MultiProcessElements(mpet)) => mpet,
+            other => panic!("{:?}",other),
+        };
+        assert_eq!(mpet.elements.len(),4);
+        let curve = match &mpet.elements[0] {
+            ProcessElement::CurveSet(curves) => &curves[0],
+            other => panic!("{:?}",other),
+        };
+        for (x,y) in [(-2.0,0.0),(0.0,0.0),(0.25,0.25),(0.5,0.5),(0.75,0.75),(1.0,1.0),(3.0,5.0)] {
+            assert!((curve.eval(x) - y).abs() < 1e-6,"{} {}",x,curve.eval(x));
+        }
+        assert_eq!(mpet.elements[2].eval(&[0.25,0.5]).unwrap(),vec![0.75,0.25]);
+        // curves -> (0.25,0.5), matrix -> (0.75,0.25), clut (x0 * x1 shape) -> 0.75 * 0.25
+        let out = mpet.eval(&[0.25,0.5]).unwrap();
+        assert!((out[0] - 0.1875).abs() < 1e-6,"{:?}",out);
+        assert!(mpet.eval(&[0.25]).is_err());
+        #[allow(deprecated)]
+        let raw = mpet.data.len();
+        assert_eq!(raw,cvst.len() + bacs.len() + matf.len() + clut.len());
+        // a short input takes the missing channel as 0.0
+        match &mpet.elements[3] {
+            ProcessElement::Clut(clut) => {
+                assert_eq!(clut.eval(&[1.0,1.0]),vec![1.0]);
+                assert_eq!(clut.eval(&[1.0]),vec![0.0]);
+            },
+            other => panic!("{:?}",other),
+        }
+    }
+
+    #[test]
+    fn segmented_curve_tags() {
+        let mut samf = vec![0,0,0,3];
+        samf.extend_from_slice(&floats(&[0.1,0.2,0.3]));
+        let data = profile(&[("tst1",segmented_curve()),("tst2",tag("samf",&samf))]);
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        let curve = match decoded.tags.get("tst1") {
+            Some(Data::SegmentedCurve(curve)) => curve,
+            other => panic!("{:?}",other),
+        };
+        assert_eq!(curve.break_points,vec![0.0,1.0]);
+        assert_eq!(curve.segments.len(),3);
+        for (x,y) in [(-1e6,0.0),(-0.5,0.0),(0.125,0.125),(0.5,0.5),(0.625,0.625),(1.5,2.0),(1e3,1999.0)] {
+            assert!((curve.eval(x) - y).abs() < 1e-3,"{} {}",x,curve.eval(x));
+        }
+        assert!(curve.eval(f32::NAN).is_nan());
+        assert!(matches!(decoded.tags.get("tst2"),Some(Data::SampledCurve(entries)) if entries.len() == 3));
+
+        let mut broken = segmented_curve();
+        broken[12..20].copy_from_slice(&floats(&[1.0,0.0]));
+        assert!(DecodedICCProfile::new(&profile(&[("tst1",broken)])).is_err());
+        let mut broken = segmented_curve();
+        broken.truncate(broken.len() - 4);
+        assert!(DecodedICCProfile::new(&profile(&[("tst1",broken)])).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Named color (spot color) lists
+//! NamedColorList reads the `ncl2` tag of named color profiles, looks colors up by name,
+//! searches the nearest color by ΔE, converts colors through an output profile
+//! and builds `nmcl` profiles from a swatch list.
+//! ```
+//! use icc_profile::named_color::NamedColorList;
+//! use icc_profile::cms::delta_e::DeltaE;
+//!
+//! fn spot_colors() -> std::io::Result<Vec<u8>> {
+//!     let mut list = NamedColorList::new("PANTONE "," C");
+//!     list.push("Red 032",[52.7,74.0,44.6],vec![0,0xd999,0xbfff,0])?
+//!         .push("Reflex Blue",[23.6,20.6,-64.7],vec![0xffff,0xbfff,0,0x1999])?;
+//!     let red = list.get("PANTONE Red 032 C").unwrap();
+//!     assert_eq!(red.device.len(),4);
+//!     let (nearest,_) = list.nearest([50.0,70.0,40.0],DeltaE::Ciede2000).unwrap();
+//!     assert_eq!(nearest.name,"Red 032");
+//!     list.to_profile(u32::from_be_bytes(*b"CMYK"),"spot colors","No copyright")
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::iccprofile::*;
+use crate::builder::ICCProfileBuilder;
+use crate::encoder::check_color_name;
+use crate::cms::delta_e::DeltaE;
+use crate::cms::pcs::{PcsEncoding,lab_to_xyz,xyz_to_lab};
+use crate::cms::transform::Transform;
+
+const LAB: u32 = 0x4c616220;    // 'Lab '
+const XYZ: u32 = 0x58595a20;    // 'XYZ '
+
+/// ncl2 stores PCS Lab in the legacy 16 bit encoding, L 0xFF00 = 100.0 and a/b 0x8000 = 0.0
+fn decode_lab(pcs:[u16;3]) -> [f64;3] {
+    PcsEncoding::Lab16Legacy.decode_u16(pcs)
+}
+
+fn encode_lab(lab:[f64;3]) -> [u16;3] {
+    PcsEncoding::Lab16Legacy.encode_u16(lab)
+}
+
+/// PCS XYZ is u1Fixed15, 0x8000 = 1.0
+fn decode_xyz(pcs:[u16;3]) -> [f64;3] {
+    PcsEncoding::Xyz16.decode_u16(pcs)
+}
+
+#[derive(Debug,Clone)]
+pub struct NamedColor {
+    /// root name without the prefix and the suffix
+    pub name: String,
+    /// D50 relative PCS L*a*b*
+    pub lab: [f64;3],
+    /// D50 relative PCS XYZ
+    pub xyz: [f64;3],
+    /// device coordinates, 0x0000-0xFFFF
+    pub device: Vec<u16>,
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct NamedColorList {
+    pub vendor_specific_flag: u32,
+    pub prefix: String,
+    pub suffix: String,
+    pub colors: Vec<NamedColor>,
+}
+
+impl NamedColorList {
+    pub fn new(prefix:&str,suffix:&str) -> Self {
+        Self {
+            vendor_specific_flag: 0,
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            colors: vec![],
+        }
+    }
+
+    /// from_profile reads the ncl2 tag, the PCS values are decoded by the header PCS
+    pub fn from_profile(decoded:&DecodedICCProfile) -> Result<Self> {
+        match decoded.tags.get("ncl2") {
+            Some(Data::NamedColor(named_color)) => Self::from_data(named_color,decoded.pcs),
+            _ => Err(Error::new(ErrorKind::InvalidData,"profile has no ncl2 tag")),
+        }
+    }
+
+    /// from_data converts ncl2 tag data, `pcs` is 'Lab ' or 'XYZ '
+    pub fn from_data(named_color:&NamedColor2Type,pcs:u32) -> Result<Self> {
+        if pcs != LAB && pcs != XYZ {
+            return Err(Error::new(ErrorKind::InvalidData,format!("unknown PCS {:08x}",pcs)))
+        }
+        let colors = named_color.entries.iter().map(|entry| {
+            let (lab,xyz) = if pcs == LAB {
+                let lab = decode_lab(entry.pcs);
+                (lab,lab_to_xyz(lab))
+            } else {
+                let xyz = decode_xyz(entry.pcs);
+                (xyz_to_lab(xyz),xyz)
+            };
+            NamedColor {
+                name: entry.root_color_name.to_string(),
+                lab,
+                xyz,
+                device: entry.coordinate.clone(),
+            }
+        }).collect();
+        Ok(Self {
+            vendor_specific_flag: named_color.vendor_specific_flag,
+            prefix: named_color.prefix_color_name.to_string(),
+            suffix: named_color.suffix_color_name.to_string(),
+            colors,
+        })
+    }
+
+    /// push adds a color by its root name and PCS L*a*b*, the name is ASCII up to 31 bytes
+    pub fn push(&mut self,name:&str,lab:[f64;3],device:Vec<u16>) -> Result<&mut Self> {
+        check_color_name(name)?;
+        if let Some(color) = self.colors.first() {
+            if color.device.len() != device.len() {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                    format!("{} has {} device coordinates, expected {}",name,device.len(),color.device.len())))
+            }
+        }
+        self.colors.push(NamedColor {
+            name: name.to_string(),
+            lab,
+            xyz: lab_to_xyz(lab),
+            device,
+        });
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// full_name is prefix + root name + suffix
+    pub fn full_name(&self,color:&NamedColor) -> String {
+        format!("{}{}{}",self.prefix,color.name,self.suffix)
+    }
+
+    /// get looks a color up by its full name
+    pub fn get(&self,name:&str) -> Option<&NamedColor> {
+        self.colors.iter().find(|color| self.full_name(color) == name)
+    }
+
+    /// nearest returns the closest color to `lab` and its ΔE
+    pub fn nearest(&self,lab:[f64;3],delta_e:DeltaE) -> Option<(&NamedColor,f64)> {
+        self.colors.iter()
+            .map(|color| (color,delta_e.compute(lab,color.lab)))
+            .min_by(|a,b| a.1.total_cmp(&b.1))
+    }
+
+    /// convert maps every color from the PCS to an output profile for `rendering_intent` (0-3)
+    /// with its B2A tag (lut8, lut16, mBA) or the inverse of its matrix/TRC.
+    /// The result is normalized device values 0.0-1.0 in list order.
+    pub fn convert(&self,output:&DecodedICCProfile,rendering_intent:u32) -> Result<Vec<Vec<f64>>> {
+        let transform = Transform::from_pcs(output,rendering_intent)?;
+        Ok(self.colors.iter().map(|color| transform.transform(&color.xyz)).collect())
+    }
+
+    /// to_data returns the ncl2 tag data with Lab PCS values
+    pub fn to_data(&self) -> Data {
+        let entries = self.colors.iter().map(|color| ColorNameWithData {
+            root_color_name: color.name.to_string(),
+            pcs: encode_lab(color.lab),
+            coordinate: color.device.clone(),
+        }).collect();
+        Data::NamedColor(NamedColor2Type {
+            vendor_specific_flag: self.vendor_specific_flag,
+            prefix_color_name: self.prefix.to_string(),
+            suffix_color_name: self.suffix.to_string(),
+            entries,
+        })
+    }
+
+    /// to_profile builds a version 4 named color profile with a Lab PCS
+    pub fn to_profile(&self,color_space:u32,description:&str,copyright:&str) -> Result<Vec<u8>> {
+        let mut builder = ICCProfileBuilder::new(u32::from_be_bytes(*b"nmcl"),color_space,LAB);
+        builder.tag("desc",&Data::ASCII(description.to_string()),"mluc")?
+            .tag("cprt",&Data::ASCII(copyright.to_string()),"mluc")?
+            .tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642,1.0,0.8249)),"XYZ ")?
+            .tag("ncl2",&self.to_data(),"ncl2")?;
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn named_colors() {
+        use crate::cms::delta_e::delta_e2000;
+        // Sharma et al. CIEDE2000 test data, pair 1
+        assert!((delta_e2000([50.0,2.6772,-79.7751],[50.0,0.0,-82.7485]) - 2.0425).abs() < 1e-4);
+
+        let mut list = NamedColorList::new("SPOT "," C");
+        list.push("Red",[50.0,70.0,40.0],vec![0x0000,0xffff,0xc000]).unwrap()
+            .push("Gray",[60.0,0.0,0.0],vec![0x8000,0x8000,0x8000]).unwrap();
+        assert!(list.push("Bad",[0.0,0.0,0.0],vec![0]).is_err());
+        assert!(list.push("a name that is longer than 31 bytes",[0.0,0.0,0.0],vec![0,0,0]).is_err());
+        assert!(list.push("Gr\u{fc}n",[0.0,0.0,0.0],vec![0,0,0]).is_err());
+        assert_eq!(list.len(),2);
+        let mut long = list.colors[0].clone();
+        long.name = "a name that is longer than 31 bytes".to_string();
+        list.colors.push(long);
+        let data = list.to_profile(u32::from_be_bytes(*b"RGB "),"spot","none");
+        assert!(data.is_err());
+        list.colors.pop();
+
+        let data = list.to_profile(u32::from_be_bytes(*b"RGB "),"spot","none").unwrap();
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        let read = NamedColorList::from_profile(&decoded).unwrap();
+        assert_eq!(read.len(),2);
+        let red = read.get("SPOT Red C").unwrap();
+        assert_eq!(red.device,vec![0x0000,0xffff,0xc000]);
+        for i in 0..3 {
+            assert!((red.lab[i] - [50.0,70.0,40.0][i]).abs() < 0.01,"{:?}",red.lab);
+        }
+        assert!(read.get("Red").is_none());
+        let (gray,delta) = read.nearest([58.0,1.0,-1.0],DeltaE::Cie76).unwrap();
+        assert_eq!(gray.name,"Gray");
+        assert!((delta - 6.0_f64.sqrt()).abs() < 0.01);
+
+        // lut16 that outputs the PCS L channel
+        let mft2 = lut16_fixture(3,1,2,&|p| vec![(p[0] * 65535.0) as u16]);
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"prtr"),u32::from_be_bytes(*b"GRAY"),u32::from_be_bytes(*b"Lab "));
+        builder.raw_tag("B2A0",tag("mft2",&mft2)).unwrap();
+        let output = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        let devices = read.convert(&output,1).unwrap();
+        assert!((devices[0][0] - 50.0 * 652.8 / 65535.0).abs() < 1e-3,"{:?}",devices);
+        assert!((devices[1][0] - 60.0 * 652.8 / 65535.0).abs() < 1e-3,"{:?}",devices);
+        // matrix/TRC output
+        let srgb = crate::CicpType::new(1,13,0,true).to_profile("sRGB","none").unwrap();
+        let devices = read.convert(&DecodedICCProfile::new(&srgb).unwrap(),1).unwrap();
+        assert!(devices[0][0] > 0.8 && devices[0][1] < 0.2,"{:?}",devices);
+        assert!((devices[1][0] - devices[1][2]).abs() < 1e-3 && (devices[1][0] - 0.57).abs() < 0.02,"{:?}",devices);
+    }
+}
@@ -0,0 +1,150 @@
+//! Borrowed ICC profile
+//! IccProfileRef reads the header and the tag table from a `&[u8]` without copying.
+//! Tags are decoded on demand and cached.
+//! ```
+//! use icc_profile::IccProfileRef;
+//!
+//! fn description(data:&[u8]) -> std::io::Result<Option<String>> {
+//!     let profile = IccProfileRef::new(data)?;
+//!     profile.description()
+//! }
+//! ```
+
+use std::cell::OnceCell;
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::utils::{bound_check,limit_check,read_u128};
+
+#[derive(Debug)]
+pub struct IccProfileRef<'a> {
+    data: &'a [u8],
+    options: DecodeOptions,
+    directory: TagDirectory,
+    cache: Vec<OnceCell<std::result::Result<Data,String>>>,
+}
+
+impl<'a> IccProfileRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        Self::new_with_options(data,&DecodeOptions::default())
+    }
+
+    /// new_with_options checks the header and the tag table. Tag data is not read.
+    pub fn new_with_options(data: &'a [u8],options: &DecodeOptions) -> Result<Self> {
+        limit_check("file size",data.len(),options.max_file_size)?;
+        if data.len() < 132 {
+            return Err(Error::new(ErrorKind::InvalidData,"ICCProfile data shotage"))
+        }
+        let count = read_u32_be(data,128) as usize;
+        limit_check("tag count",count,options.max_tag_count)?;
+        let directory = TagDirectory::read(data,count)?;
+        let cache = (0..count).map(|_| OnceCell::new()).collect();
+        Ok(Self {
+            data,
+            options: options.clone(),
+            directory,
+            cache,
+        })
+    }
+
+    /// the borrowed profile data
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn length(&self) -> u32 { read_u32_be(self.data,0) }
+    pub fn cmmid(&self) -> u32 { read_u32_be(self.data,4) }
+    pub fn version(&self) -> u32 { read_u32_be(self.data,8) }
+    pub fn device_class(&self) -> u32 { read_u32_be(self.data,12) }
+    pub fn color_space(&self) -> u32 { read_u32_be(self.data,16) }
+    pub fn pcs(&self) -> u32 { read_u32_be(self.data,20) }
+    pub fn magicnumber_ascp(&self) -> u32 { read_u32_be(self.data,36) }
+    pub fn platform(&self) -> u32 { read_u32_be(self.data,40) }
+    pub fn flags(&self) -> u32 { read_u32_be(self.data,44) }
+    pub fn manufacturer(&self) -> u32 { read_u32_be(self.data,48) }
+    pub fn model(&self) -> u32 { read_u32_be(self.data,52) }
+    pub fn attributes(&self) -> u64 { read_u64_be(self.data,56) }
+    pub fn rendering_intent(&self) -> u32 { read_u32_be(self.data,64) }
+    pub fn creator(&self) -> u32 { read_u32_be(self.data,80) }
+    pub fn profile_id(&self) -> u128 { read_u128(self.data,84) }
+
+    pub fn illuminate(&self) -> XYZNumber {
+        // the header is at least 128 bytes
+        Data::xyz_number(self.data,68).unwrap()
+    }
+
+    pub fn create_date(&self) -> String {
+        let d :Vec<u16> = (0..6).map(|i| read_u16_be(self.data,24 + i * 2)).collect();
+        format!("{:>4}/{:>2}/{:>2} {:>02}:{:>02}:{:>02}",d[0],d[1],d[2],d[3],d[4],d[5])
+    }
+
+    /// tag table in file order
+    pub fn directory(&self) -> &TagDirectory {
+        &self.directory
+    }
+
+    /// tag signatures in the tag table order
+    pub fn tag_names(&self) -> Vec<String> {
+        self.directory.iter().map(|entry| entry.signature.to_string()).collect()
+    }
+
+    pub fn has_tag(&self,name:&str) -> bool {
+        self.position(name).is_some()
+    }
+
+    fn position(&self,name:&str) -> Option<usize> {
+        self.directory.iter().position(|entry| entry.signature == name)
+    }
+
+    /// raw tag data including the type signature, borrowed from the profile
+    pub fn raw_tag(&self,name:&str) -> Option<&'a [u8]> {
+        self.directory.raw(self.data,name)
+    }
+
+    /// tag decodes a tag on first access. Ok(None) means the tag does not exist.
+    pub fn tag(&self,name:&str) -> Result<Option<&Data>> {
+        let i = match self.position(name) {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        let decoded = self.cache[i].get_or_init(|| {
+            let entry = &self.directory.entries[i];
+            let (offset,size) = (entry.offset as usize,entry.size as usize);
+            bound_check(self.data,offset,size)
+                .and_then(|_| Data::parse_with_options(&self.data[offset..],size,self.version(),&self.options))
+                .map(|(_,data)| data)
+                .map_err(|err| err.to_string())
+        });
+        match decoded {
+            Ok(data) => Ok(Some(data)),
+            Err(message) => Err(Error::new(ErrorKind::InvalidData,format!("{} {}",name,message))),
+        }
+    }
+
+    /// description returns the 'desc' text
+    pub fn description(&self) -> Result<Option<String>> {
+        let description = match self.tag("desc")? {
+            Some(Data::ASCII(string)) => Some(string.to_string()),
+            Some(Data::Descriptor(descriptor)) => Some(descriptor.ascii_string.to_string()),
+            Some(Data::MultiLocalizedUnicode(mluc)) => Some(mluc.as_string()),
+            _ => None,
+        };
+        Ok(description)
+    }
+
+    /// compute_profile_id calculates the profile ID (MD5) of the profile data
+    pub fn compute_profile_id(&self) -> Result<u128> {
+        compute_profile_id(self.data)
+    }
+
+    /// verify_profile_id returns None when no ID is stored, otherwise whether the stored ID is correct
+    pub fn verify_profile_id(&self) -> Result<Option<bool>> {
+        verify_profile_id(self.data)
+    }
+
+    /// to_decoded decodes all tags
+    pub fn to_decoded(&self) -> Result<DecodedICCProfile> {
+        icc_profile_decode_with_options(self.data,&self.options)
+    }
+}
@@ -0,0 +1,336 @@
+//! Profile sequence description (pseq) and profile sequence identifier (psid)
+//! Device links record the profiles they were made from.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn link(source:&DecodedICCProfile,destination:&DecodedICCProfile) -> std::io::Result<ICCProfileBuilder> {
+//!     let mut builder = ICCProfileBuilder::new(
+//!         u32::from_be_bytes(*b"link"),source.color_space,destination.color_space);
+//!     builder.profile_sequence(&[source,destination])?;
+//!     Ok(builder)
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::builder::ICCProfileBuilder;
+use crate::utils::{bound_check,read_u128};
+
+/// embedded text of pseq and psid
+#[derive(Debug,Clone)]
+pub enum TextDescription {
+    /// textDescriptionType 'desc' of v2 profiles
+    Text(Descriptor),
+    /// multiLocalizedUnicodeType 'mluc' of v4 profiles
+    Localized(MultiLocalizedUnicodeType),
+}
+
+impl TextDescription {
+    pub fn as_string(&self) -> String {
+        match self {
+            TextDescription::Text(descriptor) => descriptor.ascii_string.to_string(),
+            TextDescription::Localized(mluc) => mluc.as_string(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        TextDescription::Text(Descriptor {
+            ascii_string: String::new(),
+            lang: String::new(),
+            local_string: String::new(),
+        })
+    }
+
+    /// from_data takes text tag data, 'desc', 'text' or 'mluc'
+    pub fn from_data(data:&Data) -> Option<Self> {
+        match data {
+            Data::ASCII(string) => Some(TextDescription::Text(Descriptor {
+                ascii_string: string.to_string(),
+                lang: String::new(),
+                local_string: String::new(),
+            })),
+            Data::Descriptor(descriptor) => Some(TextDescription::Text(descriptor.clone())),
+            Data::MultiLocalizedUnicode(mluc) => Some(TextDescription::Localized(mluc.clone())),
+            _ => Option::None,
+        }
+    }
+
+    /// to_localized converts 'desc' text to 'mluc', empty text has no records
+    pub fn to_localized(&self) -> MultiLocalizedUnicodeType {
+        match self {
+            TextDescription::Localized(mluc) => mluc.clone(),
+            TextDescription::Text(descriptor) if descriptor.ascii_string.is_empty() => MultiLocalizedUnicodeType {
+                unicode_strings: vec![],
+            },
+            TextDescription::Text(descriptor) => MultiLocalizedUnicodeType::from(descriptor.ascii_string.to_string()),
+        }
+    }
+
+    /// encode writes the embedded tag including the type signature
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            TextDescription::Text(descriptor) => Data::Descriptor(descriptor.clone()).encode("desc"),
+            TextDescription::Localized(mluc) => Data::MultiLocalizedUnicode(mluc.clone()).encode("mluc"),
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct ProfileDescription {
+    pub device_manufacturer:u32,
+    pub device_model:u32,
+    pub device_attributes:u64,
+    pub technology_information:u32,
+    pub description_device_manufacturer: TextDescription,
+    pub description_device_model: TextDescription,
+}
+
+impl ProfileDescription {
+    /// from_profile takes the header fields and the tech, dmnd and dmdd tags
+    pub fn from_profile(decoded:&DecodedICCProfile) -> Self {
+        let text = |tag:&str| decoded.tags.get(tag)
+            .and_then(TextDescription::from_data)
+            .unwrap_or_else(TextDescription::empty);
+        let technology_information = match decoded.tags.get("tech") {
+            Some(Data::ASCII(signature)) => {
+                let mut bytes = [b' ';4];
+                for (i,b) in signature.bytes().take(4).enumerate() { bytes[i] = b; }
+                u32::from_be_bytes(bytes)
+            },
+            _ => 0,
+        };
+        Self {
+            device_manufacturer: decoded.manufacturer,
+            device_model: decoded.model,
+            device_attributes: decoded.attributes,
+            technology_information,
+            description_device_manufacturer: text("dmnd"),
+            description_device_model: text("dmdd"),
+        }
+    }
+}
+
+/// profileSequenceDescType 'pseq'
+#[derive(Debug,Clone,Default)]
+pub struct ProfileSequenceDescription {
+    pub profiles: Vec<ProfileDescription>,
+}
+
+impl ProfileSequenceDescription {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self,profile:ProfileDescription) -> &mut Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    /// from_profiles describes profiles in the order they are applied
+    pub fn from_profiles(profiles:&[&DecodedICCProfile]) -> Self {
+        Self {
+            profiles: profiles.iter().map(|decoded| ProfileDescription::from_profile(decoded)).collect(),
+        }
+    }
+
+    /// read reads tag data starting with the type signature
+    pub(crate) fn read(data:&[u8],version:u32) -> Result<Self> {
+        let mut ptr = 8;
+        bound_check(data,ptr,4)?;
+        let counts = read_u32_be(data,ptr) as usize;
+        ptr += 4;
+        let mut profiles = vec![];
+        for _ in 0..counts {
+            bound_check(data,ptr,20)?;
+            let device_manufacturer = read_u32_be(data,ptr);
+            let device_model = read_u32_be(data,ptr + 4);
+            let device_attributes = read_u64_be(data,ptr + 8);
+            let technology_information = read_u32_be(data,ptr + 16);
+            ptr += 20;
+            let (len,description_device_manufacturer) = Data::read_embedded_text(&data[ptr..],version)?;
+            ptr += len;
+            let (len,description_device_model) = Data::read_embedded_text(&data[ptr..],version)?;
+            ptr += len;
+            profiles.push(ProfileDescription {
+                device_manufacturer,
+                device_model,
+                device_attributes,
+                technology_information,
+                description_device_manufacturer,
+                description_device_model,
+            });
+        }
+        Ok(Self { profiles })
+    }
+
+    /// write appends the tag body after the type signature
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) -> Result<()> {
+        write_u32_be(self.profiles.len() as u32,buf);
+        for profile in &self.profiles {
+            write_u32_be(profile.device_manufacturer,buf);
+            write_u32_be(profile.device_model,buf);
+            write_u64_be(profile.device_attributes,buf);
+            write_u32_be(profile.technology_information,buf);
+            buf.extend_from_slice(&profile.description_device_manufacturer.encode()?);
+            buf.extend_from_slice(&profile.description_device_model.encode()?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct ProfileIdentifier {
+    /// MD5 profile ID, 0 when the profile has none
+    pub profile_id: u128,
+    pub description: MultiLocalizedUnicodeType,
+}
+
+impl ProfileIdentifier {
+    /// from_profile takes the profile ID and the desc tag
+    pub fn from_profile(decoded:&DecodedICCProfile) -> Self {
+        let description = decoded.tags.get("desc")
+            .and_then(TextDescription::from_data)
+            .unwrap_or_else(TextDescription::empty)
+            .to_localized();
+        Self {
+            profile_id: decoded.profile_id,
+            description,
+        }
+    }
+}
+
+/// profileSequenceIdentifierType 'psid'
+#[derive(Debug,Clone,Default)]
+pub struct ProfileSequenceIdentifier {
+    pub profiles: Vec<ProfileIdentifier>,
+}
+
+impl ProfileSequenceIdentifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self,profile:ProfileIdentifier) -> &mut Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    pub fn from_profiles(profiles:&[&DecodedICCProfile]) -> Self {
+        Self {
+            profiles: profiles.iter().map(|decoded| ProfileIdentifier::from_profile(decoded)).collect(),
+        }
+    }
+
+    /// read reads tag data starting with the type signature, positions are relative to the tag
+    pub(crate) fn read(data:&[u8],version:u32) -> Result<Self> {
+        bound_check(data,8,4)?;
+        let counts = read_u32_be(data,8) as usize;
+        bound_check(data,12,counts.saturating_mul(8))?;
+        let mut profiles = Vec::with_capacity(counts);
+        for i in 0..counts {
+            let offset = read_u32_be(data,12 + i * 8) as usize;
+            let size = read_u32_be(data,16 + i * 8) as usize;
+            bound_check(data,offset,size)?;
+            if size < 16 + 8 {
+                return Err(Error::new(ErrorKind::InvalidData,format!("psid entry size {} is too short",size)))
+            }
+            let entry = &data[offset..offset + size];
+            let profile_id = read_u128(entry,0);
+            let description = match Data::read_embedded_text(&entry[16..],version)?.1 {
+                TextDescription::Localized(mluc) => mluc,
+                TextDescription::Text(_) => return Err(Error::new(ErrorKind::InvalidData,"psid description must be mluc")),
+            };
+            profiles.push(ProfileIdentifier { profile_id,description });
+        }
+        Ok(Self { profiles })
+    }
+
+    /// write appends the tag body to `buf` that starts with the type signature, entries are 4 byte aligned
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) -> Result<()> {
+        let mut entries = vec![];
+        for profile in &self.profiles {
+            let mut entry = profile.profile_id.to_be_bytes().to_vec();
+            entry.extend_from_slice(&Data::MultiLocalizedUnicode(profile.description.clone()).encode("mluc")?);
+            entries.push(entry);
+        }
+        write_u32_be(entries.len() as u32,buf);
+        let mut offset = buf.len() + entries.len() * 8;
+        for entry in &entries {
+            offset = (offset + 3) & !3;
+            write_u32_be(offset as u32,buf);
+            write_u32_be(entry.len() as u32,buf);
+            offset += entry.len();
+        }
+        for entry in &entries {
+            buf.resize((buf.len() + 3) & !3,0);
+            buf.extend_from_slice(entry);
+        }
+        Ok(())
+    }
+}
+
+impl ICCProfileBuilder {
+    /// profile_sequence stores the pseq tag and, for version 4 profiles, the psid tag.
+    /// Texts are converted to mluc in version 4 profiles.
+    pub fn profile_sequence(&mut self,profiles:&[&DecodedICCProfile]) -> Result<&mut Self> {
+        let mut sequence = ProfileSequenceDescription::from_profiles(profiles);
+        if self.is_v4() {
+            for profile in &mut sequence.profiles {
+                profile.description_device_manufacturer = TextDescription::Localized(profile.description_device_manufacturer.to_localized());
+                profile.description_device_model = TextDescription::Localized(profile.description_device_model.to_localized());
+            }
+            let identifier = ProfileSequenceIdentifier::from_profiles(profiles);
+            self.tag("psid",&Data::ProfileSequenceId(identifier),"psid")?;
+        }
+        self.tag("pseq",&Data::ProfileSequence(sequence),"pseq")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn profile_sequence() {
+        let decoded = DecodedICCProfile::new(&sample()).unwrap();
+        let sequence = match decoded.tags.get("pseq") {
+            Some(Data::ProfileSequence(sequence)) => sequence,
+            other => panic!("{:?}",other),
+        };
+        assert!(matches!(&sequence.profiles[0].description_device_manufacturer,TextDescription::Text(desc) if desc.ascii_string == "A"));
+        assert!(matches!(&sequence.profiles[0].description_device_model,TextDescription::Localized(mluc) if mluc.as_string() == "OK"));
+        let encoded = Data::ProfileSequence(sequence.clone()).encode("pseq").unwrap();
+        let read = ProfileSequenceDescription::read(&encoded,decoded.version).unwrap();
+        assert_eq!(read.profiles[0].description_device_model.as_string(),"OK");
+
+        let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"mntr"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"XYZ "));
+        builder.manufacturer(u32::from_be_bytes(*b"ACME")).attributes(1)
+            .tag("desc",&Data::ASCII("display".to_string()),"mluc").unwrap()
+            .tag("dmdd",&Data::ASCII("monitor 27".to_string()),"mluc").unwrap()
+            .tag("tech",&Data::ASCII("CRT".to_string()),"sig ").unwrap();
+        let display = DecodedICCProfile::new(&builder.build().unwrap()).unwrap();
+        let mut link = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"link"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"RGB "));
+        link.profile_sequence(&[&display,&decoded]).unwrap();
+        let link = DecodedICCProfile::new(&link.build().unwrap()).unwrap();
+        let sequence = match link.tags.get("pseq") {
+            Some(Data::ProfileSequence(sequence)) => sequence,
+            other => panic!("{:?}",other),
+        };
+        assert_eq!(sequence.profiles.len(),2);
+        assert_eq!(sequence.profiles[0].device_manufacturer,u32::from_be_bytes(*b"ACME"));
+        assert_eq!(sequence.profiles[0].device_attributes,1);
+        assert_eq!(sequence.profiles[0].technology_information,u32::from_be_bytes(*b"CRT "));
+        assert!(matches!(&sequence.profiles[0].description_device_model,TextDescription::Localized(mluc) if mluc.as_string() == "monitor 27"));
+        let identifier = match link.tags.get("psid") {
+            Some(Data::ProfileSequenceId(identifier)) => identifier,
+            other => panic!("{:?}",other),
+        };
+        assert_eq!(identifier.profiles[0].profile_id,display.profile_id);
+        assert_ne!(display.profile_id,0);
+        assert_eq!(identifier.profiles[0].description.as_string(),"display");
+        assert_eq!(identifier.profiles[1].description.as_string(),"test");
+    }
+}
@@ -0,0 +1,162 @@
+//! Reader based ICC profile loading
+//! IccProfileReader reads the header and the tag table from a `Read + Seek` source.
+//! Tag data is read only when it is requested.
+//! ```
+//! use icc_profile::IccProfileReader;
+//!
+//! fn white_point(file:std::fs::File) -> std::io::Result<Option<icc_profile::Data>> {
+//!     let mut reader = IccProfileReader::new(file)?;
+//!     reader.tag("wtpt")
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::{Read,Seek,SeekFrom};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::utils::limit_check;
+
+#[derive(Debug)]
+pub struct IccProfileReader<R> {
+    reader: R,
+    options: DecodeOptions,
+    start: u64,
+    header: ICCProfile,
+    directory: TagDirectory,
+}
+
+impl<R:Read + Seek> IccProfileReader<R> {
+    pub fn new(reader:R) -> Result<Self> {
+        Self::new_with_options(reader,&DecodeOptions::default())
+    }
+
+    /// new_with_options reads the header and the tag table from the current stream position.
+    pub fn new_with_options(mut reader:R,options: &DecodeOptions) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let mut head = vec![0;132];
+        reader.read_exact(&mut head)?;
+        let count = read_u32_be(&head,128) as usize;
+        limit_check("tag count",count,options.max_tag_count)?;
+        let table = count.checked_mul(12)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData,"tag count overflow"))?;
+        limit_check("tag table size",table,options.max_file_size)?;
+        // the tag table must fit the declared profile size before anything is allocated
+        let length = read_u32_be(&head,0) as usize;
+        limit_check("file size",length,options.max_file_size)?;
+        if 132 + table > length {
+            return Err(Error::new(ErrorKind::InvalidData,format!("tag count {} is over the profile size {}",count,length)))
+        }
+        head.resize(132 + table,0);
+        reader.read_exact(&mut head[132..])?;
+        let header = ICCProfile::new(&head)?;
+        let mut directory = TagDirectory::read(&head,count)?;
+        // type signatures are left empty; the tag data is not read yet
+        for entry in directory.entries.iter_mut() {
+            entry.type_signature.clear();
+        }
+        Ok(Self {
+            reader,
+            options: options.clone(),
+            start,
+            header,
+            directory,
+        })
+    }
+
+    /// the profile header. `data` holds only the header and the tag table.
+    pub fn header(&self) -> &ICCProfile {
+        &self.header
+    }
+
+    /// tag table in file order
+    pub fn directory(&self) -> &TagDirectory {
+        &self.directory
+    }
+
+    pub fn has_tag(&self,name:&str) -> bool {
+        self.directory.get(name).is_some()
+    }
+
+    /// raw_tag reads raw tag data including the type signature. Ok(None) means the tag does not exist.
+    pub fn raw_tag(&mut self,name:&str) -> Result<Option<Vec<u8>>> {
+        let (offset,size) = match self.directory.get(name) {
+            Some(entry) => (entry.offset as u64,entry.size as usize),
+            None => return Ok(None),
+        };
+        limit_check("tag size",size,self.options.max_file_size)?;
+        if offset + size as u64 > self.header.length as u64 {
+            return Err(Error::new(ErrorKind::InvalidData,format!("tag {} is out of the profile size {}",name,self.header.length)))
+        }
+        self.reader.seek(SeekFrom::Start(self.start + offset))?;
+        let mut buf = vec![0;size];
+        self.reader.read_exact(&mut buf)?;
+        Ok(Some(buf))
+    }
+
+    /// tag reads and decodes one tag. Ok(None) means the tag does not exist.
+    pub fn tag(&mut self,name:&str) -> Result<Option<Data>> {
+        let buf = match self.raw_tag(name)? {
+            Some(buf) => buf,
+            None => return Ok(None),
+        };
+        let (_,data) = Data::parse_with_options(&buf,buf.len(),self.header.version,&self.options)?;
+        Ok(Some(data))
+    }
+
+    /// to_decoded reads the whole profile and decodes all tags
+    pub fn to_decoded(&mut self) -> Result<DecodedICCProfile> {
+        self.reader.seek(SeekFrom::Start(self.start))?;
+        let mut buf = vec![0;self.header.length as usize];
+        self.reader.read_exact(&mut buf)?;
+        icc_profile_decode_with_options(&buf,&self.options)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn read_from_reader() {
+        use std::io::{Cursor,Seek};
+        let base = sample();
+        let decoded = DecodedICCProfile::from_reader(Cursor::new(&base)).unwrap();
+        assert_eq!(decoded.directory.len(),7);
+        let options = DecodeOptions { max_file_size: 256, ..DecodeOptions::default() };
+        assert!(DecodedICCProfile::from_reader_with_options(Cursor::new(&base),&options).is_err());
+
+        let mut reader = crate::IccProfileReader::new(Cursor::new(&base)).unwrap();
+        assert_eq!(reader.header().version,0x02100000);
+        assert!(reader.has_tag("rTRC"));
+        assert!(reader.tag("bTRC").unwrap().is_none());
+        let mut cursor = reader.into_inner();
+        assert_eq!(cursor.stream_position().unwrap(),132 + 7 * 12);
+        cursor.rewind().unwrap();
+        let mut reader = crate::IccProfileReader::new(cursor).unwrap();
+        assert!(matches!(reader.tag("rTRC").unwrap(),Some(Data::Curve(_))));
+        assert_eq!(reader.raw_tag("wtpt").unwrap().as_deref(),decoded.directory.raw(&base,"wtpt"));
+        assert_eq!(reader.to_decoded().unwrap().tags.len(),decoded.tags.len());
+    }
+
+    #[test]
+    fn reject_out_of_profile_sizes() {
+        use std::io::Cursor;
+        let mut base = sample();
+        base[128..132].copy_from_slice(&0x0fff_ffff_u32.to_be_bytes());
+        let err = crate::IccProfileReader::new(Cursor::new(&base)).unwrap_err();
+        assert_eq!(err.kind(),std::io::ErrorKind::InvalidData);
+
+        // wtpt claims 2GB
+        let mut base = sample();
+        base[132 + 20..132 + 24].copy_from_slice(&0x7fff_ffff_u32.to_be_bytes());
+        let mut reader = crate::IccProfileReader::new(Cursor::new(&base)).unwrap();
+        assert_eq!(reader.raw_tag("wtpt").unwrap_err().kind(),std::io::ErrorKind::InvalidData);
+        assert!(reader.raw_tag("rTRC").unwrap().is_some());
+    }
+}
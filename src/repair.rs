@@ -0,0 +1,256 @@
+//! Automatic repair of common profile defects
+//! repair rewrites a profile and reports every change it made.
+//! ```
+//! use icc_profile::repair::repair;
+//!
+//! fn clean(data:&[u8]) -> std::io::Result<Vec<u8>> {
+//!     let (repaired,repairs) = repair(data)?;
+//!     for repair in &repairs {
+//!         eprintln!("{}",repair);
+//!     }
+//!     Ok(repaired)
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::builder::ICCProfileBuilder;
+use crate::cms::ColorMatrix3D;
+use crate::validator::{Check,allowed_types,is_d50};
+
+const D50: (f64,f64,f64) = (0.9642,1.0,0.8249);
+
+#[derive(Debug,Clone)]
+pub struct Repair {
+    /// the validator check the defect belongs to
+    pub check: Check,
+    pub tag: Option<String>,
+    pub message: String,
+}
+
+impl Repair {
+    fn new(check:Check,tag:Option<&str>,message:String) -> Self {
+        Self {
+            check,
+            tag: tag.map(|tag| tag.to_string()),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self,f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tag {
+            Some(tag) => write!(f,"{:?} {}: {}",self.check,tag,self.message),
+            None => write!(f,"{:?}: {}",self.check,self.message),
+        }
+    }
+}
+
+fn read_xyz(raw:&[u8]) -> Option<[i32;3]> {
+    if raw.len() < 20 || &raw[0..4] != b"XYZ " {
+        return None
+    }
+    Some([read_i32_be(raw,8),read_i32_be(raw,12),read_i32_be(raw,16)])
+}
+
+fn xyz_tag(x:f64,y:f64,z:f64) -> Result<Vec<u8>> {
+    Data::XYZNumber(XYZNumber::from_f64(x,y,z)).encode("XYZ ")
+}
+
+fn chad_tag(matrix:&ColorMatrix3D) -> Result<Vec<u8>> {
+    let vals = matrix.e.iter().map(|val| S15Fixed16Number::from_f64(*val)).collect();
+    Data::S15Fixed16NumberArray(vals).encode("sf32")
+}
+
+/// text tags of v2 profiles (desc, text) are converted to mluc in v4 profiles
+fn repair_text(signature:&str,raw:&[u8],version:u32) -> Result<Option<Vec<u8>>> {
+    let type_signature = &raw[0..4];
+    if type_signature != b"desc" && type_signature != b"text" {
+        return Ok(None)
+    }
+    match allowed_types(signature) {
+        Some((_,v4_types)) if v4_types == ["mluc"] => {},
+        _ => return Ok(None),
+    }
+    let text = match Data::parse(raw,raw.len(),version)?.1 {
+        Data::ASCII(text) => text,
+        Data::Descriptor(descriptor) => descriptor.ascii_string,
+        _ => return Ok(None),
+    };
+    let text = text.trim_end_matches('\0').to_string();
+    Ok(Some(Data::ASCII(text).encode("mluc")?))
+}
+
+/// repair fixes well known defects and returns the repaired profile with the list of changes.
+/// It fixes the header length, the profile ID, v2 text types in v4 profiles, a missing `wtpt`,
+/// a non-D50 header illuminant, a missing `chad` in v4 display profiles with a non-D50 `wtpt`, misaligned tags
+/// and duplicate tag entries. Tags outside of the data and v2 text tags of v4 profiles
+/// that can not be decoded are removed.
+pub fn repair(data:&[u8]) -> Result<(Vec<u8>,Vec<Repair>)> {
+    if data.len() < 132 {
+        return Err(Error::new(ErrorKind::InvalidData,"ICCProfile data shotage"))
+    }
+    let mut repairs = vec![];
+    let mut header = data[..128].to_vec();
+    let version = read_u32_be(data,8);
+    let v4 = version >= 0x04000000;
+
+    if &header[36..40] != b"acsp" {
+        header[36..40].copy_from_slice(b"acsp");
+        repairs.push(Repair::new(Check::Magic,None,"profile file signature set to 'acsp'".to_string()));
+    }
+    let length = read_u32_be(data,0) as usize;
+    if length != data.len() {
+        repairs.push(Repair::new(Check::Length,None,
+            format!("header length {} did not match the data size {}",length,data.len())));
+    }
+    let illuminant = [read_i32_be(data,68),read_i32_be(data,72),read_i32_be(data,76)];
+    if !is_d50(illuminant) {
+        let xyz = XYZNumber::from_f64(D50.0,D50.1,D50.2);
+        header[68..72].copy_from_slice(&xyz.x.to_be_bytes());
+        header[72..76].copy_from_slice(&xyz.y.to_be_bytes());
+        header[76..80].copy_from_slice(&xyz.z.to_be_bytes());
+        repairs.push(Repair::new(Check::Illuminant,None,"PCS illuminant set to D50".to_string()));
+    }
+
+    let directory = TagDirectory::new(data)?;
+    let mut tags :Vec<(String,Vec<u8>)> = vec![];
+    for entry in &directory {
+        let tag = Some(&*entry.signature);
+        if tags.iter().any(|(name,_)| *name == entry.signature) {
+            repairs.push(Repair::new(Check::DuplicateTag,tag,"duplicate tag entry removed".to_string()));
+            continue;
+        }
+        let raw = match entry.raw(data) {
+            Some(raw) if raw.len() >= 8 => raw,
+            _ => {
+                repairs.push(Repair::new(Check::OutOfRange,tag,
+                    format!("tag data {}+{} is outside of the profile, tag removed",entry.offset,entry.size)));
+                continue;
+            }
+        };
+        if !entry.offset.is_multiple_of(4) {
+            repairs.push(Repair::new(Check::Alignment,tag,
+                format!("tag data at offset {} moved to a 4 byte boundary",entry.offset)));
+        }
+        let mut raw = raw.to_vec();
+        if v4 {
+            match repair_text(&entry.signature,&raw,version) {
+                Ok(Some(mluc)) => {
+                    repairs.push(Repair::new(Check::VersionType,tag,
+                        format!("'{}' type converted to 'mluc'",entry.type_signature)));
+                    raw = mluc;
+                },
+                Ok(None) => {},
+                Err(err) => {
+                    repairs.push(Repair::new(Check::TagType,tag,
+                        format!("'{}' type can not be decoded ({}), tag removed",entry.type_signature,err)));
+                    continue;
+                }
+            }
+        }
+        tags.push((entry.signature.to_string(),raw));
+    }
+
+    let device_class = read_u32_be(data,12);
+    let position = |tags:&[(String,Vec<u8>)],name:&str| tags.iter().position(|(tag,_)| tag == name);
+    if device_class != 0x6c696e6b && position(&tags,"wtpt").is_none() {    // 'link'
+        tags.push(("wtpt".to_string(),xyz_tag(D50.0,D50.1,D50.2)?));
+        repairs.push(Repair::new(Check::RequiredTag,Some("wtpt"),"missing media white point added as D50".to_string()));
+    }
+
+    // v4 display profiles store D50 as wtpt and the adaptation from the display white in chad
+    if v4 && device_class == 0x6d6e7472 && position(&tags,"chad").is_none() {    // 'mntr'
+        let i = position(&tags,"wtpt").unwrap_or(0);
+        if let Some(white) = read_xyz(&tags[i].1).filter(|white| !is_d50(*white)) {
+            let src = (white[0] as f64 / 65536.0,white[1] as f64 / 65536.0,white[2] as f64 / 65536.0);
+            let matrix = ColorMatrix3D::chromatic_adaptation(src,D50)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,"media white point can not be adapted"))?;
+            tags[i].1 = xyz_tag(D50.0,D50.1,D50.2)?;
+            repairs.push(Repair::new(Check::RequiredTag,Some("chad"),
+                format!("chad added for media white X:{:.4} Y:{:.4} Z:{:.4}, wtpt set to D50",src.0,src.1,src.2)));
+            tags.push(("chad".to_string(),chad_tag(&matrix)?));
+        }
+    }
+
+    // the builder computes the ID for 2.4 or later profiles and keeps it for older ones that had one
+    match verify_profile_id(data) {
+        Ok(Some(false)) => repairs.push(Repair::new(Check::ProfileId,None,"wrong profile ID recomputed".to_string())),
+        Ok(None) if version >= 0x02400000 => repairs.push(Repair::new(Check::ProfileId,None,"missing profile ID computed".to_string())),
+        _ => {},
+    }
+
+    let repaired = ICCProfileBuilder::from_parts(header,tags).build()?;
+    Ok((repaired,repairs))
+}
+
+impl ICCProfile {
+    /// repair returns repaired profile data and the list of changes
+    pub fn repair(&self) -> Result<(Vec<u8>,Vec<Repair>)> {
+        repair(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn repair_profiles() {
+        use crate::validator::*;
+        let d65 = [0,0,0xf3,0x51,0,1,0,0,0,1,0x16,0xcc];
+        let mut data = profile(&[
+            ("desc",tag("desc",&[0,0,0,5,b'g',b'r',b'a',b'y',0])),
+            ("cprt",tag("text",b"public domain\0")),
+            ("wtpt",tag("XYZ ",&d65)),
+            ("kTRC",tag("curv",&[0,0,0,1,2,0x33])),
+            ("desc",tag("desc",&[0,0,0,4,b'b',b'a',b'd',0])),
+        ]);
+        data[8..12].copy_from_slice(&0x04300000_u32.to_be_bytes());
+        data[12..16].copy_from_slice(b"mntr");
+        data[16..20].copy_from_slice(b"GRAY");
+        data[20..24].copy_from_slice(b"XYZ ");
+        for (i,val) in [2024_u16,2,29,12,0,0].iter().enumerate() {
+            data[24 + i * 2..26 + i * 2].copy_from_slice(&val.to_be_bytes());
+        }
+        data[68..80].copy_from_slice(&d65);
+        data[84] = 1;
+        data.extend_from_slice(&[0;3]);
+        assert!(!is_conforming(&validate(&data)));
+
+        let (repaired,repairs) = crate::repair::repair(&data).unwrap();
+        let checks :Vec<Check> = repairs.iter().map(|repair| repair.check).collect();
+        for check in [Check::Length,Check::Illuminant,Check::DuplicateTag,Check::Alignment,
+                Check::VersionType,Check::RequiredTag,Check::ProfileId] {
+            assert!(checks.contains(&check),"{:?}",check);
+        }
+        let findings = validate(&repaired);
+        assert!(is_conforming(&findings),"{:?}",findings);
+        let decoded = DecodedICCProfile::new(&repaired).unwrap();
+        assert!(matches!(decoded.tags.get("desc"),Some(Data::MultiLocalizedUnicode(mluc)) if mluc.as_string() == "gray"));
+        assert!(matches!(decoded.tags.get("chad"),Some(Data::S15Fixed16NumberArray(vals))
+            if vals.len() == 9 && (vals[0].as_f64() - 1.0479).abs() < 0.001 && (vals[8].as_f64() - 0.7519).abs() < 0.001));
+        let (_,repairs) = crate::repair::repair(&repaired).unwrap();
+        assert!(repairs.is_empty(),"{:?}",repairs);
+    }
+
+    #[test]
+    fn repair_truncated_text() {
+        // the ASCII count runs past the end of the tag
+        let mut data = profile(&[
+            ("desc",tag("desc",&[0,0,0,40,b'g',b'r'])),
+            ("cprt",tag("text",b"public domain\0")),
+        ]);
+        data[8..12].copy_from_slice(&0x04300000_u32.to_be_bytes());
+        let (repaired,repairs) = crate::repair::repair(&data).unwrap();
+        assert!(repairs.iter().any(|repair| repair.check == Check::TagType && repair.tag.as_deref() == Some("desc")),"{:?}",repairs);
+        let decoded = DecodedICCProfile::new(&repaired).unwrap();
+        assert!(!decoded.tags.contains_key("desc"));
+        assert!(matches!(decoded.tags.get("cprt"),Some(Data::MultiLocalizedUnicode(_))));
+    }
+}
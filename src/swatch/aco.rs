@@ -0,0 +1,146 @@
+//! Photoshop color swatches (.aco)
+//! A version 1 section is followed by a version 2 section that adds the names.
+//! Colors are RGB, HSB, CMYK (0 = 100% ink), Lab and grayscale (10000 = black).
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::utils::bound_check;
+use super::{DeviceColor,Swatch,SwatchBook};
+
+const RGB: u16 = 0;
+const HSB: u16 = 1;
+const CMYK: u16 = 2;
+const LAB: u16 = 7;
+const GRAY: u16 = 8;
+
+fn hsb_to_rgb(h:f64,s:f64,v:f64) -> [f64;3] {
+    let h = h * 6.0;
+    let i = h.floor().rem_euclid(6.0);
+    let f = h - h.floor();
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    match i as u8 {
+        0 => [v,t,p],
+        1 => [q,v,p],
+        2 => [p,v,t],
+        3 => [p,q,v],
+        4 => [t,p,v],
+        _ => [v,p,q],
+    }
+}
+
+fn read_color(data:&[u8],ptr:usize) -> Result<(Option<[f64;3]>,Option<DeviceColor>)> {
+    let space = read_u16_be(data,ptr);
+    let w = read_u16_be(data,ptr + 2) as f64;
+    let x = read_u16_be(data,ptr + 4) as f64;
+    let y = read_u16_be(data,ptr + 6) as f64;
+    let z = read_u16_be(data,ptr + 8) as f64;
+    let color = match space {
+        RGB => (None,Some(DeviceColor::Rgb([w / 65535.0,x / 65535.0,y / 65535.0]))),
+        HSB => (None,Some(DeviceColor::Rgb(hsb_to_rgb(w / 65535.0,x / 65535.0,y / 65535.0)))),
+        CMYK => (None,Some(DeviceColor::Cmyk([1.0 - w / 65535.0,1.0 - x / 65535.0,1.0 - y / 65535.0,1.0 - z / 65535.0]))),
+        LAB => {
+            let a = read_i16_be(data,ptr + 4) as f64;
+            let b = read_i16_be(data,ptr + 6) as f64;
+            (Some([w / 100.0,a / 100.0,b / 100.0]),None)
+        },
+        GRAY => (None,Some(DeviceColor::Gray(1.0 - w / 10000.0))),
+        _ => return Err(Error::new(ErrorKind::Unsupported,format!("ACO color space {} is not supported",space))),
+    };
+    Ok(color)
+}
+
+fn write_color(swatch:&Swatch,buf:&mut Vec<u8>) -> Result<()> {
+    let u16_val = |val:f64,max:f64| (val.clamp(0.0,1.0) * max).round() as u16;
+    let (space,vals) = match (&swatch.lab,&swatch.device) {
+        (Some(lab),_) => {
+            let a = (lab[1] * 100.0).round().clamp(-12800.0,12700.0) as i16;
+            let b = (lab[2] * 100.0).round().clamp(-12800.0,12700.0) as i16;
+            (LAB,[(lab[0] * 100.0).round().clamp(0.0,10000.0) as u16,a as u16,b as u16,0])
+        },
+        (None,Some(DeviceColor::Rgb(rgb))) => (RGB,[u16_val(rgb[0],65535.0),u16_val(rgb[1],65535.0),u16_val(rgb[2],65535.0),0]),
+        (None,Some(DeviceColor::Cmyk(cmyk))) => (CMYK,[u16_val(1.0 - cmyk[0],65535.0),u16_val(1.0 - cmyk[1],65535.0),
+            u16_val(1.0 - cmyk[2],65535.0),u16_val(1.0 - cmyk[3],65535.0)]),
+        (None,Some(DeviceColor::Gray(gray))) => (GRAY,[u16_val(1.0 - gray,10000.0),0,0,0]),
+        (None,None) => return Err(Error::new(ErrorKind::InvalidInput,format!("swatch '{}' has no color",swatch.name))),
+    };
+    write_u16_be(space,buf);
+    for val in vals {
+        write_u16_be(val,buf);
+    }
+    Ok(())
+}
+
+impl SwatchBook {
+    pub fn from_aco(data:&[u8]) -> Result<Self> {
+        bound_check(data,0,4)?;
+        let mut ptr = 0;
+        let mut version = read_u16_be(data,ptr);
+        let mut count = read_u16_be(data,ptr + 2) as usize;
+        if version != 1 && version != 2 {
+            return Err(Error::new(ErrorKind::InvalidData,format!("unknown ACO version {}",version)))
+        }
+        if version == 1 {
+            bound_check(data,4,count * 10)?;
+            ptr = 4 + count * 10;
+            // version 2 section with names
+            if data.len() >= ptr + 4 && read_u16_be(data,ptr) == 2 {
+                version = 2;
+                count = read_u16_be(data,ptr + 2) as usize;
+            } else {
+                ptr = 0;
+            }
+        }
+        ptr += 4;
+        let mut book = SwatchBook::default();
+        for i in 0..count {
+            bound_check(data,ptr,10)?;
+            let (lab,device) = read_color(data,ptr)?;
+            ptr += 10;
+            let name = if version == 2 {
+                bound_check(data,ptr,4)?;
+                let length = read_u32_be(data,ptr) as usize;
+                bound_check(data,ptr + 4,length.saturating_mul(2))?;
+                let units :Vec<u16> = (0..length).map(|i| read_u16_be(data,ptr + 4 + i * 2))
+                    .take_while(|unit| *unit != 0).collect();
+                ptr += 4 + length * 2;
+                String::from_utf16_lossy(&units)
+            } else {
+                format!("Color {}",i + 1)
+            };
+            book.swatches.push(Swatch {
+                name,
+                lab,
+                device,
+                spot: false,
+            });
+        }
+        Ok(book)
+    }
+
+    /// to_aco writes Lab when a swatch has it, otherwise the device values
+    pub fn to_aco(&self) -> Result<Vec<u8>> {
+        let count = u16::try_from(self.swatches.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput,"ACO holds up to 65535 colors"))?;
+        let mut buf = vec![];
+        write_u16_be(1,&mut buf);
+        write_u16_be(count,&mut buf);
+        for swatch in &self.swatches {
+            write_color(swatch,&mut buf)?;
+        }
+        write_u16_be(2,&mut buf);
+        write_u16_be(count,&mut buf);
+        for swatch in &self.swatches {
+            write_color(swatch,&mut buf)?;
+            let units :Vec<u16> = swatch.name.encode_utf16().collect();
+            write_u32_be(units.len() as u32 + 1,&mut buf);
+            for unit in units {
+                write_u16_be(unit,&mut buf);
+            }
+            write_u16_be(0,&mut buf);
+        }
+        Ok(buf)
+    }
+}
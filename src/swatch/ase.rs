@@ -0,0 +1,143 @@
+//! Adobe Swatch Exchange (.ase)
+//! Groups are flattened on reading, the first group name becomes the book name.
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::utils::bound_check;
+use super::{DeviceColor,Swatch,SwatchBook};
+
+const GROUP_START: u16 = 0xc001;
+const GROUP_END: u16 = 0xc002;
+const COLOR: u16 = 0x0001;
+
+fn read_f32_be(data:&[u8],ptr:usize) -> f64 {
+    f32::from_be_bytes([data[ptr],data[ptr + 1],data[ptr + 2],data[ptr + 3]]) as f64
+}
+
+/// names are u16 length including the terminator and UTF-16BE
+fn read_name(data:&[u8],ptr:usize) -> Result<(String,usize)> {
+    bound_check(data,ptr,2)?;
+    let length = read_u16_be(data,ptr) as usize;
+    bound_check(data,ptr + 2,length * 2)?;
+    let units :Vec<u16> = (0..length).map(|i| read_u16_be(data,ptr + 2 + i * 2))
+        .take_while(|unit| *unit != 0).collect();
+    Ok((String::from_utf16_lossy(&units),2 + length * 2))
+}
+
+fn write_name(name:&str,buf:&mut Vec<u8>) {
+    let units :Vec<u16> = name.encode_utf16().collect();
+    write_u16_be(units.len() as u16 + 1,buf);
+    for unit in units {
+        write_u16_be(unit,buf);
+    }
+    write_u16_be(0,buf);
+}
+
+fn write_block(block_type:u16,body:&[u8],buf:&mut Vec<u8>) {
+    write_u16_be(block_type,buf);
+    write_u32_be(body.len() as u32,buf);
+    buf.extend_from_slice(body);
+}
+
+fn read_color(data:&[u8],ptr:usize) -> Result<Swatch> {
+    let (name,size) = read_name(data,ptr)?;
+    let mut ptr = ptr + size;
+    bound_check(data,ptr,4)?;
+    let model = &data[ptr..ptr + 4];
+    let channels = match model {
+        b"CMYK" => 4,
+        b"RGB " | b"LAB " => 3,
+        b"Gray" => 1,
+        _ => return Err(Error::new(ErrorKind::InvalidData,format!("unknown ASE color model {:?}",String::from_utf8_lossy(model)))),
+    };
+    ptr += 4;
+    bound_check(data,ptr,channels * 4 + 2)?;
+    let vals :Vec<f64> = (0..channels).map(|i| read_f32_be(data,ptr + i * 4)).collect();
+    let color_type = read_u16_be(data,ptr + channels * 4);
+    let (lab,device) = match model {
+        b"CMYK" => (None,Some(DeviceColor::Cmyk([vals[0],vals[1],vals[2],vals[3]]))),
+        b"RGB " => (None,Some(DeviceColor::Rgb([vals[0],vals[1],vals[2]]))),
+        b"LAB " => (Some([vals[0] * 100.0,vals[1],vals[2]]),None),
+        _ => (None,Some(DeviceColor::Gray(vals[0]))),
+    };
+    Ok(Swatch {
+        name,
+        lab,
+        device,
+        spot: color_type == 1,
+    })
+}
+
+impl SwatchBook {
+    pub fn from_ase(data:&[u8]) -> Result<Self> {
+        bound_check(data,0,12)?;
+        if &data[0..4] != b"ASEF" {
+            return Err(Error::new(ErrorKind::InvalidData,"not an ASE file"))
+        }
+        let blocks = read_u32_be(data,8) as usize;
+        let mut book = SwatchBook::default();
+        let mut ptr = 12;
+        for _ in 0..blocks {
+            bound_check(data,ptr,6)?;
+            let block_type = read_u16_be(data,ptr);
+            let length = read_u32_be(data,ptr + 2) as usize;
+            ptr += 6;
+            bound_check(data,ptr,length)?;
+            let block = &data[..ptr + length];
+            match block_type {
+                GROUP_START => {
+                    let (name,_) = read_name(block,ptr)?;
+                    if book.name.is_empty() {
+                        book.name = name;
+                    }
+                },
+                COLOR => book.swatches.push(read_color(block,ptr)?),
+                _ => {},
+            }
+            ptr += length;
+        }
+        Ok(book)
+    }
+
+    /// to_ase writes Lab when a swatch has it, otherwise the device values.
+    /// Swatches are grouped under the book name when it is not empty.
+    pub fn to_ase(&self) -> Result<Vec<u8>> {
+        let mut blocks = vec![];
+        let mut count = 0_u32;
+        if !self.name.is_empty() {
+            let mut body = vec![];
+            write_name(&self.name,&mut body);
+            write_block(GROUP_START,&body,&mut blocks);
+            count += 1;
+        }
+        for swatch in &self.swatches {
+            let (model,vals) = match (&swatch.lab,&swatch.device) {
+                (Some(lab),_) => (b"LAB ",vec![lab[0] / 100.0,lab[1],lab[2]]),
+                (None,Some(DeviceColor::Cmyk(cmyk))) => (b"CMYK",cmyk.to_vec()),
+                (None,Some(DeviceColor::Rgb(rgb))) => (b"RGB ",rgb.to_vec()),
+                (None,Some(DeviceColor::Gray(gray))) => (b"Gray",vec![*gray]),
+                (None,None) => return Err(Error::new(ErrorKind::InvalidInput,format!("swatch '{}' has no color",swatch.name))),
+            };
+            let mut body = vec![];
+            write_name(&swatch.name,&mut body);
+            body.extend_from_slice(model);
+            for val in vals {
+                write_f32_be(val as f32,&mut body);
+            }
+            write_u16_be(if swatch.spot { 1 } else { 2 },&mut body);
+            write_block(COLOR,&body,&mut blocks);
+            count += 1;
+        }
+        if !self.name.is_empty() {
+            write_block(GROUP_END,&[],&mut blocks);
+            count += 1;
+        }
+        let mut buf = b"ASEF".to_vec();
+        write_u16_be(1,&mut buf);
+        write_u16_be(0,&mut buf);
+        write_u32_be(count,&mut buf);
+        buf.extend_from_slice(&blocks);
+        Ok(buf)
+    }
+}
@@ -0,0 +1,291 @@
+//! X-Rite CxF3 (Color Exchange Format, ISO 17972-1)
+//! Reads `Object` elements with ColorCIELab, ColorCMYK (percent), ColorSRGB and ColorRGB (0-255) values.
+//! Lab values are taken as D50, spectral data and color specifications are ignored.
+//! CxF3 has no gray color type, books with gray swatches can not be written.
+//! CxF3 core has no spot flag either, `Swatch::spot` is false on import and is not written.
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use super::{DeviceColor,Swatch,SwatchBook};
+
+/// minimal XML element, namespace prefixes are removed
+#[derive(Debug,Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String,String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self,name:&str) -> Option<&str> {
+        self.attributes.iter().find(|(key,_)| key == name).map(|(_,val)| val.as_str())
+    }
+
+    fn child(&self,name:&str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn number(&self,name:&str) -> Option<f64> {
+        self.child(name)?.text.trim().parse().ok()
+    }
+
+    fn find_all<'a>(&'a self,name:&str,found:&mut Vec<&'a Element>) {
+        for child in &self.children {
+            if child.name == name {
+                found.push(child);
+            } else {
+                child.find_all(name,found);
+            }
+        }
+    }
+}
+
+/// elements nested deeper than this are rejected, CxF3 files use less than 10 levels
+const MAX_DEPTH: usize = 64;
+
+fn xml_error(message:&str) -> Error {
+    Error::new(ErrorKind::InvalidData,format!("CxF: {}",message))
+}
+
+fn local_name(name:&str) -> String {
+    name.rsplit(':').next().unwrap_or(name).to_string()
+}
+
+fn unescape(text:&str) -> String {
+    let mut string = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        string.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..],16).ok().and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match ch {
+            Some(ch) => {
+                string.push(ch);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                string.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    string.push_str(rest);
+    string
+}
+
+fn escape(text:&str) -> String {
+    text.replace('&',"&amp;").replace('<',"&lt;").replace('>',"&gt;").replace('"',"&quot;")
+}
+
+fn parse_tag(tag:&str) -> Result<Element> {
+    let tag = tag.trim();
+    let end = tag.find(|c:char| c.is_whitespace()).unwrap_or(tag.len());
+    let mut element = Element { name: local_name(&tag[..end]), ..Element::default() };
+    let mut rest = tag[end..].trim_start();
+    while !rest.is_empty() {
+        let eq = rest.find('=').ok_or_else(|| xml_error("broken attribute"))?;
+        let key = local_name(rest[..eq].trim());
+        let value = rest[eq + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| xml_error("attribute value is not quoted"))?;
+        let close = value[1..].find(quote).ok_or_else(|| xml_error("attribute value is not closed"))?;
+        element.attributes.push((key,unescape(&value[1..close + 1])));
+        rest = value[close + 2..].trim_start();
+    }
+    Ok(element)
+}
+
+fn parse(xml:&str) -> Result<Element> {
+    let mut stack = vec![Element::default()];
+    let mut rest = xml;
+    while let Some(i) = rest.find('<') {
+        let text = &rest[..i];
+        if let Some(parent) = stack.last_mut() {
+            parent.text.push_str(&unescape(text));
+        }
+        rest = &rest[i..];
+        let (skip,close) = if rest.starts_with("<!--") {
+            ("<!--".len(),"-->")
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").ok_or_else(|| xml_error("CDATA is not closed"))?;
+            if let Some(parent) = stack.last_mut() {
+                parent.text.push_str(&rest[9..end]);
+            }
+            rest = &rest[end + 3..];
+            continue;
+        } else if rest.starts_with("<?") {
+            (2,"?>")
+        } else if rest.starts_with("<!") {
+            (2,">")
+        } else {
+            (0,">")
+        };
+        let end = rest[skip..].find(close).ok_or_else(|| xml_error("tag is not closed"))? + skip;
+        let tag = &rest[1..end];
+        rest = &rest[end + close.len()..];
+        if skip != 0 {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().ok_or_else(|| xml_error("unbalanced tags"))?;
+            if element.name != local_name(name.trim()) || stack.is_empty() {
+                return Err(xml_error(&format!("unexpected closing tag {}",name)))
+            }
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(element);
+            }
+        } else if let Some(tag) = tag.strip_suffix('/') {
+            let element = parse_tag(tag)?;
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(element);
+            }
+        } else {
+            if stack.len() > MAX_DEPTH {
+                return Err(xml_error("elements are nested too deep"))
+            }
+            stack.push(parse_tag(tag)?);
+        }
+    }
+    if stack.len() != 1 {
+        return Err(xml_error("unclosed elements"))
+    }
+    Ok(stack.remove(0))
+}
+
+fn read_object(object:&Element) -> Swatch {
+    let mut swatch = Swatch {
+        name: object.attribute("Name").or_else(|| object.attribute("Id")).unwrap_or("").to_string(),
+        lab: None,
+        device: None,
+        spot: false,
+    };
+    let values = match object.child("ColorValues") {
+        Some(values) => values,
+        None => return swatch,
+    };
+    for value in &values.children {
+        match &*value.name {
+            "ColorCIELab" if swatch.lab.is_none() => {
+                if let (Some(l),Some(a),Some(b)) = (value.number("L"),value.number("A"),value.number("B")) {
+                    swatch.lab = Some([l,a,b]);
+                }
+            },
+            "ColorCMYK" => {
+                if let (Some(c),Some(m),Some(y),Some(k)) = (value.number("Cyan"),value.number("Magenta"),
+                        value.number("Yellow"),value.number("Black")) {
+                    swatch.device = Some(DeviceColor::Cmyk([c / 100.0,m / 100.0,y / 100.0,k / 100.0]));
+                }
+            },
+            "ColorSRGB" | "ColorRGB" if swatch.device.is_none() => {
+                let max = value.attribute("MaxRange").and_then(|max| max.parse().ok()).unwrap_or(255.0);
+                if let (Some(r),Some(g),Some(b)) = (value.number("R"),value.number("G"),value.number("B")) {
+                    swatch.device = Some(DeviceColor::Rgb([r / max,g / max,b / max]));
+                }
+            },
+            _ => {},
+        }
+    }
+    swatch
+}
+
+impl SwatchBook {
+    pub fn from_cxf(xml:&str) -> Result<Self> {
+        let root = parse(xml)?;
+        if root.child("CxF").is_none() {
+            return Err(xml_error("CxF root element is missing"))
+        }
+        let mut objects = vec![];
+        root.find_all("Object",&mut objects);
+        let name = root.child("CxF")
+            .and_then(|cxf| cxf.child("FileInformation"))
+            .and_then(|info| info.child("Description"))
+            .map(|description| description.text.trim().to_string())
+            .unwrap_or_default();
+        Ok(Self {
+            name,
+            swatches: objects.into_iter().map(read_object).collect(),
+        })
+    }
+
+    /// to_cxf writes every swatch as an Object with D50 Lab and CMYK or sRGB values
+    pub fn to_cxf(&self) -> Result<String> {
+        let mut xml = String::new();
+        let mut srgb = false;
+        xml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+        xml += "<cc:CxF xmlns:cc=\"http://colorexchangeformat.com/CxF3-core\">\n";
+        xml += "  <cc:FileInformation>\n";
+        xml += &format!("    <cc:Description>{}</cc:Description>\n",escape(&self.name));
+        xml += "  </cc:FileInformation>\n";
+        xml += "  <cc:Resources>\n";
+        xml += "    <cc:ObjectCollection>\n";
+        for (i,swatch) in self.swatches.iter().enumerate() {
+            xml += &format!("      <cc:Object ObjectType=\"Standard\" Name=\"{}\" Id=\"{}\">\n",escape(&swatch.name),i + 1);
+            xml += "        <cc:ColorValues>\n";
+            if let Some(lab) = swatch.lab {
+                xml += "          <cc:ColorCIELab ColorSpecification=\"CS_D50_2\">\n";
+                xml += &format!("            <cc:L>{}</cc:L>\n            <cc:A>{}</cc:A>\n            <cc:B>{}</cc:B>\n",lab[0],lab[1],lab[2]);
+                xml += "          </cc:ColorCIELab>\n";
+            }
+            match &swatch.device {
+                Some(DeviceColor::Cmyk(cmyk)) => {
+                    xml += "          <cc:ColorCMYK ColorSpecification=\"CS_D50_2\">\n";
+                    for (name,val) in ["Cyan","Magenta","Yellow","Black"].iter().zip(cmyk.iter()) {
+                        xml += &format!("            <cc:{}>{}</cc:{}>\n",name,val * 100.0,name);
+                    }
+                    xml += "          </cc:ColorCMYK>\n";
+                },
+                Some(DeviceColor::Rgb(rgb)) => {
+                    srgb = true;
+                    xml += "          <cc:ColorSRGB ColorSpecification=\"CS_sRGB\">\n";
+                    for (name,val) in ["R","G","B"].iter().zip(rgb.iter()) {
+                        xml += &format!("            <cc:{}>{}</cc:{}>\n",name,(val * 255.0).round(),name);
+                    }
+                    xml += "          </cc:ColorSRGB>\n";
+                },
+                Some(DeviceColor::Gray(_)) => {
+                    return Err(Error::new(ErrorKind::InvalidInput,format!("swatch '{}' is gray, CxF has no gray color",swatch.name)))
+                },
+                None => {},
+            }
+            xml += "        </cc:ColorValues>\n";
+            xml += "      </cc:Object>\n";
+        }
+        xml += "    </cc:ObjectCollection>\n";
+        xml += "    <cc:ColorSpecificationCollection>\n";
+        xml += "      <cc:ColorSpecification Id=\"CS_D50_2\">\n";
+        xml += "        <cc:TristimulusSpec>\n";
+        xml += "          <cc:Illuminant>D50</cc:Illuminant>\n";
+        xml += "          <cc:Observer>2_Degree</cc:Observer>\n";
+        xml += "          <cc:Method>COLORIMETRIC</cc:Method>\n";
+        xml += "        </cc:TristimulusSpec>\n";
+        xml += "      </cc:ColorSpecification>\n";
+        if srgb {
+            xml += "      <cc:ColorSpecification Id=\"CS_sRGB\">\n";
+            xml += "        <cc:TristimulusSpec>\n";
+            xml += "          <cc:Illuminant>D65</cc:Illuminant>\n";
+            xml += "          <cc:Observer>2_Degree</cc:Observer>\n";
+            xml += "          <cc:Method>COLORIMETRIC</cc:Method>\n";
+            xml += "        </cc:TristimulusSpec>\n";
+            xml += "      </cc:ColorSpecification>\n";
+        }
+        xml += "    </cc:ColorSpecificationCollection>\n";
+        xml += "  </cc:Resources>\n";
+        xml += "</cc:CxF>\n";
+        Ok(xml)
+    }
+}
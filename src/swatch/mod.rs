@@ -0,0 +1,206 @@
+//! Swatch libraries
+//! SwatchBook reads and writes Adobe Swatch Exchange (.ase), Photoshop color swatches (.aco)
+//! and X-Rite CxF3 XML, and converts to and from named color lists.
+//! ```
+//! use icc_profile::swatch::*;
+//!
+//! fn ase_to_profile(data:&[u8]) -> std::io::Result<Vec<u8>> {
+//!     let book = SwatchBook::from_ase(data)?;
+//!     let list = book.to_named_colors()?;
+//!     list.to_profile(u32::from_be_bytes(*b"CMYK"),&book.name,"No copyright")
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use crate::named_color::NamedColorList;
+use crate::cms::pcs::xyz_to_lab;
+
+pub mod ase;
+pub mod aco;
+pub mod cxf;
+
+/// device color values, 0.0-1.0
+#[derive(Debug,Clone,PartialEq)]
+pub enum DeviceColor {
+    /// 0.0 is black, 1.0 is white
+    Gray(f64),
+    Rgb([f64;3]),
+    /// ink amounts, 1.0 is 100%
+    Cmyk([f64;4]),
+}
+
+impl DeviceColor {
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            DeviceColor::Gray(gray) => vec![*gray],
+            DeviceColor::Rgb(rgb) => rgb.to_vec(),
+            DeviceColor::Cmyk(cmyk) => cmyk.to_vec(),
+        }
+    }
+
+    /// color_space returns the ICC color space signature
+    pub fn color_space(&self) -> u32 {
+        match self {
+            DeviceColor::Gray(_) => u32::from_be_bytes(*b"GRAY"),
+            DeviceColor::Rgb(_) => u32::from_be_bytes(*b"RGB "),
+            DeviceColor::Cmyk(_) => u32::from_be_bytes(*b"CMYK"),
+        }
+    }
+
+    fn from_values(color_space:u32,values:&[f64]) -> Option<Self> {
+        match (&color_space.to_be_bytes(),values.len()) {
+            (b"GRAY",1) => Some(DeviceColor::Gray(values[0])),
+            (b"RGB ",3) => Some(DeviceColor::Rgb([values[0],values[1],values[2]])),
+            (b"CMYK",4) => Some(DeviceColor::Cmyk([values[0],values[1],values[2],values[3]])),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Swatch {
+    pub name: String,
+    /// D50 L*a*b*
+    pub lab: Option<[f64;3]>,
+    pub device: Option<DeviceColor>,
+    /// spot (named) color rather than a process color
+    pub spot: bool,
+}
+
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SwatchBook {
+    pub name: String,
+    pub swatches: Vec<Swatch>,
+}
+
+/// srgb_to_lab uses the D50 adapted sRGB matrix of the ICC sRGB profile
+pub(crate) fn srgb_to_lab(rgb:[f64;3]) -> [f64;3] {
+    let linear = |v:f64| if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+    let (r,g,b) = (linear(rgb[0]),linear(rgb[1]),linear(rgb[2]));
+    let x = 0.4360747 * r + 0.3850649 * g + 0.1430804 * b;
+    let y = 0.2225045 * r + 0.7168786 * g + 0.0606169 * b;
+    let z = 0.0139322 * r + 0.0971045 * g + 0.7141733 * b;
+    xyz_to_lab([x,y,z])
+}
+
+impl Swatch {
+    /// pcs_lab returns the Lab value, RGB and gray colors without Lab are taken as sRGB
+    pub fn pcs_lab(&self) -> Option<[f64;3]> {
+        match (&self.lab,&self.device) {
+            (Some(lab),_) => Some(*lab),
+            (None,Some(DeviceColor::Rgb(rgb))) => Some(srgb_to_lab(*rgb)),
+            (None,Some(DeviceColor::Gray(gray))) => Some(srgb_to_lab([*gray;3])),
+            _ => None,
+        }
+    }
+}
+
+impl SwatchBook {
+    pub fn new(name:&str) -> Self {
+        Self {
+            name: name.to_string(),
+            swatches: vec![],
+        }
+    }
+
+    /// device_space returns the color space when every swatch has device values of the same space
+    pub fn device_space(&self) -> Option<u32> {
+        let space = self.swatches.first()?.device.as_ref()?.color_space();
+        self.swatches.iter()
+            .all(|swatch| swatch.device.as_ref().map(|device| device.color_space()) == Some(space))
+            .then_some(space)
+    }
+
+    /// to_named_colors converts swatches to a named color list.
+    /// Device coordinates are stored only when every swatch has values of the same color space.
+    pub fn to_named_colors(&self) -> Result<NamedColorList> {
+        let mut list = NamedColorList::new("","");
+        let device = self.device_space().is_some();
+        for swatch in &self.swatches {
+            let lab = swatch.pcs_lab()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData,format!("swatch '{}' has no Lab value",swatch.name)))?;
+            let values = match (&swatch.device,device) {
+                (Some(color),true) => color.values().iter().map(|v| (v.clamp(0.0,1.0) * 65535.0).round() as u16).collect(),
+                _ => vec![],
+            };
+            list.push(&swatch.name,lab,values)?;
+        }
+        Ok(list)
+    }
+
+    /// from_named_colors uses full names, `color_space` tells how to read the device coordinates
+    pub fn from_named_colors(list:&NamedColorList,color_space:u32) -> Self {
+        let swatches = list.colors.iter().map(|color| {
+            let values :Vec<f64> = color.device.iter().map(|v| *v as f64 / 65535.0).collect();
+            Swatch {
+                name: list.full_name(color),
+                lab: Some(color.lab),
+                device: DeviceColor::from_values(color_space,&values),
+                spot: true,
+            }
+        }).collect();
+        Self {
+            name: String::new(),
+            swatches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iccprofile::*;
+
+    #[test]
+    fn swatch_books() {
+        let mut book = SwatchBook::new("inks & papers");
+        book.swatches.push(Swatch { name: "Red".to_string(), lab: Some([50.0,70.0,40.0]),
+            device: Some(DeviceColor::Cmyk([0.0,0.85,0.75,0.0])), spot: true });
+        book.swatches.push(Swatch { name: "Blue 072".to_string(), lab: Some([25.0,21.25,-65.0]),
+            device: Some(DeviceColor::Cmyk([1.0,0.75,0.0,0.1])), spot: true });
+
+        let ase = SwatchBook::from_ase(&book.to_ase().unwrap()).unwrap();
+        assert_eq!(ase.name,"inks & papers");
+        assert_eq!(ase.swatches[1].name,"Blue 072");
+        assert_eq!(ase.swatches[1].lab,Some([25.0,21.25,-65.0]));
+        assert!(ase.swatches[1].spot);
+
+        let aco = SwatchBook::from_aco(&book.to_aco().unwrap()).unwrap();
+        assert_eq!(aco.swatches[0].name,"Red");
+        assert_eq!(aco.swatches[0].lab,Some([50.0,70.0,40.0]));
+        let mut cmyk = book.clone();
+        for swatch in &mut cmyk.swatches { swatch.lab = Option::None; }
+        let aco = SwatchBook::from_aco(&cmyk.to_aco().unwrap()).unwrap();
+        assert!(matches!(aco.swatches[1].device,Some(DeviceColor::Cmyk(vals)) if (vals[1] - 0.75).abs() < 1e-4 && (vals[3] - 0.1).abs() < 1e-4));
+
+        let xml = book.to_cxf().unwrap();
+        assert!(!xml.contains("CS_sRGB"));
+        let cxf = SwatchBook::from_cxf(&xml).unwrap();
+        assert_eq!(cxf.name,book.name);
+        assert_eq!(cxf.swatches[1].name,"Blue 072");
+        assert_eq!(cxf.swatches[1].lab,Some([25.0,21.25,-65.0]));
+        assert!(matches!(cxf.swatches[0].device,Some(DeviceColor::Cmyk(vals)) if (vals[1] - 0.85).abs() < 1e-9));
+        let mut rgb = SwatchBook::new("screen");
+        rgb.swatches.push(Swatch { name: "Orange".to_string(), lab: Option::None, device: Some(DeviceColor::Rgb([1.0,0.6,0.0])), spot: false });
+        let xml = rgb.to_cxf().unwrap();
+        assert!(xml.contains("<cc:ColorSRGB ColorSpecification=\"CS_sRGB\">") && xml.contains("<cc:ColorSpecification Id=\"CS_sRGB\">"));
+        assert!(matches!(SwatchBook::from_cxf(&xml).unwrap().swatches[0].device,Some(DeviceColor::Rgb(vals)) if vals == [1.0,0.6,0.0]));
+        rgb.swatches[0].device = Some(DeviceColor::Gray(0.5));
+        assert!(matches!(rgb.to_cxf(),Err(err) if err.kind() == std::io::ErrorKind::InvalidInput));
+        assert!(SwatchBook::from_cxf("<cc:CxF><cc:Object></cc:CxF>").is_err());
+        assert!(!cxf.swatches[0].spot);
+        let deep = format!("<cc:CxF>{}{}</cc:CxF>","<a>".repeat(100000),"</a>".repeat(100000));
+        assert!(SwatchBook::from_cxf(&deep).is_err());
+
+        let list = book.to_named_colors().unwrap();
+        let data = list.to_profile(book.device_space().unwrap(),&book.name,"none").unwrap();
+        let decoded = DecodedICCProfile::new(&data).unwrap();
+        let read = SwatchBook::from_named_colors(&NamedColorList::from_profile(&decoded).unwrap(),decoded.color_space);
+        assert_eq!(read.swatches[0].name,"Red");
+        assert!(matches!(read.swatches[0].device,Some(DeviceColor::Cmyk(vals)) if (vals[1] - 0.85).abs() < 1e-4));
+        // RGB without Lab is taken as sRGB
+        let white = Swatch { name: "White".to_string(), lab: Option::None, device: Some(DeviceColor::Rgb([1.0,1.0,1.0])), spot: false };
+        assert!((white.pcs_lab().unwrap()[0] - 100.0).abs() < 0.01);
+    }
+}
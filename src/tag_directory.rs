@@ -0,0 +1,131 @@
+//! Tag table of ICC Profile
+//! TagDirectory keeps the tag table in file order with offsets, sizes and shared data blocks.
+
+use std::collections::HashMap;
+use std::io::Result;
+use bin_rs::io::*;
+use crate::utils::bound_check;
+
+#[derive(Debug,Clone)]
+pub struct TagEntry {
+    /// tag signature e.g. "rTRC"
+    pub signature: String,
+    pub offset: u32,
+    pub size: u32,
+    /// type signature of the tag data e.g. "curv", empty when the offset is out of the profile
+    pub type_signature: String,
+    /// other tags which point to the same data block
+    pub shared_with: Vec<String>,
+}
+
+impl TagEntry {
+    pub fn is_shared(&self) -> bool {
+        !self.shared_with.is_empty()
+    }
+
+    /// raw tag data including the type signature
+    pub fn raw<'a>(&self,data:&'a [u8]) -> Option<&'a [u8]> {
+        let offset = self.offset as usize;
+        data.get(offset..offset.checked_add(self.size as usize)?)
+    }
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct TagDirectory {
+    pub entries: Vec<TagEntry>,
+}
+
+impl TagDirectory {
+    /// new reads the tag table of profile data
+    pub fn new(data:&[u8]) -> Result<Self> {
+        bound_check(data,128,4)?;
+        let count = read_u32_be(data,128) as usize;
+        Self::read(data,count)
+    }
+
+    pub(crate) fn read(data:&[u8],count:usize) -> Result<Self> {
+        let mut ptr = 132;
+        bound_check(data,ptr,count * 12)?;
+        let mut entries :Vec<TagEntry> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let signature = read_string(data,ptr,4);
+            let offset = read_u32_be(data,ptr + 4);
+            let size = read_u32_be(data,ptr + 8);
+            ptr += 12;
+            let type_signature = if bound_check(data,offset as usize,4).is_ok() {
+                read_string(data,offset as usize,4)
+            } else {
+                "".to_string()
+            };
+            entries.push(TagEntry{signature,offset,size,type_signature,shared_with: vec![]});
+        }
+        let mut blocks :HashMap<u32,Vec<usize>> = HashMap::new();
+        for (i,entry) in entries.iter().enumerate() {
+            blocks.entry(entry.offset).or_default().push(i);
+        }
+        for block in blocks.values().filter(|block| block.len() > 1) {
+            for &i in block {
+                entries[i].shared_with = block.iter()
+                    .filter(|&&j| j != i)
+                    .map(|&j| entries[j].signature.to_string())
+                    .collect();
+            }
+        }
+        Ok(Self{entries})
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_,TagEntry> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self,signature:&str) -> Option<&TagEntry> {
+        self.entries.iter().find(|entry| entry.signature == signature)
+    }
+
+    /// raw tag data including the type signature
+    pub fn raw<'a>(&self,data:&'a [u8],signature:&str) -> Option<&'a [u8]> {
+        self.get(signature)?.raw(data)
+    }
+}
+
+impl<'a> IntoIterator for &'a TagDirectory {
+    type Item = &'a TagEntry;
+    type IntoIter = std::slice::Iter<'a,TagEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::iccprofile::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn tag_directory() {
+        let mut base = sample();
+        // point wtpt at the rTRC block
+        let (offset,size) = (base[132 + 28..132 + 36].to_vec(),base[132 + 16..132 + 24].to_vec());
+        assert_ne!(offset,size);
+        base[132 + 16..132 + 24].copy_from_slice(&offset);
+        let decoded = DecodedICCProfile::new(&base).unwrap();
+        let names :Vec<&str> = decoded.directory.iter().map(|entry| &*entry.signature).collect();
+        assert_eq!(names,["desc","wtpt","rTRC","A2B0","A2B1","cprt","pseq"]);
+        let wtpt = decoded.directory.get("wtpt").unwrap();
+        assert_eq!(wtpt.type_signature,"curv");
+        assert_eq!(wtpt.shared_with,["rTRC"]);
+        assert!(!decoded.directory.get("desc").unwrap().is_shared());
+        assert_eq!(decoded.directory.raw(&base,"rTRC"),Some(&tag("curv",&[0,0,0,2,0,0,0xff,0xff])[..]));
+        let profile = crate::IccProfileRef::new(&base).unwrap();
+        assert_eq!(profile.directory().get("rTRC").unwrap().shared_with,["wtpt"]);
+    }
+}
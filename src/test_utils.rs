@@ -0,0 +1,164 @@
+//! Profile fixtures shared by the unit tests
+
+/// tag prepends the type signature and the reserved bytes to a tag body
+pub(crate) fn tag(sig:&str,body:&[u8]) -> Vec<u8> {
+    let mut data = sig.as_bytes().to_vec();
+    data.extend_from_slice(&[0;4]);
+    data.extend_from_slice(body);
+    data
+}
+
+/// profile makes a v2 profile of the tags with a zero filled header
+pub(crate) fn profile(tags:&[(&str,Vec<u8>)]) -> Vec<u8> {
+    let mut table = vec![];
+    let mut data = vec![];
+    let mut offset = 132 + tags.len() * 12;
+    for (name,body) in tags {
+        table.extend_from_slice(name.as_bytes());
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        data.extend_from_slice(body);
+        offset += body.len();
+    }
+    let mut buf = vec![0_u8;128];
+    buf[0..4].copy_from_slice(&(offset as u32).to_be_bytes());
+    buf[8..12].copy_from_slice(&0x02100000_u32.to_be_bytes());
+    buf[36..40].copy_from_slice(b"acsp");
+    buf.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&table);
+    buf.extend_from_slice(&data);
+    buf
+}
+
+/// sample has text, curve, lut, pseq and a shareable block in v2 and v4 types
+pub(crate) fn sample() -> Vec<u8> {
+    let mut mft2 = vec![3,3,2,0];
+    for i in 0..9 { mft2.extend_from_slice(&(if i % 4 == 0 {0x10000_u32} else {0}).to_be_bytes()); }
+    mft2.extend_from_slice(&2_u16.to_be_bytes());
+    mft2.extend_from_slice(&2_u16.to_be_bytes());
+    for i in 0..(3 * 2 + 8 * 3 + 3 * 2) { mft2.extend_from_slice(&((i * 1000) as u16).to_be_bytes()); }
+    let mut mluc = vec![0,0,0,1,0,0,0,12,b'e',b'n',b'U',b'S',0,0,0,4,0,0,0,28];
+    mluc.extend_from_slice(&[0,b'O',0,b'K']);
+    let mut pseq = vec![0,0,0,1];
+    pseq.extend_from_slice(&[0;20]);
+    pseq.extend_from_slice(&tag("desc",&[0,0,0,2,b'A',0,0,0,0,0,0,0,0,0]));
+    pseq.extend_from_slice(&[0;70]);
+    pseq.extend_from_slice(&tag("mluc",&mluc));
+    let mut mab = vec![3,3,0,0,0,0,0,32,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0];
+    for _ in 0..3 { mab.extend_from_slice(&tag("para",&[0,0,0,0,0,1,0,0])); }
+    profile(&[
+        ("desc",tag("desc",&[0,0,0,5,b't',b'e',b's',b't',0])),
+        ("wtpt",tag("XYZ ",&[0,0,0xf6,0xd6,0,1,0,0,0,0,0xd3,0x2d])),
+        ("rTRC",tag("curv",&[0,0,0,2,0,0,0xff,0xff])),
+        ("A2B0",tag("mft2",&mft2)),
+        ("A2B1",tag("mAB ",&mab)),
+        ("cprt",tag("mluc",&mluc)),
+        ("pseq",tag("pseq",&pseq)),
+    ])
+}
+
+/// grid_points lists the points of a clut grid, 0.0-1.0 per input, the first input varies slowest
+fn grid_points(inputs:usize,grid:usize) -> Vec<Vec<f64>> {
+    (0..grid.pow(inputs as u32)).map(|i| (0..inputs)
+        .map(|j| (i / grid.pow((inputs - 1 - j) as u32) % grid) as f64 / (grid - 1) as f64).collect()).collect()
+}
+
+fn identity_matrix() -> Vec<u8> {
+    (0..9).flat_map(|i| (if i % 4 == 0 { 0x10000_u32 } else { 0 }).to_be_bytes()).collect()
+}
+
+/// lut16_fixture makes a mft2 body with the identity matrix and identity curves.
+/// `clut` returns the outputs of a grid point, its inputs are 0.0-1.0 and the first input varies slowest.
+pub(crate) fn lut16_fixture(inputs:usize,outputs:usize,grid:usize,clut:&dyn Fn(&[f64]) -> Vec<u16>) -> Vec<u8> {
+    lut16_curves_fixture(inputs,outputs,grid,&[0,0xffff],clut,&[0,0xffff])
+}
+
+/// lut16_curves_fixture is lut16_fixture with `input_curve` on every input and `output_curve` on every output
+pub(crate) fn lut16_curves_fixture(inputs:usize,outputs:usize,grid:usize,input_curve:&[u16],
+        clut:&dyn Fn(&[f64]) -> Vec<u16>,output_curve:&[u16]) -> Vec<u8> {
+    let mut mft2 = vec![inputs as u8,outputs as u8,grid as u8,0];
+    mft2.extend_from_slice(&identity_matrix());
+    mft2.extend_from_slice(&(input_curve.len() as u16).to_be_bytes());
+    mft2.extend_from_slice(&(output_curve.len() as u16).to_be_bytes());
+    for _ in 0..inputs {
+        mft2.extend(input_curve.iter().flat_map(|val| val.to_be_bytes()));
+    }
+    for point in grid_points(inputs,grid) {
+        let values = clut(&point);
+        assert_eq!(values.len(),outputs);
+        mft2.extend(values.iter().flat_map(|val| val.to_be_bytes()));
+    }
+    for _ in 0..outputs {
+        mft2.extend(output_curve.iter().flat_map(|val| val.to_be_bytes()));
+    }
+    mft2
+}
+
+/// lut8_fixture makes a mft1 body with the identity matrix and identity 256 entry curves
+pub(crate) fn lut8_fixture(inputs:usize,outputs:usize,grid:usize,clut:&dyn Fn(&[f64]) -> Vec<u8>) -> Vec<u8> {
+    let mut mft1 = vec![inputs as u8,outputs as u8,grid as u8,0];
+    mft1.extend_from_slice(&identity_matrix());
+    for _ in 0..inputs {
+        mft1.extend(0..=255_u8);
+    }
+    for point in grid_points(inputs,grid) {
+        let values = clut(&point);
+        assert_eq!(values.len(),outputs);
+        mft1.extend_from_slice(&values);
+    }
+    for _ in 0..outputs {
+        mft1.extend(0..=255_u8);
+    }
+    mft1
+}
+
+/// mab_fixture makes a mAB body of identity A curves, a 16 bit clut and identity B curves
+pub(crate) fn mab_fixture(inputs:usize,outputs:usize,grid:usize,clut:&dyn Fn(&[f64]) -> Vec<u16>) -> Vec<u8> {
+    let identity = tag("para",&[0,0,0,0,0,1,0,0]);
+    // offsets count from the type signature, the body starts at 8
+    let a_curves = 32;
+    let clut_offset = a_curves + inputs * identity.len();
+    let mut table = vec![];
+    for point in grid_points(inputs,grid) {
+        let values = clut(&point);
+        assert_eq!(values.len(),outputs);
+        table.extend(values.iter().flat_map(|val| val.to_be_bytes()));
+    }
+    table.resize((table.len() + 3) & !3,0);
+    let b_curves = clut_offset + 20 + table.len();
+    let mut mab = vec![inputs as u8,outputs as u8,0,0];
+    for offset in [b_curves,0,0,clut_offset,a_curves] {
+        mab.extend_from_slice(&(offset as u32).to_be_bytes());
+    }
+    for _ in 0..inputs {
+        mab.extend_from_slice(&identity);
+    }
+    let mut grid_points = [0_u8;16];
+    grid_points[..inputs].fill(grid as u8);
+    mab.extend_from_slice(&grid_points);
+    mab.extend_from_slice(&[2,0,0,0]);
+    mab.extend_from_slice(&table);
+    for _ in 0..outputs {
+        mab.extend_from_slice(&identity);
+    }
+    mab
+}
+
+/// floats encodes float32Number values
+pub(crate) fn floats(vals:&[f32]) -> Vec<u8> {
+    vals.iter().flat_map(|val| val.to_be_bytes()).collect()
+}
+
+/// segmented_curve is a curf body: 0 for x <= 0, samples 0.5, 1.0 on (0,1], 2x - 1 for x > 1
+pub(crate) fn segmented_curve() -> Vec<u8> {
+    let mut curf = b"curf".to_vec();
+    curf.extend_from_slice(&[0,0,0,0,0,3,0,0]);
+    curf.extend_from_slice(&floats(&[0.0,1.0]));
+    curf.extend_from_slice(b"parf\0\0\0\0\0\0\0\0");
+    curf.extend_from_slice(&floats(&[1.0,1.0,0.0,0.0]));
+    curf.extend_from_slice(b"samf\0\0\0\0\0\0\0\x02");
+    curf.extend_from_slice(&floats(&[0.5,1.0]));
+    curf.extend_from_slice(b"parf\0\0\0\0\0\0\0\0");
+    curf.extend_from_slice(&floats(&[1.0,2.0,-1.0,0.0]));
+    curf
+}
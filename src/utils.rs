@@ -3,31 +3,56 @@ use crate::DecodedICCProfile;
 use bin_rs::io::*;
 use crate::ICCProfile;
 use std::fs::File;
+use std::path::Path;
 use std::io::Result;
 use std::io::{Error,ErrorKind};
 use std::io::prelude::*;
 
 pub(crate) fn bound_check(buf:&[u8],ptr:usize,size:usize) -> Result<()> {
-    if buf.len() < ptr + size {
-        let string = format!("Outbound check error len {} but index {}",buf.len(),ptr+size);
-        return Err(Error::new(ErrorKind::Other,string))
+    match ptr.checked_add(size) {
+        Some(end) if end <= buf.len() => Ok(()),
+        Some(end) => {
+            let string = format!("Outbound check error len {} but index {}",buf.len(),end);
+            Err(Error::new(ErrorKind::InvalidData,string))
+        },
+        None => {
+            let string = format!("Outbound check error len {} but index overflow {}+{}",buf.len(),ptr,size);
+            Err(Error::new(ErrorKind::InvalidData,string))
+        }
     }
+}
 
+/// limit_check returns an error when a value read from a profile exceeds a decode limit
+pub(crate) fn limit_check(name:&str,value:usize,limit:usize) -> Result<()> {
+    if value > limit {
+        let string = format!("{} {} exceeds limit {}",name,value,limit);
+        return Err(Error::new(ErrorKind::InvalidData,string))
+    }
     Ok(())
 }
 
-pub fn dump(filename:String,buf:&mut [u8]) -> Result<()>  {
+/// read_u128_be of bin_rs 0.0.7 reads the first 8 bytes twice
+pub(crate) fn read_u128(buf:&[u8],ptr:usize) -> u128 {
+    (read_u64_be(buf,ptr) as u128) << 64 | read_u64_be(buf,ptr + 8) as u128
+}
+
+/// read_to_limit reads a reader to the end, failing when more than `limit` bytes are read
+pub(crate) fn read_to_limit<R:Read>(reader:R,limit:usize) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.take((limit as u64).saturating_add(1)).read_to_end(&mut buf)?;
+    limit_check("file size",buf.len(),limit)?;
+    Ok(buf)
+}
+
+pub fn dump<P:AsRef<Path>>(filename:P,buf:&[u8]) -> Result<()>  {
     let mut file = File::create(filename)?;
     file.write_all(buf)?;
     file.flush()?;
     Ok(())
 }
 
-pub fn load(filename:String) -> Result<ICCProfile> {
-    let mut file = File::open(filename)?;
-    let mut buf = vec![];
-    file.read_to_end(&mut buf)?;
-    ICCProfile::new(&buf)
+pub fn load<P:AsRef<Path>>(filename:P) -> Result<ICCProfile> {
+    ICCProfile::open(filename)
 }
 
 pub fn icc_profile_header_print(header: &ICCProfile) -> String {
@@ -38,7 +63,7 @@ pub fn icc_profile_header_print(header: &ICCProfile) -> String {
     str += &format!("Color Space {}\n",read_ascii_string(&header.color_space.to_be_bytes(),0,4));
     str += &format!("PCS {}\n",read_ascii_string(&header.pcs.to_be_bytes(),0,4));
     str += &format!("DATE {}\n",header.create_date);
-    str += &format!("It MUST be 'ascp' {}\n",read_ascii_string(&header.magicnumber_ascp.to_be_bytes(),0,4));
+    str += &format!("It MUST be 'acsp' {}\n",read_ascii_string(&header.magicnumber_ascp.to_be_bytes(),0,4));
     str += &format!("Platform {}\n",read_ascii_string(&header.platform.to_be_bytes(),0,4));
     str += &format!("flags {}\n",&header.flags);
     str += &format!("manufacturer {}\n",read_ascii_string(&header.manufacturer.to_be_bytes(),0,4));
@@ -46,7 +71,7 @@ pub fn icc_profile_header_print(header: &ICCProfile) -> String {
     str += &format!("Attributes {:>064b}\n",&header.attributes);
     str += &format!("Illiuminate X:{} Y:{} Z:{}\n",&header.illuminate.x.as_f64(),&header.illuminate.y.as_f64(),&header.illuminate.z.as_f64());
     str += &format!("Creator {}\n",read_ascii_string(&header.creator.to_be_bytes(),0,4));
-    str += &format!("Profile ID (MD5 {:016x})\n",&header.profile_id);
+    str += &format!("Profile ID (MD5 {:032x})\n",&header.profile_id);
     str += &format!("Data length {}bytes\n",&header.length);
     str
 }
@@ -68,7 +93,7 @@ pub fn decoded_print(header :&DecodedICCProfile,verbose:usize) -> Result<String>
     str += &format!("Color Space {}\n",read_ascii_string(&header.color_space.to_be_bytes(),0,4));
     str += &format!("PCS {}\n",read_ascii_string(&header.pcs.to_be_bytes(),0,4));
     str += &format!("DATE {}\n",header.create_date);
-    str += &format!("It MUST be 'ascp' {}\n",read_ascii_string(&header.magicnumber_ascp.to_be_bytes(),0,4));
+    str += &format!("It MUST be 'acsp' {}\n",read_ascii_string(&header.magicnumber_ascp.to_be_bytes(),0,4));
     str += &format!("Platform {}\n",read_ascii_string(&header.platform.to_be_bytes(),0,4));
     str += &format!("flags {}\n",&header.flags);
     str += &format!("manufacturer {}\n",read_ascii_string(&header.manufacturer.to_be_bytes(),0,4));
@@ -80,8 +105,16 @@ pub fn decoded_print(header :&DecodedICCProfile,verbose:usize) -> Result<String>
     let z = header.illuminate.z.as_f64();
     str += &format!("Illiuminate X:{} Y:{} Z:{}\n",x,y,z);
     str += &format!("Creator {}\n",read_ascii_string(&header.creator.to_be_bytes(),0,4));
-    str += &format!("Profile ID (MD5 {:016x})\n",&header.profile_id);
+    str += &format!("Profile ID (MD5 {:032x})\n",&header.profile_id);
     str += &format!("Data length {}bytes\n",&header.length);
+    str += "\n==== Tag directory ====\n";
+    for entry in &header.directory {
+        str += &format!("{} {} offset {} size {}",entry.signature,entry.type_signature,entry.offset,entry.size);
+        if entry.is_shared() {
+            str += &format!(" shared with {}",entry.shared_with.join(","));
+        }
+        str += "\n";
+    }
     str += "\n==== ICC Profiles defined data ====\n";
     for (tag_name,val) in &header.tags {
         let tag_name = tag_name.to_string();
@@ -119,6 +152,15 @@ pub fn decoded_print(header :&DecodedICCProfile,verbose:usize) -> Result<String>
         str += &val.as_string(verbose);
         str += "\n";
     }
+    if !header.warnings.is_empty() {
+        str += "\n==== Warnings ====\n";
+        for warning in &header.warnings {
+            match &warning.tag {
+                Some(tag) => str += &format!("{:?} {}: {}\n",warning.kind,tag,warning.message),
+                None => str += &format!("{:?}: {}\n",warning.kind,warning.message),
+            }
+        }
+    }
     Ok(str)
 }
 
@@ -0,0 +1,433 @@
+//! Profile conformance validator
+//! validate checks profile data against ICC.1 v2 and v4 and returns findings with severity.
+//! ```
+//! use icc_profile::validator::*;
+//!
+//! fn intake(data:&[u8]) -> Result<(),String> {
+//!     let findings = validate(data);
+//!     if is_conforming(&findings) {
+//!         return Ok(())
+//!     }
+//!     let reasons :Vec<String> = findings.iter()
+//!         .filter(|finding| finding.severity == Severity::Error)
+//!         .map(|finding| finding.to_string())
+//!         .collect();
+//!     Err(reasons.join("\n"))
+//! }
+//! ```
+
+use std::fmt;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Severity {
+    /// not a violation, e.g. a private tag
+    Info,
+    /// accepted by most CMMs, but not recommended
+    Warning,
+    /// the profile does not conform
+    Error,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Check {
+    Header,
+    Magic,
+    Length,
+    Version,
+    Date,
+    Illuminant,
+    ProfileId,
+    TagTable,
+    Alignment,
+    OutOfRange,
+    Overlap,
+    DuplicateTag,
+    RequiredTag,
+    TagType,
+    VersionType,
+    PrivateTag,
+}
+
+#[derive(Debug,Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub check: Check,
+    /// tag signature when the finding is about a tag
+    pub tag: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity:Severity,check:Check,tag:Option<&str>,message:String) -> Self {
+        Self {
+            severity,
+            check,
+            tag: tag.map(|tag| tag.to_string()),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self,f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tag {
+            Some(tag) => write!(f,"{:?} {:?} {}: {}",self.severity,self.check,tag,self.message),
+            None => write!(f,"{:?} {:?}: {}",self.severity,self.check,self.message),
+        }
+    }
+}
+
+/// is_conforming returns true when there is no error finding
+pub fn is_conforming(findings:&[Finding]) -> bool {
+    findings.iter().all(|finding| finding.severity != Severity::Error)
+}
+
+fn signature_string(val:u32) -> String {
+    read_ascii_string(&val.to_be_bytes(),0,4)
+}
+
+/// allowed tag types for each tag signature, (v2 types, v4 types)
+pub(crate) fn allowed_types(signature:&str) -> Option<(&'static [&'static str],&'static [&'static str])> {
+    let types :(&[&str],&[&str]) = match signature {
+        "A2B0" | "A2B1" | "A2B2" => (&["mft1","mft2"],&["mft1","mft2","mAB "]),
+        "B2A0" | "B2A1" | "B2A2" | "gamt" => (&["mft1","mft2"],&["mft1","mft2","mBA "]),
+        "pre0" | "pre1" | "pre2" => (&["mft1","mft2"],&["mft1","mft2","mAB ","mBA "]),
+        "D2B0" | "D2B1" | "D2B2" | "D2B3" | "B2D0" | "B2D1" | "B2D2" | "B2D3" => (&[],&["mpet"]),
+        "rXYZ" | "gXYZ" | "bXYZ" | "wtpt" | "bkpt" | "lumi" => (&["XYZ "],&["XYZ "]),
+        "rTRC" | "gTRC" | "bTRC" | "kTRC" => (&["curv"],&["curv","para"]),
+        "desc" | "dmnd" | "dmdd" | "vued" => (&["desc"],&["mluc"]),
+        "cprt" => (&["text"],&["mluc"]),
+        "targ" => (&["text"],&["text"]),
+        "chad" => (&["sf32"],&["sf32"]),
+        "chrm" => (&["chrm"],&["chrm"]),
+        "clro" => (&["clro"],&["clro"]),
+        "clrt" | "clot" => (&["clrt"],&["clrt"]),
+        "meas" => (&["meas"],&["meas"]),
+        "view" => (&["view"],&["view"]),
+        "tech" | "rig0" | "rig2" | "ciis" => (&["sig "],&["sig "]),
+        "calt" => (&["dtim"],&["dtim"]),
+        "ncl2" => (&["ncl2"],&["ncl2"]),
+        "ncol" => (&["ncol"],&[]),
+        "pseq" => (&["pseq"],&["pseq"]),
+        "psid" => (&[],&["psid"]),
+        "resp" => (&[],&["rcs2"]),
+        "cicp" => (&[],&["cicp"]),
+        "meta" => (&[],&["dict"]),
+        "vcgt" => (&["vcgt"],&["vcgt"]),
+        "crdi" => (&["crdi"],&[]),
+        "devs" => (&["devs"],&[]),
+        "scrd" => (&["desc"],&[]),
+        "scrn" => (&["scrn"],&[]),
+        "bfd " => (&["bfd "],&[]),
+        "ps2s" | "ps2i" | "ps2r" | "psd0" | "psd1" | "psd2" | "psd3" => (&["data"],&[]),
+        _ => return Option::None,
+    };
+    Some(types)
+}
+
+/// required tags for each device class. Each inner slice lists alternatives, e.g. matrix/TRC or A2B0.
+fn required_tags(device_class:&str,color_space:&str,version:u32) -> Vec<Vec<&'static str>> {
+    let mut required :Vec<Vec<&str>> = vec![vec!["desc"],vec!["cprt"]];
+    if device_class != "link" {
+        required.push(vec!["wtpt"]);
+    }
+    let gray = color_space == "GRAY";
+    match device_class {
+        "scnr" | "mntr" => {
+            if gray {
+                required.push(vec!["kTRC","A2B0"]);
+            } else {
+                // matrix/TRC based or LUT based
+                required.push(vec!["rXYZ","A2B0"]);
+                required.push(vec!["gXYZ","A2B0"]);
+                required.push(vec!["bXYZ","A2B0"]);
+                required.push(vec!["rTRC","A2B0"]);
+                required.push(vec!["gTRC","A2B0"]);
+                required.push(vec!["bTRC","A2B0"]);
+            }
+        },
+        "prtr" => {
+            if gray {
+                required.push(vec!["kTRC","A2B0"]);
+            } else {
+                for tag in ["A2B0","A2B1","A2B2","B2A0","B2A1","B2A2","gamt"] {
+                    required.push(vec![tag]);
+                }
+            }
+        },
+        "link" => {
+            required.push(vec!["pseq"]);
+            required.push(vec!["A2B0"]);
+        },
+        "spac" => {
+            required.push(vec!["A2B0"]);
+            required.push(vec!["B2A0"]);
+        },
+        "abst" => {
+            required.push(vec!["A2B0"]);
+        },
+        "nmcl" => {
+            if version >= 0x04000000 {
+                required.push(vec!["ncl2"]);
+            } else {
+                required.push(vec!["ncl2","ncol"]);
+            }
+        },
+        _ => {},
+    }
+    required
+}
+
+fn days_of_month(year:u16,month:u16) -> u16 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// is_d50 checks the raw s15Fixed16Number XYZ values within 1 LSB
+pub(crate) fn is_d50(xyz:[i32;3]) -> bool {
+    let d50 = [0xf6d6,0x10000,0xd32d];
+    xyz.iter().zip(d50.iter()).all(|(val,d50)| (val - d50).abs() <= 1)
+}
+
+fn check_header(data:&[u8],findings:&mut Vec<Finding>) {
+    let magic = read_u32_be(data,36);
+    if magic != 0x61637370 {
+        findings.push(Finding::new(Severity::Error,Check::Magic,Option::None,
+            format!("profile file signature is '{}', it must be 'acsp'",signature_string(magic))));
+    }
+
+    let length = read_u32_be(data,0) as usize;
+    if length > data.len() {
+        findings.push(Finding::new(Severity::Error,Check::Length,Option::None,
+            format!("header length {} is larger than the data size {}",length,data.len())));
+    } else if length < data.len() {
+        findings.push(Finding::new(Severity::Warning,Check::Length,Option::None,
+            format!("header length {} is smaller than the data size {}",length,data.len())));
+    }
+    if !length.is_multiple_of(4) {
+        findings.push(Finding::new(Severity::Warning,Check::Length,Option::None,
+            format!("profile length {} is not a multiple of 4",length)));
+    }
+
+    let version = read_u32_be(data,8);
+    let major = version >> 24;
+    let minor = version >> 20 & 0xf;
+    let bugfix = version >> 16 & 0xf;
+    if major == 5 {
+        findings.push(Finding::new(Severity::Warning,Check::Version,Option::None,
+            format!("version {}.{}.{} (iccMAX) is unsupported, only 2.x and 4.x are checked",major,minor,bugfix)));
+    } else if major != 2 && major != 4 {
+        findings.push(Finding::new(Severity::Error,Check::Version,Option::None,
+            format!("version {}.{}.{} is not supported, only 2.x and 4.x are checked",major,minor,bugfix)));
+    }
+    if version & 0xffff != 0 {
+        findings.push(Finding::new(Severity::Warning,Check::Version,Option::None,
+            format!("reserved bytes of the version {:08x} are not zero",version)));
+    }
+
+    let date :Vec<u16> = (0..6).map(|i| read_u16_be(data,24 + i * 2)).collect();
+    let (year,month,day,hour,minute,second) = (date[0],date[1],date[2],date[3],date[4],date[5]);
+    if month == 0 || month > 12 || day == 0 || day > days_of_month(year,month) || hour > 23 || minute > 59 || second > 59 {
+        findings.push(Finding::new(Severity::Error,Check::Date,Option::None,
+            format!("creation date {:04}-{:02}-{:02} {:02}:{:02}:{:02} is not a valid date",year,month,day,hour,minute,second)));
+    }
+
+    // PCS illuminant must be D50 (0.9642, 1.0, 0.8249) as s15Fixed16Number
+    let illuminant = [read_i32_be(data,68),read_i32_be(data,72),read_i32_be(data,76)];
+    if !is_d50(illuminant) {
+        findings.push(Finding::new(Severity::Error,Check::Illuminant,Option::None,
+            format!("PCS illuminant X:{:.4} Y:{:.4} Z:{:.4} is not D50",
+                illuminant[0] as f64 / 65536.0,illuminant[1] as f64 / 65536.0,illuminant[2] as f64 / 65536.0)));
+    }
+
+    if length <= data.len() {
+        match verify_profile_id(data) {
+            Ok(Some(false)) => findings.push(Finding::new(Severity::Error,Check::ProfileId,Option::None,
+                "stored profile ID does not match the MD5 of the profile".to_string())),
+            Ok(Option::None) if major >= 4 => findings.push(Finding::new(Severity::Info,Check::ProfileId,Option::None,
+                "profile ID is not set".to_string())),
+            _ => {},
+        }
+    }
+}
+
+fn check_tags(data:&[u8],directory:&TagDirectory,findings:&mut Vec<Finding>) {
+    let length = (read_u32_be(data,0) as usize).min(data.len());
+    let version = read_u32_be(data,8);
+    let v4 = version >= 0x04000000;
+    let table_end = 132 + directory.len() * 12;
+
+    let mut blocks :Vec<(usize,usize,&str)> = vec![];
+    for (i,entry) in directory.iter().enumerate() {
+        let tag = Some(&*entry.signature);
+        let offset = entry.offset as usize;
+        let size = entry.size as usize;
+        if directory.entries[..i].iter().any(|other| other.signature == entry.signature) {
+            findings.push(Finding::new(Severity::Error,Check::DuplicateTag,tag,
+                "tag appears more than once in the tag table".to_string()));
+        }
+        if !offset.is_multiple_of(4) {
+            findings.push(Finding::new(Severity::Error,Check::Alignment,tag,
+                format!("tag offset {} is not 4 byte aligned",offset)));
+        }
+        if offset < table_end || offset.saturating_add(size) > length || size < 8 {
+            findings.push(Finding::new(Severity::Error,Check::OutOfRange,tag,
+                format!("tag data {}..{} is outside of the tag data area {}..{}",offset,offset.saturating_add(size),table_end,length)));
+            continue;
+        }
+        blocks.push((offset,size,&entry.signature));
+
+        let type_signature = &*entry.type_signature;
+        match allowed_types(&entry.signature) {
+            Some((v2_types,v4_types)) => {
+                let (types,other) = if v4 {(v4_types,v2_types)} else {(v2_types,v4_types)};
+                if !types.contains(&type_signature) {
+                    if other.contains(&type_signature) {
+                        findings.push(Finding::new(Severity::Error,Check::VersionType,tag,
+                            format!("type '{}' is not allowed in version {}, use {:?}",type_signature,version >> 24,types)));
+                    } else {
+                        findings.push(Finding::new(Severity::Error,Check::TagType,tag,
+                            format!("type '{}' is not allowed, use {:?}",type_signature,types)));
+                    }
+                }
+            },
+            Option::None => {
+                findings.push(Finding::new(Severity::Info,Check::PrivateTag,tag,
+                    format!("unknown or private tag with type '{}'",type_signature)));
+            }
+        }
+    }
+
+    // identical offset and size is a shared tag, any other intersection is an overlap
+    blocks.sort();
+    for (i,&(offset,size,signature)) in blocks.iter().enumerate() {
+        for &(other_offset,other_size,other) in &blocks[i + 1..] {
+            if other_offset >= offset + size {
+                break;
+            }
+            if other_offset != offset || other_size != size {
+                findings.push(Finding::new(Severity::Error,Check::Overlap,Some(other),
+                    format!("tag data {}..{} overlaps {} {}..{}",other_offset,other_offset + other_size,signature,offset,offset + size)));
+            }
+        }
+    }
+
+    let device_class = signature_string(read_u32_be(data,12));
+    let color_space = signature_string(read_u32_be(data,16));
+    for alternatives in required_tags(&device_class,&color_space,version) {
+        if !alternatives.iter().any(|tag| directory.get(tag).is_some()) {
+            findings.push(Finding::new(Severity::Error,Check::RequiredTag,Some(alternatives[0]),
+                format!("required tag is missing for device class '{}'{}",device_class,
+                    if alternatives.len() > 1 {format!(", alternatives {:?}",&alternatives[1..])} else {"".to_string()})));
+        }
+    }
+
+    // v4 display profiles with a media white other than D50 carry the adaptation to D50 in chad
+    if v4 && device_class == "mntr" && directory.get("chad").is_none() {
+        let white = directory.raw(data,"wtpt")
+            .filter(|raw| raw.len() >= 20 && &raw[0..4] == b"XYZ ")
+            .map(|raw| [read_i32_be(raw,8),read_i32_be(raw,12),read_i32_be(raw,16)]);
+        if white.is_some_and(|white| !is_d50(white)) {
+            findings.push(Finding::new(Severity::Error,Check::RequiredTag,Some("chad"),
+                "chad is missing and the media white point is not D50".to_string()));
+        }
+    }
+}
+
+/// validate checks profile data and returns all findings. Broken data is reported, never panics.
+pub fn validate(data:&[u8]) -> Vec<Finding> {
+    let mut findings = vec![];
+    if data.len() < 132 {
+        findings.push(Finding::new(Severity::Error,Check::Header,Option::None,
+            format!("profile data size {} is smaller than the header and the tag count",data.len())));
+        return findings
+    }
+    check_header(data,&mut findings);
+    match TagDirectory::new(data) {
+        Ok(directory) => check_tags(data,&directory,&mut findings),
+        Err(err) => findings.push(Finding::new(Severity::Error,Check::TagTable,Option::None,
+            format!("tag table is broken: {}",err))),
+    }
+    findings
+}
+
+impl ICCProfile {
+    /// validate checks the profile data against the ICC specification
+    pub fn validate(&self) -> Vec<Finding> {
+        validate(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn validate_profiles() {
+        let signature = |s:&[u8;4]| u32::from_be_bytes(*s);
+        let mut builder = crate::ICCProfileBuilder::new(signature(b"mntr"),signature(b"GRAY"),signature(b"XYZ "));
+        builder.tag("desc",&Data::ASCII("gray".to_string()),"mluc").unwrap()
+            .tag("cprt",&Data::ASCII("public domain".to_string()),"mluc").unwrap()
+            .tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9642,1.0,0.8249)),"XYZ ").unwrap()
+            .tag("kTRC",&Data::Curve(vec![0x0233]),"curv").unwrap();
+        let identity = [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0].iter().map(|val| S15Fixed16Number::from_f64(*val)).collect();
+        builder.tag("chad",&Data::S15Fixed16NumberArray(identity),"sf32").unwrap();
+        let data = builder.build().unwrap();
+        let findings = validate(&data);
+        assert!(is_conforming(&findings),"{:?}",findings);
+
+        let checks = |data:&[u8]| -> Vec<Check> {
+            validate(data).iter().filter(|f| f.severity == Severity::Error).map(|f| f.check).collect()
+        };
+        let mut broken = data.clone();
+        broken[36..40].copy_from_slice(b"ascp");
+        broken[70] = 0;
+        broken[26..28].copy_from_slice(&13_u16.to_be_bytes());
+        broken[84] ^= 1;
+        let errors = checks(&broken);
+        for check in [Check::Magic,Check::Illuminant,Check::Date,Check::ProfileId] {
+            assert!(errors.contains(&check),"{:?}",check);
+        }
+
+        // v4 types in a v2 profile, then v2 types in v4, missing, unaligned and overlapping tags
+        let errors = checks(&sample());
+        for check in [Check::VersionType,Check::Date,Check::Illuminant] {
+            assert!(errors.contains(&check),"{:?}",check);
+        }
+        let mut builder = crate::ICCProfileBuilder::from_bytes(&data).unwrap();
+        builder.tag("desc",&Data::ASCII("gray".to_string()),"desc").unwrap().remove_tag("cprt");
+        let mut broken = builder.build().unwrap();
+        let errors = checks(&broken);
+        assert!(errors.contains(&Check::VersionType));
+        assert!(errors.contains(&Check::RequiredTag));
+        let offset = read_u32_be(&broken,132 + 4) + 2;
+        broken[132 + 4..132 + 8].copy_from_slice(&offset.to_be_bytes());
+        let errors = checks(&broken);
+        assert!(errors.contains(&Check::Alignment));
+        assert!(errors.contains(&Check::Overlap));
+        assert!(checks(&broken[..100]).contains(&Check::Header));
+
+        // v4 displays need chad unless wtpt is D50, iccMAX is only a warning, ucrbg uses the 'bfd ' type
+        let mut builder = crate::ICCProfileBuilder::from_bytes(&data).unwrap();
+        builder.remove_tag("chad");
+        assert!(is_conforming(&validate(&builder.build().unwrap())));
+        builder.tag("wtpt",&Data::XYZNumber(XYZNumber::from_f64(0.9505,1.0,1.0891)),"XYZ ").unwrap();
+        let missing = validate(&builder.build().unwrap());
+        assert!(missing.iter().any(|f| f.check == Check::RequiredTag && f.tag.as_deref() == Some("chad")),"{:?}",missing);
+        let mut iccmax = data.clone();
+        iccmax[8..12].copy_from_slice(&0x05000000_u32.to_be_bytes());
+        let findings = validate(&iccmax);
+        assert!(findings.iter().any(|f| f.check == Check::Version && f.severity == Severity::Warning));
+        assert!(!checks(&iccmax).contains(&Check::Version));
+        let ucrbg = profile(&[("bfd ",tag("bfd ",&[0,0,0,1,0,0x80,0,0,0,1,0,0x80,b'x',0]))]);
+        assert!(validate(&ucrbg).iter().all(|f| f.check != Check::TagType),"{:?}",validate(&ucrbg));
+    }
+}
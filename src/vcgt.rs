@@ -0,0 +1,235 @@
+//! Video card gamma 'vcgt' (Apple private tag, written by DisplayCAL and ArgyllCMS)
+//! The calibration curves loaded into the display LUT, as a table or a gamma formula per channel.
+//! ```
+//! use icc_profile::*;
+//!
+//! fn calibrate(decoded:&DecodedICCProfile,rgb:&mut [u8]) {
+//!     if let Some(vcgt) = decoded.vcgt() {
+//!         vcgt.apply_u8(rgb,3);
+//!     }
+//! }
+//! ```
+
+use std::io::{Error,ErrorKind};
+use std::io::Result;
+use bin_rs::io::*;
+use crate::iccprofile::*;
+use crate::utils::bound_check;
+
+/// value = min + (max - min) * input ^ gamma
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct VcgtFormula {
+    pub gamma: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for VcgtFormula {
+    fn default() -> Self {
+        Self { gamma: 1.0,min: 0.0,max: 1.0 }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum VideoCardGammaType {
+    /// gamma type 0, 1 or 3 channels of `entry_size` byte entries. Values are stored as read.
+    Table {
+        entry_size: u16,
+        channels: Vec<Vec<u16>>,
+    },
+    /// gamma type 1, red, green and blue
+    Formula([VcgtFormula;3]),
+}
+
+fn s15fixed16(data:&[u8],ptr:usize) -> f64 {
+    read_i32_be(data,ptr) as f64 / 65536.0
+}
+
+impl VideoCardGammaType {
+    /// identity returns a linear 256 entry 16 bit table
+    pub fn identity() -> Self {
+        let curve :Vec<u16> = (0..256).map(|i| i * 257).collect();
+        VideoCardGammaType::Table { entry_size: 2,channels: vec![curve.clone(),curve.clone(),curve] }
+    }
+
+    /// from_curves makes a 16 bit table from 1 or 3 curves
+    pub fn from_curves(channels:Vec<Vec<u16>>) -> Result<Self> {
+        if !(channels.len() == 1 || channels.len() == 3)
+                || channels.iter().any(|curve| curve.len() < 2 || curve.len() != channels[0].len() || curve.len() > u16::MAX as usize) {
+            return Err(Error::new(ErrorKind::InvalidInput,"vcgt needs 1 or 3 curves of the same length, 2 to 65535 entries"))
+        }
+        Ok(VideoCardGammaType::Table { entry_size: 2,channels })
+    }
+
+    /// read reads tag data starting with the type signature
+    pub(crate) fn read(data:&[u8]) -> Result<Self> {
+        bound_check(data,8,4)?;
+        match read_u32_be(data,8) {
+            0 => {
+                bound_check(data,12,6)?;
+                let count = read_u16_be(data,12) as usize;
+                let entries = read_u16_be(data,14) as usize;
+                let entry_size = read_u16_be(data,16);
+                if !(count == 1 || count == 3) || !(entry_size == 1 || entry_size == 2) {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                        format!("vcgt table has {} channels of {} byte entries",count,entry_size)))
+                }
+                bound_check(data,18,count * entries * entry_size as usize)?;
+                let mut ptr = 18;
+                let mut channels = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let mut curve = Vec::with_capacity(entries);
+                    for _ in 0..entries {
+                        if entry_size == 1 {
+                            curve.push(data[ptr] as u16);
+                        } else {
+                            curve.push(read_u16_be(data,ptr));
+                        }
+                        ptr += entry_size as usize;
+                    }
+                    channels.push(curve);
+                }
+                Ok(VideoCardGammaType::Table { entry_size,channels })
+            },
+            1 => {
+                bound_check(data,12,36)?;
+                let mut formula = [VcgtFormula::default();3];
+                for (i,channel) in formula.iter_mut().enumerate() {
+                    let ptr = 12 + i * 12;
+                    *channel = VcgtFormula {
+                        gamma: s15fixed16(data,ptr),
+                        min: s15fixed16(data,ptr + 4),
+                        max: s15fixed16(data,ptr + 8),
+                    };
+                }
+                Ok(VideoCardGammaType::Formula(formula))
+            },
+            gamma_type => Err(Error::new(ErrorKind::InvalidData,format!("vcgt gamma type {}",gamma_type))),
+        }
+    }
+
+    /// write appends the tag body after the type signature
+    pub(crate) fn write(&self,buf:&mut Vec<u8>) -> Result<()> {
+        match self {
+            VideoCardGammaType::Table { entry_size,channels } => {
+                let entries = channels.first().map(|curve| curve.len()).unwrap_or(0);
+                if !(channels.len() == 1 || channels.len() == 3) || channels.iter().any(|curve| curve.len() != entries)
+                        || entries > u16::MAX as usize || !(*entry_size == 1 || *entry_size == 2) {
+                    return Err(Error::new(ErrorKind::InvalidInput,"vcgt table must have 1 or 3 curves of the same length"))
+                }
+                write_u32_be(0,buf);
+                write_u16_be(channels.len() as u16,buf);
+                write_u16_be(entries as u16,buf);
+                write_u16_be(*entry_size,buf);
+                for val in channels.iter().flatten() {
+                    if *entry_size == 1 {
+                        buf.push((*val).min(255) as u8);
+                    } else {
+                        write_u16_be(*val,buf);
+                    }
+                }
+            },
+            VideoCardGammaType::Formula(formula) => {
+                write_u32_be(1,buf);
+                for channel in formula {
+                    for val in [channel.gamma,channel.min,channel.max] {
+                        buf.extend_from_slice(&S15Fixed16Number::from_f64(val).to_be_bytes());
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// eval maps an input 0.0-1.0 of channel 0 (red), 1 (green) or 2 (blue) to 0.0-1.0.
+    /// Tables are interpolated linearly, a single channel table is used for every channel.
+    pub fn eval(&self,channel:usize,val:f64) -> f64 {
+        let val = val.clamp(0.0,1.0);
+        match self {
+            VideoCardGammaType::Table { entry_size,channels } => {
+                let curve = match channels.get(channel).or(channels.first()) {
+                    Some(curve) if !curve.is_empty() => curve,
+                    _ => return val,
+                };
+                let max = if *entry_size == 1 { 255.0 } else { 65535.0 };
+                let pos = val * (curve.len() - 1) as f64;
+                let i = (pos.floor() as usize).min(curve.len() - 1);
+                let j = (i + 1).min(curve.len() - 1);
+                let frac = pos - i as f64;
+                (curve[i] as f64 * (1.0 - frac) + curve[j] as f64 * frac) / max
+            },
+            VideoCardGammaType::Formula(formula) => {
+                let channel = formula[channel.min(2)];
+                (channel.min + (channel.max - channel.min) * val.powf(channel.gamma)).clamp(0.0,1.0)
+            },
+        }
+    }
+
+    /// lookup_tables returns red, green and blue curves of `size` 16 bit entries
+    pub fn lookup_tables(&self,size:usize) -> [Vec<u16>;3] {
+        let curve = |channel:usize| (0..size)
+            .map(|i| (self.eval(channel,i as f64 / (size.max(2) - 1) as f64) * 65535.0).round() as u16)
+            .collect();
+        [curve(0),curve(1),curve(2)]
+    }
+
+    /// apply_u8 applies the curves to the first three samples of each pixel
+    pub fn apply_u8(&self,buf:&mut [u8],samples_per_pixel:usize) {
+        let tables = self.lookup_tables(256);
+        for pixel in buf.chunks_exact_mut(samples_per_pixel.max(1)) {
+            for (val,table) in pixel.iter_mut().zip(tables.iter()) {
+                *val = ((table[*val as usize] as u32 * 255 + 32767) / 65535) as u8;
+            }
+        }
+    }
+
+    /// apply_u16 applies the curves to the first three samples of each pixel
+    pub fn apply_u16(&self,buf:&mut [u16],samples_per_pixel:usize) {
+        for pixel in buf.chunks_exact_mut(samples_per_pixel.max(1)) {
+            for (channel,val) in pixel.iter_mut().take(3).enumerate() {
+                *val = (self.eval(channel,*val as f64 / 65535.0) * 65535.0).round() as u16;
+            }
+        }
+    }
+}
+
+impl DecodedICCProfile {
+    /// vcgt returns the calibration curves of the vcgt tag
+    pub fn vcgt(&self) -> Option<&VideoCardGammaType> {
+        match self.tags.get("vcgt") {
+            Some(Data::VideoCardGamma(vcgt)) => Some(vcgt),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_card_gamma() {
+        let formula = VideoCardGammaType::Formula([VcgtFormula { gamma: 2.25,min: 0.0,max: 1.0 },
+            VcgtFormula::default(),VcgtFormula { gamma: 1.0,min: 0.0,max: 0.5 }]);
+        let table = VideoCardGammaType::from_curves(vec![vec![0,0x4000,0xffff]]).unwrap();
+        for vcgt in [formula,table] {
+            let mut builder = crate::ICCProfileBuilder::new(u32::from_be_bytes(*b"mntr"),u32::from_be_bytes(*b"RGB "),u32::from_be_bytes(*b"XYZ "));
+            builder.tag("vcgt",&Data::VideoCardGamma(vcgt.clone()),"vcgt").unwrap();
+            let data = builder.build().unwrap();
+            let decoded = DecodedICCProfile::new(&data).unwrap();
+            assert_eq!(decoded.vcgt(),Some(&vcgt));
+            assert!(crate::validator::validate(&data).iter().all(|finding| finding.check != crate::validator::Check::TagType));
+        }
+        let formula = VideoCardGammaType::Formula([VcgtFormula { gamma: 2.0,min: 0.0,max: 1.0 },
+            VcgtFormula::default(),VcgtFormula { gamma: 1.0,min: 0.0,max: 0.5 }]);
+        assert!((formula.eval(0,0.5) - 0.25).abs() < 1e-9);
+        let mut rgba = [255,128,255,7];
+        formula.apply_u8(&mut rgba,4);
+        assert_eq!(rgba,[255,128,128,7]);
+        let table = VideoCardGammaType::from_curves(vec![vec![0,0x4000,0xffff]]).unwrap();
+        assert!((table.eval(2,0.25) - 0x2000 as f64 / 65535.0).abs() < 1e-9);
+        let mut rgb = [0xffff,0x8000,0];
+        VideoCardGammaType::identity().apply_u16(&mut rgb,3);
+        assert_eq!(rgb,[0xffff,0x8000,0]);
+    }
+}